@@ -1,10 +1,12 @@
+use chrono::Utc;
 use std::path::Path;
 use tokio::fs;
 
 use crate::{
     diff, helm,
     kubeapi::ShipKube,
-    kubectl, track,
+    kubectl, registry,
+    track::{self, RolloutDiagnosis, WorkloadResult},
     webhooks::{self, UpgradeState},
 };
 use serde_json::json;
@@ -12,11 +14,36 @@ use serde_json::json;
 use shipcat_definitions::{
     status::{make_date, Condition},
     structs::{Metadata, NotificationMode},
-    Config, Manifest, PrimaryWorkload, ReconciliationMode, Region,
+    Config, DiffIgnore, Manifest, PrimaryWorkload, ReconciliationMode, Region, RegistryConfig,
 };
 
 use super::{ErrorKind, Result, ResultExt};
 
+/// Fill in image size and digest from the registry
+///
+/// The digest is only set when the registry pins digests.
+/// Images hosted on other registries are left alone with a warning.
+/// Diffs need this as well, so that pinned images render the same as on apply.
+pub async fn resolve_image(mf: &mut Manifest, registry: &RegistryConfig) -> Result<()> {
+    let (image, version) = match (&mf.image, &mf.version) {
+        (Some(i), Some(v)) => (i, v),
+        _ => bail!("{} needs an image and a version to look up", mf.name),
+    };
+    if registry::repository(image, &registry.url).is_none() {
+        warn!("{} is not hosted on {}, not resolving its digest", image, registry.url);
+        return Ok(());
+    }
+    let info = match registry::lookup(registry, image, version).await? {
+        Some(info) => info,
+        None => bail!("Tag {} of {} not found in {}", version, image, registry.url),
+    };
+    mf.imageSize = Some(info.size_mb());
+    if registry.pinDigests {
+        mf.imageDigest = Some(info.digest);
+    }
+    Ok(())
+}
+
 /// Information from an upgrade
 ///
 /// This information is generated by apply on a best-effort basis.
@@ -37,6 +64,8 @@ pub struct UpgradeInfo {
     pub namespace: String,
     /// Computed diff string (if available)
     pub diff: Option<String>,
+    /// Classified cause of a failed rollout (if available)
+    pub failure: Option<RolloutDiagnosis>,
 }
 
 impl UpgradeInfo {
@@ -58,6 +87,7 @@ impl UpgradeInfo {
             region: mf.region.clone(),
             namespace: mf.namespace.clone(),
             diff: None,
+            failure: None,
         }
     }
 }
@@ -249,6 +279,24 @@ async fn apply_kubectl(
         }
     };
 
+    // Use the registry for image sizes and (optionally) pinned digests
+    if let Some(registry) = &region.registry {
+        match resolve_image(&mut mf, registry).await {
+            Ok(()) => {
+                if let Some(digest) = &mf.imageDigest {
+                    s.update_image_digest(digest).await?;
+                }
+            }
+            Err(e) if registry.pinDigests => {
+                webhooks::apply_event(UpgradeState::Failed, &ui, &region, &conf).await;
+                s.update_generate_false("RegistryFailure", e.description().to_string())
+                    .await?;
+                return Err(e);
+            }
+            Err(e) => warn!("Failed to look up the image of {}: {}", svc, e),
+        }
+    }
+
     // Create completed kubernetes yaml (via shipcat values | helm template)
     let tfile = format!("{}.kube.gen.yml", svc);
     let tpth = Path::new(".").join(tfile.clone());
@@ -263,7 +311,7 @@ async fn apply_kubectl(
     // Attach diff to UpgradeInfo if diffing is possible
     if can_diff {
         // helm diff only supports diffing if already installed..
-        match diff_kubectl(&mf, &tfile).await {
+        match diff_kubectl(&mf, &tfile, &conf.diff_ignore(&region)).await {
             Ok(Some(kdiff)) => {
                 ui.diff = Some(kdiff);
                 reason = reason.or(Some(UpgradeReason::TemplateDiff));
@@ -296,6 +344,7 @@ async fn apply_kubectl(
     webhooks::apply_event(UpgradeState::Started, &ui, &region, &conf).await;
     s.update_generate_true().await?; // if this fails, stop, want .status to be correct
 
+    let started = Utc::now(); // events before this belong to previous rollouts
     match upgrade_kubectl(&mf, &tfile).await {
         Err(e) => {
             error!("{} from {}", e, ui.name);
@@ -310,29 +359,36 @@ async fn apply_kubectl(
             if !wait {
                 info!("successfully applied {} (without waiting)", ui.name);
             } else {
-                match track::workload_rollout(&mf, &s).await {
-                    Ok(true) => {
-                        info!("successfully rolled out {}", &ui.name);
-                        webhooks::apply_event(UpgradeState::Completed, &ui, &region, &conf).await;
-                        s.update_rollout_true(&actual_version).await?;
+                let results = track::manifest_rollout(&mf, &s, &started).await;
+                if let Err(e) = s.update_workloads(&results).await {
+                    warn!("Failed to update workload status for {}: {}", &ui.name, e);
+                }
+                let failed = results.iter().filter(|r| !r.is_ok()).collect::<Vec<_>>();
+                if failed.is_empty() {
+                    info!("successfully rolled out {}", &ui.name);
+                    webhooks::apply_event(UpgradeState::Completed, &ui, &region, &conf).await;
+                    s.update_rollout_true(&actual_version).await?;
+                } else {
+                    for f in &failed {
+                        warn!("{} failed: {}", f.workload, f.message.clone().unwrap_or_default());
                     }
-                    Ok(false) => {
+                    // the first failure is the main workload if that failed
+                    let first = failed[0];
+                    let err = first.reason.clone().expect("failed workloads have a reason");
+                    let reason = format!(
+                        "{}: {}",
+                        first.workload,
+                        first.message.clone().unwrap_or_default()
+                    );
+                    ui.failure = failed.iter().find_map(|f| f.diagnosis.clone());
+                    warn!("failed to roll out {}", &ui.name);
+                    webhooks::apply_event(UpgradeState::Failed, &ui, &region, &conf).await;
+                    s.update_rollout_false(&err, reason).await?; // TODO: chain
+                    if first.is_timeout() {
                         let time = mf.estimate_wait_time();
-                        let reason = format!("timed out waiting {}s for rollout", time);
-                        //let _ = kubectl::debug_rollout_status(&mf).await;
-                        let _ = track::debug(&mf, &s).await;
-                        // TODO: collect these for .status call ^?
-                        warn!("failed to roll out {}", &ui.name);
-                        webhooks::apply_event(UpgradeState::Failed, &ui, &region, &conf).await;
-                        s.update_rollout_false("Timeout", reason).await?; // TODO: chain
                         return Err(ErrorKind::UpgradeTimeout(mf.name.clone(), time).into());
                     }
-                    Err(e) => {
-                        webhooks::apply_event(UpgradeState::Failed, &ui, &region, &conf).await;
-                        s.update_rollout_false("RolloutTrackFailure", e.description().to_string())
-                            .await?; // TODO: chain
-                        return Err(e);
-                    }
+                    return Err(ErrorKind::WorkloadFailure(mf.name.clone(), first.workload.clone()).into());
                 }
             }
         }
@@ -366,10 +422,12 @@ async fn upgrade_kubectl(mf: &Manifest, tfile: &str) -> Result<()> {
 /// Minified kubectl diff shell out
 ///
 /// Requires kubernetes 1.13
-pub async fn diff_kubectl(mf: &Manifest, tfile: &str) -> Result<Option<String>> {
+/// Changes covered by the ignore rules do not count as a diff.
+pub async fn diff_kubectl(mf: &Manifest, tfile: &str, ignore: &DiffIgnore) -> Result<Option<String>> {
     let namespace = mf.namespace.clone();
     let pth = Path::new(tfile);
-    let (kdiffunobfusc, kdifferr, success) = kubectl::diff(pth.to_path_buf(), &namespace).await?;
+    let (kdiffunobfusc, kdifferr, success) =
+        kubectl::diff(pth.to_path_buf(), &namespace, !ignore.is_empty()).await?;
 
     let kubediff = diff::obfuscate_secrets(
         kdiffunobfusc, // move this away quickly..
@@ -389,7 +447,7 @@ pub async fn diff_kubectl(mf: &Manifest, tfile: &str) -> Result<Option<String>>
         }
     }
 
-    let smalldiff = diff::minify(&kubediff);
+    let smalldiff = diff::minify(&diff::filter_ignored(&kubediff, ignore));
     Ok(if !smalldiff.is_empty() {
         debug!("{}", kubediff); // full diff for logs
         println!("{}", smalldiff);
@@ -403,6 +461,7 @@ pub async fn diff_kubectl(mf: &Manifest, tfile: &str) -> Result<Option<String>>
 ///
 /// Optionally wait for the main resource
 pub async fn restart(mf: &Manifest, wait: bool) -> Result<()> {
+    let started = Utc::now(); // events before this belong to previous rollouts
     for w in &mf.workers {
         let r = Restartable {
             name: w.container.name.clone(),
@@ -426,18 +485,22 @@ pub async fn restart(mf: &Manifest, wait: bool) -> Result<()> {
         return Ok(());
     }
     let sk = ShipKube::new(&mf).await?;
-    // wait for primary if we are waiting
-    if track::workload_rollout(&mf, &sk).await? {
+    // wait for primary and workers if we are waiting
+    let results = track::manifest_rollout(&mf, &sk, &started).await;
+    let failed = results.iter().filter(|r| !r.is_ok()).collect::<Vec<_>>();
+    if failed.is_empty() {
         info!("successfully restarted {}/{}", mf.workload.to_string(), &mf.name);
         Ok(())
     } else {
-        let time = mf.estimate_wait_time();
-        let reason = format!("timed out waiting {}s for rollout to restart", time);
-        //let _ = kubectl::debug_rollout_status(&mf).await;
-        let _ = track::debug(&mf, &sk).await;
         warn!("failed to roll out {}", &mf.name);
-        warn!("{}", reason);
-        Err(ErrorKind::UpgradeTimeout(mf.name.clone(), time).into())
+        for f in &failed {
+            warn!("{} failed: {}", f.workload, f.message.clone().unwrap_or_default());
+        }
+        if failed[0].is_timeout() {
+            let time = mf.estimate_wait_time();
+            return Err(ErrorKind::UpgradeTimeout(mf.name.clone(), time).into());
+        }
+        Err(ErrorKind::WorkloadFailure(mf.name.clone(), failed[0].workload.clone()).into())
     }
 }
 
@@ -617,6 +680,43 @@ impl ShipKube {
         self.patch(&data).await
     }
 
+    pub async fn update_image_digest(&self, digest: &str) -> Result<()> {
+        debug!("Setting imageDigest");
+        let data = json!({
+            "status": {
+                "imageDigest": digest
+            }
+        });
+        self.patch(&data).await
+    }
+
+    pub async fn update_workloads(&self, results: &[WorkloadResult]) -> Result<()> {
+        debug!("Setting workloads");
+        let mut workloads = serde_json::Map::new();
+        // null out workloads that are no longer part of the manifest
+        if let Some(stat) = self.get_minimal().await?.status {
+            for k in stat.workloads.keys() {
+                workloads.insert(k.clone(), serde_json::Value::Null);
+            }
+        }
+        for r in results {
+            let cond = match &r.reason {
+                None => Condition {
+                    message: r.message.clone(),
+                    ..Condition::ok(&self.applier)
+                },
+                Some(err) => Condition::bad(&self.applier, err, r.message.clone().unwrap_or_default()),
+            };
+            workloads.insert(r.workload.clone(), serde_json::to_value(cond)?);
+        }
+        let data = json!({
+            "status": {
+                "workloads": workloads
+            }
+        });
+        self.patch(&data).await
+    }
+
     pub async fn update_rollout_true(&self, version: &str) -> Result<()> {
         debug!("Setting rolledout true");
         let now = make_date();
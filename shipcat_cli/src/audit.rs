@@ -96,6 +96,12 @@ struct DeploymentPayload {
     service: String,
     version: String,
     manifests_revision: String,
+    /// Classified rollout failure reason (failed rollouts only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failure_reason: Option<String>,
+    /// Explanation of the rollout failure (failed rollouts only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failure_message: Option<String>,
 }
 impl DeploymentPayload {
     fn new(whc: &WHC, info: &UpgradeInfo) -> Self {
@@ -105,6 +111,8 @@ impl DeploymentPayload {
             service: info.name.clone(),
             version: info.version.clone(),
             manifests_revision: whc["SHIPCAT_AUDIT_REVISION"].clone(),
+            failure_reason: info.failure.as_ref().map(|f| f.cause.reason().to_string()),
+            failure_message: info.failure.as_ref().map(|f| f.to_string()),
         }
     }
 }
@@ -224,6 +232,29 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn audit_deployment_includes_failure() {
+        use crate::track::{RolloutDiagnosis, RolloutFailure};
+        let mut whc: BTreeMap<String, String> = BTreeMap::default();
+        whc.insert("SHIPCAT_AUDIT_REVISION".into(), "egrevision".into());
+
+        let mut ud = UpgradeInfo::new(&Manifest::test("fake-svc"));
+        let pl = serde_json::to_value(audit::DeploymentPayload::new(&whc, &ud)).unwrap();
+        assert!(pl.get("failure_reason").is_none());
+
+        ud.failure = Some(RolloutDiagnosis {
+            cause: RolloutFailure::ImagePull,
+            object: "Pod/fake-svc-abc".into(),
+            message: "Back-off pulling image".into(),
+        });
+        let pl = serde_json::to_value(audit::DeploymentPayload::new(&whc, &ud)).unwrap();
+        assert_eq!(pl["failure_reason"], "ImagePullBackOff");
+        assert_eq!(
+            pl["failure_message"],
+            "ImagePullBackOff on Pod/fake-svc-abc: Back-off pulling image"
+        );
+    }
+
     #[test]
     fn audit_reconciliation_has_type() {
         let mut whc: BTreeMap<String, String> = BTreeMap::default();
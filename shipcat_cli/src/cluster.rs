@@ -1,6 +1,6 @@
 use futures::stream::{self, StreamExt};
-use shipcat_definitions::{BaseManifest, Config, Region, ShipcatConfig};
-use shipcat_filebacked::SimpleManifest;
+use shipcat_definitions::{BaseManifest, Config, Manifest, Region, ShipcatConfig};
+use shipcat_filebacked::ManifestCache;
 
 use super::{kubectl, Error, ErrorKind, Result};
 use crate::{
@@ -13,18 +13,18 @@ struct DiffResult {
     name: String,
     diff: Option<String>,
 }
-async fn diff_summary(svc: String, conf: &Config, reg: &Region) -> Result<DiffResult> {
-    let mut mf = shipcat_filebacked::load_manifest(&svc, &conf, &reg)
-        .await?
-        .complete(&reg)
-        .await?;
+async fn diff_summary(mf: Manifest, conf: &Config, reg: &Region) -> Result<DiffResult> {
+    let mut mf = mf.complete(reg).await?;
     // complete with version and uid from crd
     let s = ShipKube::new(&mf).await?;
     let crd = s.get().await?;
     mf.version = mf.version.or(crd.spec.version);
     mf.uid = crd.metadata.uid;
+    if let Some(registry) = reg.registry.as_ref().filter(|r| r.pinDigests) {
+        apply::resolve_image(&mut mf, registry).await?;
+    }
     info!("diffing {}", mf.name);
-    let d = if let Some(kdiffunobfusc) = diff::template_vs_kubectl(&mf).await? {
+    let d = if let Some(kdiffunobfusc) = diff::template_vs_kubectl(&mf, &conf.diff_ignore(&reg)).await? {
         let kubediff = diff::obfuscate_secrets(
             kdiffunobfusc, // move this away quickly..
             mf.get_secrets(),
@@ -44,11 +44,11 @@ async fn diff_summary(svc: String, conf: &Config, reg: &Region) -> Result<DiffRe
 ///
 /// Helper that shells out to kubectl diff in parallel.
 pub async fn mass_diff(conf: &Config, reg: &Region) -> Result<()> {
-    let svcs = shipcat_filebacked::available(conf, reg).await?;
     assert!(conf.has_secrets());
+    let cache = ManifestCache::load(conf, reg).await?;
 
-    let mut buffered = stream::iter(svcs)
-        .map(move |mf| diff_summary(mf.base.name, &conf, &reg))
+    let mut buffered = stream::iter(cache)
+        .map(move |mf| diff_summary(mf, conf, reg))
         .buffer_unordered(10);
 
     let mut errs = vec![];
@@ -87,11 +87,8 @@ pub async fn mass_diff(conf: &Config, reg: &Region) -> Result<()> {
     Ok(())
 }
 
-async fn check_summary(svc: String, skipped: &[String], conf: &Config, reg: &Region) -> Result<String> {
-    let mut mf = shipcat_filebacked::load_manifest(&svc, &conf, &reg)
-        .await?
-        .stub(&reg)
-        .await?;
+async fn check_summary(mf: Manifest, skipped: &[String], reg: &Region) -> Result<String> {
+    let mut mf = mf.stub(reg).await?;
     mf.version = mf.version.or(Some("latest".to_string()));
     mf.uid = Some("FAKE-GUID".to_string());
 
@@ -105,13 +102,14 @@ async fn check_summary(svc: String, skipped: &[String], conf: &Config, reg: &Reg
 ///
 /// Helper that shells out to helm template in parallel.
 pub async fn mass_template_verify(conf: &Config, reg: &Region, skipped: &[String]) -> Result<()> {
-    let svcs = shipcat_filebacked::available(conf, reg).await?;
+    let (cache, load_errs) = ManifestCache::load_partial(conf, reg).await?;
 
-    let mut buffered = stream::iter(svcs)
-        .map(move |mf| check_summary(mf.base.name, &skipped, &conf, &reg))
+    let mut buffered = stream::iter(cache)
+        .map(move |mf| check_summary(mf, skipped, reg))
         .buffer_unordered(100);
 
-    let (mut errs, mut passed): (Vec<Error>, Vec<_>) = (vec![], vec![]);
+    let mut errs = load_errs.into_iter().map(Error::from).collect::<Vec<_>>();
+    let mut passed = vec![];
     while let Some(r) = buffered.next().await {
         match r {
             Ok(p) => passed.push(p),
@@ -145,12 +143,12 @@ pub async fn crd_install(reg: &Region) -> Result<()> {
 ///
 /// Helper that shells out to kubectl apply in parallel.
 pub async fn mass_crd(conf_sec: &Config, conf_base: &Config, reg: &Region, n_workers: usize) -> Result<()> {
-    let svcs = shipcat_filebacked::available(conf_base, reg).await?;
+    let svcs = ManifestCache::load(conf_base, reg).await?.names();
     crd_reconcile(svcs, conf_sec, conf_base, &reg.name, n_workers).await
 }
 
 async fn crd_reconcile(
-    svcs: Vec<SimpleManifest>,
+    svcs: Vec<String>,
     config_sec: &Config,
     config_base: &Config,
     region: &str,
@@ -190,8 +188,7 @@ async fn crd_reconcile(
     kubectl::apply_resource(&region_base.name, applycfg, &region_base.namespace).await?;
 
     // Single instruction kubectl delete shipcat manifests .... of excess ones
-    let svc_names = svcs.iter().map(|x| x.base.name.to_string()).collect::<Vec<_>>();
-    let excess = kubectl::find_redundant_manifests(&region_sec.namespace, &svc_names).await?;
+    let excess = kubectl::find_redundant_manifests(&region_sec.namespace, &svcs).await?;
     if !excess.is_empty() {
         info!("Will remove excess manifests: {:?}", excess);
     }
@@ -214,9 +211,9 @@ async fn crd_reconcile(
     let conf = config_sec.clone();
     let reg = region_sec.clone();
     let mut buffered = stream::iter(svcs)
-        .map(|mf| {
-            debug!("Running CRD reconcile for {:?}", mf.base.name);
-            apply::apply(mf.base.name, force, &reg, &conf, wait_for_rollout, None)
+        .map(|svc| {
+            debug!("Running CRD reconcile for {:?}", svc);
+            apply::apply(svc, force, &reg, &conf, wait_for_rollout, None)
         })
         .buffer_unordered(n_workers);
 
@@ -0,0 +1,256 @@
+use super::{Config, Manifest, Region, Result};
+use serde_json::Value;
+use shipcat_filebacked::ManifestCache;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Placeholder for a service that is not deployed in a region
+const MISSING: &str = "-";
+/// Placeholder for an env var that is sourced from vault
+const SECRET: &str = "<secret>";
+/// Longest value printed in a table cell
+const MAX_CELL: usize = 40;
+
+/// Flattened comparable properties of a manifest
+///
+/// Keys are dotted paths like `resources.requests.cpu` or `env.RUST_LOG`.
+pub type Fields = BTreeMap<String, String>;
+
+/// Flatten the properties we care about comparing across regions
+///
+/// Only version, replicas, resources, env and kong settings are considered.
+/// Things that always differ between regions (like region names or hosts derived
+/// from them) are better handled by `shipcat diff --with-region`.
+pub fn comparable_fields(mf: &Manifest) -> Result<Fields> {
+    let mut res = Fields::new();
+    if let Some(v) = &mf.version {
+        res.insert("version".into(), v.clone());
+    }
+    if let Some(hpa) = &mf.autoScaling {
+        res.insert(
+            "replicas".into(),
+            format!("{}-{}", hpa.minReplicas, hpa.maxReplicas),
+        );
+    } else if let Some(rc) = mf.replicaCount {
+        res.insert("replicas".into(), rc.to_string());
+    }
+    if let Some(r) = &mf.resources {
+        flatten_into(&mut res, "resources", &serde_json::to_value(r)?);
+    }
+    for (k, v) in &mf.env.plain {
+        res.insert(format!("env.{}", k), v.clone());
+    }
+    for k in &mf.env.secrets {
+        res.insert(format!("env.{}", k), SECRET.into());
+    }
+    for k in &mf.kongApis {
+        let prefix = format!("kong.{}", k.name);
+        flatten_into(&mut res, &prefix, &serde_json::to_value(k)?);
+    }
+    Ok(res)
+}
+
+/// Flatten a json value into leaf paths
+fn flatten_into(res: &mut Fields, prefix: &str, value: &Value) {
+    match value {
+        Value::Null => {}
+        Value::Object(o) => {
+            for (k, v) in o {
+                flatten_into(res, &format!("{}.{}", prefix, k), v);
+            }
+        }
+        Value::String(s) => {
+            res.insert(prefix.into(), s.clone());
+        }
+        // arrays are compared as a whole - ordering matters in manifests anyway
+        other => {
+            res.insert(prefix.into(), other.to_string());
+        }
+    }
+}
+
+/// Rows where the values differ between the columns
+///
+/// Each column is `None` when the service is not in that region,
+/// in which case every field of the service is considered different.
+pub fn differing_rows(columns: &[Option<Fields>]) -> Vec<(String, Vec<String>)> {
+    let keys = columns
+        .iter()
+        .flatten()
+        .flat_map(|c| c.keys().cloned())
+        .collect::<BTreeSet<_>>();
+    let mut rows = vec![];
+    for k in keys {
+        let vals = columns
+            .iter()
+            .map(|c| match c {
+                Some(fields) => fields.get(&k).cloned().unwrap_or_else(|| MISSING.into()),
+                None => MISSING.into(),
+            })
+            .collect::<Vec<_>>();
+        if vals.iter().any(|v| v != &vals[0]) {
+            rows.push((k, vals));
+        }
+    }
+    rows
+}
+
+/// Load the comparable fields of a service in a region
+///
+/// Returns None if the service is not deployed in the region.
+async fn load_fields(svc: &str, conf: &Config, reg: &Region) -> Result<Option<Fields>> {
+    let mf = shipcat_filebacked::load_manifest(svc, conf, reg).await?;
+    manifest_fields(mf, reg).await
+}
+
+/// The comparable fields of a loaded manifest, or None if it is not deployed in the region
+async fn manifest_fields(mf: Manifest, reg: &Region) -> Result<Option<Fields>> {
+    let mf = mf.stub(reg).await?;
+    if mf.verify_region().is_err() || mf.disabled || mf.external {
+        return Ok(None);
+    }
+    Ok(Some(comparable_fields(&mf)?))
+}
+
+async fn service_rows(svc: String, conf: &Config, regions: &[Region]) -> Result<Vec<(String, Vec<String>)>> {
+    let mut columns = vec![];
+    for reg in regions {
+        columns.push(load_fields(&svc, conf, reg).await?);
+    }
+    Ok(differing_rows(&columns))
+}
+
+fn resolve_regions(conf: &Config, names: &[String]) -> Result<Vec<Region>> {
+    let mut regions = vec![];
+    for r in names {
+        if let Some(reg) = conf.get_region_unchecked(r) {
+            regions.push(reg.clone());
+        } else {
+            bail!("Region {} does not exist in shipcat.conf", r);
+        }
+    }
+    if regions.len() < 2 {
+        bail!("Need at least two regions to compare");
+    }
+    Ok(regions)
+}
+
+fn truncate(s: &str) -> String {
+    if s.chars().count() > MAX_CELL {
+        format!("{}…", s.chars().take(MAX_CELL - 1).collect::<String>())
+    } else {
+        s.to_string()
+    }
+}
+
+fn print_matrix(header: &str, regions: &[Region], rows: &[(String, Vec<String>)]) {
+    let kw = rows
+        .iter()
+        .map(|(k, _)| k.len())
+        .max()
+        .unwrap_or(0)
+        .max(header.len());
+    let mut widths = regions.iter().map(|r| r.name.len()).collect::<Vec<_>>();
+    for (_, vals) in rows {
+        for (i, v) in vals.iter().enumerate() {
+            widths[i] = widths[i].max(truncate(v).chars().count());
+        }
+    }
+    print!("{:<width$}", header.to_uppercase(), width = kw);
+    for (r, w) in regions.iter().zip(&widths) {
+        print!("  {:<width$}", r.name, width = *w);
+    }
+    println!();
+    for (k, vals) in rows {
+        print!("{:<width$}", k, width = kw);
+        for (v, w) in vals.iter().zip(&widths) {
+            print!("  {:<width$}", truncate(v), width = *w);
+        }
+        println!();
+    }
+}
+
+/// Compare a single service across several regions
+///
+/// Prints only the fields that differ. Returns whether any differences were found.
+pub async fn service(svc: &str, conf: &Config, regions: &[String]) -> Result<bool> {
+    let regions = resolve_regions(conf, regions)?;
+    let rows = service_rows(svc.to_string(), conf, &regions).await?;
+    if rows.is_empty() {
+        info!("{} is identical across {} regions", svc, regions.len());
+    } else {
+        print_matrix("field", &regions, &rows);
+    }
+    Ok(!rows.is_empty())
+}
+
+/// Compare every service across several regions
+///
+/// Services are considered if they are available in any of the regions.
+/// Prints one matrix with `service: field` rows. Returns whether any differences were found.
+pub async fn all(conf: &Config, regions: &[String]) -> Result<bool> {
+    let regions = resolve_regions(conf, regions)?;
+    let mut caches = vec![];
+    for reg in &regions {
+        caches.push(ManifestCache::load(conf, reg).await?);
+    }
+    let svcs = caches.iter().flat_map(|c| c.names()).collect::<BTreeSet<_>>();
+    let mut rows = vec![];
+    for svc in svcs {
+        let mut columns = vec![];
+        for (cache, reg) in caches.iter().zip(&regions) {
+            columns.push(match cache.get(&svc) {
+                Some(mf) => manifest_fields(mf.clone(), reg).await?,
+                None => None,
+            });
+        }
+        for (k, vals) in differing_rows(&columns) {
+            rows.push((format!("{}: {}", svc, k), vals));
+        }
+    }
+    rows.sort();
+    if rows.is_empty() {
+        info!("All services are identical across {} regions", regions.len());
+    } else {
+        print_matrix("service: field", &regions, &rows);
+    }
+    Ok(!rows.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{differing_rows, flatten_into, Fields};
+    use serde_json::json;
+
+    #[test]
+    fn flatten_leaves() {
+        let mut res = Fields::new();
+        let v = json!({"requests": {"cpu": "100m", "memory": "1Gi"}, "limits": null, "n": 2});
+        flatten_into(&mut res, "resources", &v);
+        assert_eq!(res["resources.requests.cpu"], "100m");
+        assert_eq!(res["resources.requests.memory"], "1Gi");
+        assert_eq!(res["resources.n"], "2");
+        assert!(!res.contains_key("resources.limits"));
+    }
+
+    #[test]
+    fn only_differing_rows() {
+        let mut dev = Fields::new();
+        dev.insert("version".into(), "1.0.0".into());
+        dev.insert("replicas".into(), "2".into());
+        dev.insert("env.DEBUG".into(), "true".into());
+        let mut prod = dev.clone();
+        prod.insert("version".into(), "0.9.0".into());
+        prod.remove("env.DEBUG");
+
+        let rows = differing_rows(&[Some(dev.clone()), Some(prod)]);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0], ("env.DEBUG".into(), vec!["true".into(), "-".into()]));
+        assert_eq!(rows[1], ("version".into(), vec!["1.0.0".into(), "0.9.0".into()]));
+
+        // missing service differs on everything
+        let rows = differing_rows(&[Some(dev.clone()), None]);
+        assert_eq!(rows.len(), 3);
+        // identical regions have nothing to show
+        assert!(differing_rows(&[Some(dev.clone()), Some(dev)]).is_empty());
+    }
+}
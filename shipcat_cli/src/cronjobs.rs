@@ -0,0 +1,162 @@
+use super::{Config, Manifest, Region, Result};
+use chrono::{DateTime, Duration, Local, Utc};
+use futures::stream::{self, StreamExt};
+use shipcat_definitions::structs::CronSchedule;
+use shipcat_filebacked::ManifestCache;
+
+/// A single upcoming run of a cron job
+struct Run {
+    job: String,
+    start: DateTime<Utc>,
+    /// Start plus the job timeout (or a minute when unbounded)
+    end: DateTime<Utc>,
+}
+
+async fn load_manifest(svc: String, conf: &Config, reg: &Region) -> Result<Manifest> {
+    let mf = shipcat_filebacked::load_manifest(&svc, conf, reg)
+        .await?
+        .stub(reg)
+        .await?;
+    Ok(mf)
+}
+
+fn format_time(t: &DateTime<Utc>) -> String {
+    format!(
+        "{} ({})",
+        t.format("%a %Y-%m-%d %H:%M UTC"),
+        t.with_timezone(&Local).format("%H:%M %:z")
+    )
+}
+
+/// Render the cron jobs of manifests with their next `count` runs after `now`
+///
+/// Runs of different jobs that overlap (using their timeouts) are listed at the end.
+fn render(mfs: &[Manifest], count: usize, now: &DateTime<Utc>) -> String {
+    let mut out = String::new();
+    let mut runs = vec![];
+    out += &format!(
+        "{0:<50} {1:<20} {2:<10} {3:<12}\n",
+        "JOB", "SCHEDULE", "TIMEOUT", "REQUESTS"
+    );
+    for mf in mfs {
+        for cj in &mf.cronJobs {
+            let job = format!("{}/{}", mf.name, cj.container.name);
+            let requests = cj
+                .container
+                .resources
+                .as_ref()
+                .map(|r| format!("{}/{}", r.requests.cpu, r.requests.memory))
+                .unwrap_or_else(|| "-".into());
+            let timeout = cj
+                .timeout
+                .map(|t| format!("{}s", t))
+                .unwrap_or_else(|| "-".into());
+            out += &format!(
+                "{0:<50} {1:<20} {2:<10} {3:<12}\n",
+                job, cj.schedule, timeout, requests
+            );
+            match CronSchedule::parse(&cj.schedule) {
+                Ok(schedule) => {
+                    let duration = Duration::seconds(i64::from(cj.timeout.unwrap_or(60)));
+                    for start in schedule.upcoming(now, count) {
+                        out += &format!("  {}\n", format_time(&start));
+                        runs.push(Run {
+                            job: job.clone(),
+                            start,
+                            end: start + duration,
+                        });
+                    }
+                }
+                Err(e) => out += &format!("  invalid schedule: {}\n", e),
+            }
+        }
+    }
+
+    runs.sort_by_key(|r| r.start);
+    let mut overlaps = vec![];
+    for (i, a) in runs.iter().enumerate() {
+        for b in runs[i + 1..].iter().take_while(|b| b.start < a.end) {
+            if a.job != b.job {
+                overlaps.push(format!(
+                    "  {} {} overlaps {}",
+                    format_time(&b.start),
+                    b.job,
+                    a.job
+                ));
+            }
+        }
+    }
+    if !overlaps.is_empty() {
+        out += "\nOverlapping runs:\n";
+        out += &overlaps.join("\n");
+        out += "\n";
+    }
+    out
+}
+
+/// Show the cron jobs of a service with their upcoming runs
+pub async fn service(svc: &str, conf: &Config, reg: &Region, count: usize) -> Result<()> {
+    let mf = load_manifest(svc.to_string(), conf, reg).await?;
+    if mf.cronJobs.is_empty() {
+        info!("{} has no cronJobs in {}", svc, reg.name);
+        return Ok(());
+    }
+    print!("{}", render(&[mf], count, &Utc::now()));
+    Ok(())
+}
+
+/// Show every cron job in a region with their upcoming runs
+pub async fn all(conf: &Config, reg: &Region, count: usize) -> Result<()> {
+    let cache = ManifestCache::load(conf, reg).await?;
+    let mut buffered = stream::iter(cache)
+        .map(move |mf| mf.stub(reg))
+        .buffer_unordered(20);
+    let mut mfs = vec![];
+    while let Some(r) = buffered.next().await {
+        let mf = r?;
+        if !mf.cronJobs.is_empty() {
+            mfs.push(mf);
+        }
+    }
+    mfs.sort_by(|a, b| a.name.cmp(&b.name));
+    print!("{}", render(&mfs, count, &Utc::now()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use chrono::{TimeZone, Utc};
+    use shipcat_definitions::{structs::CronJob, Manifest};
+
+    fn job(name: &str, schedule: &str, timeout: Option<u32>) -> CronJob {
+        let mut cj = CronJob::default();
+        cj.container.name = name.into();
+        cj.schedule = schedule.into();
+        cj.timeout = timeout;
+        cj
+    }
+
+    #[test]
+    fn render_runs_and_overlaps() {
+        let mut mf = Manifest::default();
+        mf.name = "fake-ask".into();
+        mf.cronJobs = vec![
+            job("hourly", "0 * * * *", Some(1800)),
+            job("halfpast", "30 * * * *", None),
+            job("quarter", "15 * * * *", None),
+        ];
+        let now = Utc.ymd(2020, 1, 1).and_hms(10, 7, 0);
+        let out = render(&[mf], 2, &now);
+        assert!(out.contains("fake-ask/hourly"));
+        assert!(out.contains("Wed 2020-01-01 11:00 UTC"));
+        assert!(out.contains("Wed 2020-01-01 12:00 UTC"));
+        // the quarter past job runs inside the 30 minutes of the hourly job
+        assert!(
+            out.contains("Wed 2020-01-01 11:15 UTC")
+                && out.contains("fake-ask/quarter overlaps fake-ask/hourly")
+        );
+        // the half past job starts as the hourly job times out
+        assert!(!out.contains("fake-ask/halfpast overlaps"));
+    }
+}
@@ -1,7 +1,8 @@
 use super::{Config, ConfigState, Manifest, Region, Result};
 use crate::{git, helm, kubectl};
 use regex::Regex;
-use shipcat_definitions::ShipcatManifest;
+use shipcat_definitions::{DiffIgnore, ShipcatManifest};
+use shipcat_filebacked::Tree;
 use std::process::Command;
 
 /// YAML serialisation of a manifest.
@@ -9,8 +10,8 @@ use std::process::Command;
 /// Return an empty string if the manifest fails region-validation,
 /// otherwise YAML serialise the content. For diff purposes, the content
 /// of a manifest not in a region is a blank, rather than being invalid.
-async fn as_yaml(svc: &str, conf: &Config, region: &Region) -> Result<String> {
-    let mf = shipcat_filebacked::load_manifest(&svc, conf, region).await?;
+async fn as_yaml(svc: &str, conf: &Config, region: &Region, tree: &Tree) -> Result<String> {
+    let mf = shipcat_filebacked::load_manifest_at(tree, svc, conf, region).await?;
     if let Ok(m) = mf.verify_region() {
         let yaml = serde_yaml::to_string(&m)?;
         Ok(yaml)
@@ -19,33 +20,28 @@ async fn as_yaml(svc: &str, conf: &Config, region: &Region) -> Result<String> {
     }
 }
 
-/// Fast local git compare of the crd
+/// Config and region as of the merge-base with master
 ///
-/// Should be pretty safe. Stashes existing work, checks out master, compares,
-/// then goes back to previous branch and pops the stash.
+/// Read from the git object database, so the working tree is left alone.
+async fn merge_base_config(region: &Region) -> Result<(Tree, Config, Region)> {
+    let tree = Tree::revision(&git::merge_base()?)?;
+    let (conf, region) = tree
+        .config()?
+        .for_context(ConfigState::Base, &region.name)
+        .await?;
+    Ok((tree, conf, region))
+}
+
+/// Fast local git compare of the crd
 ///
-/// Because this does fiddle with git state while running it is not the default implementation.
+/// Compares the working tree against the merge-base with master,
+/// reading the latter straight from git without checking it out.
 pub async fn values_vs_git(svc: &str, conf: &Config, region: &Region) -> Result<bool> {
-    let after = as_yaml(&svc, conf, region).await?;
-
-    // move git to get before state:
-    let merge_base = git::merge_base()?;
-    git::checkout(&merge_base)?;
-
-    let needs_stash = git::needs_stash();
-    if needs_stash {
-        git::stash_push()?;
-    }
+    let after = as_yaml(svc, conf, region, &Tree::Worktree).await?;
 
     // compute before state
-    let (before_conf, before_region) = Config::new(ConfigState::Base, &region.name).await?;
-    let before = as_yaml(&svc, &before_conf, &before_region).await?;
-
-    // move git back
-    if needs_stash {
-        git::stash_pop()?;
-    }
-    git::checkout("-")?;
+    let (tree, before_conf, before_region) = merge_base_config(region).await?;
+    let before = as_yaml(svc, &before_conf, &before_region, &tree).await?;
 
     // display diff
     shell_diff(&before, &after, "before", "after")
@@ -59,10 +55,10 @@ pub async fn values_vs_region(
     ref_region: &Region,
 ) -> Result<bool> {
     let before_region = format!("{}.{}", svc, ref_region.name);
-    let before_values = as_yaml(svc, conf, ref_region).await?;
+    let before_values = as_yaml(svc, conf, ref_region, &Tree::Worktree).await?;
 
     let after_region = format!("{}.{}", svc, region.name);
-    let after_values = as_yaml(svc, conf, region).await?;
+    let after_values = as_yaml(svc, conf, region, &Tree::Worktree).await?;
 
     // display diff
     shell_diff(&before_values, &after_values, &before_region, &after_region)
@@ -72,6 +68,8 @@ pub async fn values_vs_region(
 ///
 /// Because this uses the template in master against local state,
 /// we don't resolve secrets for this (would compare equal values anyway).
+/// Manifests and charts are read from git at the merge-base,
+/// with the charts exported to a temporary directory for helm.
 pub async fn template_vs_git(svc: &str, conf: &Config, region: &Region) -> Result<bool> {
     let afterpth = Path::new(".").join("after.shipcat.gen.yml");
     let mf_after = shipcat_filebacked::load_manifest(svc, conf, region)
@@ -80,30 +78,20 @@ pub async fn template_vs_git(svc: &str, conf: &Config, region: &Region) -> Resul
         .await?;
     let _after = helm::template(&mf_after, Some(afterpth.clone())).await?;
 
-    // move git to get before state:
-    let merge_base = git::merge_base()?;
-    git::checkout(merge_base.as_str())?;
-
-    let needs_stash = git::needs_stash();
-    if needs_stash {
-        git::stash_push()?;
-    }
-
     // compute old state:
-    let (before_conf, before_region) = Config::new(ConfigState::Base, &region.name).await?;
+    let (tree, before_conf, before_region) = merge_base_config(region).await?;
 
     let beforepth = Path::new(".").join("before.shipcat.gen.yml");
-    let mf_before = shipcat_filebacked::load_manifest(svc, &before_conf, &before_region)
+    let mf_before = shipcat_filebacked::load_manifest_at(&tree, svc, &before_conf, &before_region)
         .await?
         .stub(region)
         .await?;
-    let _before = helm::template(&mf_before, Some(beforepth.clone())).await?;
-
-    // move git back
-    if needs_stash {
-        git::stash_pop()?;
-    }
-    git::checkout("-")?;
+    // removed when dropped, even on failure
+    let charts = tempfile::Builder::new()
+        .prefix(&format!("shipcat-{}-charts", svc))
+        .tempdir()?;
+    tree.export(Path::new("charts"), charts.path())?;
+    let _before = helm::template_from(&mf_before, Some(beforepth.clone()), charts.path()).await?;
 
     // display diff
     // doesn't reuse shell_diff because we already have files from direct::template
@@ -137,7 +125,7 @@ pub async fn values_vs_kubectl(svc: &str, conf: &Config, region: &Region) -> Res
     let mut f = File::create(&pth)?;
     writeln!(f, "{}", encoded)?;
     // shell out to kubectl:
-    let (out, _err, success) = kubectl::diff(pth.clone(), &region.namespace).await?;
+    let (out, _err, success) = kubectl::diff(pth.clone(), &region.namespace, false).await?;
     println!("{}", out);
     // cleanup:
     fs::remove_file(pth)?;
@@ -148,19 +136,21 @@ pub async fn values_vs_kubectl(svc: &str, conf: &Config, region: &Region) -> Res
 ///
 /// Generate template as we write it and pipe it to `kubectl diff -`
 /// Only works on clusters with kubectl 1.13 on the server side, so not available everywhere
-pub async fn template_vs_kubectl(mf: &Manifest) -> Result<Option<String>> {
+/// Changes covered by the ignore rules are stripped from the output.
+pub async fn template_vs_kubectl(mf: &Manifest, ignore: &DiffIgnore) -> Result<Option<String>> {
     // Generate template in a temp file:
     let tfile = format!("{}.shipcat.tpl.gen.yml", mf.name);
     let pth = Path::new(".").join(tfile);
 
     let _tpl = helm::template(&mf, Some(pth.clone())).await?;
 
-    let (out, err, success) = kubectl::diff(pth.clone(), &mf.namespace).await?;
+    let (out, err, success) = kubectl::diff(pth.clone(), &mf.namespace, !ignore.is_empty()).await?;
     // cleanup:
     fs::remove_file(pth)?;
     if !success && !err.is_empty() && err.trim() != "exit status 1" {
         println!("kubectl diff stderr: {}", err.trim());
     }
+    let out = filter_ignored(&out, ignore);
     if !out.is_empty() {
         Ok(Some(out))
     } else {
@@ -193,6 +183,32 @@ fn shell_diff(before: &str, after: &str, before_name: &str, after_name: &str) ->
     Ok(s.success())
 }
 
+/// Unified diff of two strings as text
+///
+/// Like shell_diff, but captures the output rather than printing it.
+/// The inputs are written to a temporary directory and labelled with the given names.
+/// Returns an empty string when the inputs are identical.
+pub fn unified_diff(before: &str, after: &str, before_name: &str, after_name: &str) -> Result<String> {
+    let dir = tempfile::tempdir()?;
+    let beforepth = dir.path().join("before");
+    fs::write(&beforepth, before)?;
+    let afterpth = dir.path().join("after");
+    fs::write(&afterpth, after)?;
+
+    let args = ["-u", "--label", before_name, "--label", after_name];
+    debug!("diff {}", args.join(" "));
+    let s = Command::new("diff")
+        .args(args.iter())
+        .arg(&beforepth)
+        .arg(&afterpth)
+        .output()?;
+    // diff exits with 1 when the inputs differ, and 2 on trouble
+    match s.status.code() {
+        Some(0) | Some(1) => Ok(String::from_utf8_lossy(&s.stdout).into()),
+        _ => bail!("diff failed: {}", String::from_utf8_lossy(&s.stderr).trim()),
+    }
+}
+
 /// Minify diff output from kubectl diff
 pub fn minify(diff: &str) -> String {
     let minusplus = Regex::new(r"^\- |^\+ ").unwrap();
@@ -219,6 +235,209 @@ pub fn minify(diff: &str) -> String {
     res.join("\n")
 }
 
+/// Glob match where `*` matches any sequence of characters
+fn glob_match(pattern: &str, input: &str) -> bool {
+    let re = format!("^{}$", regex::escape(pattern).replace(r"\*", ".*"));
+    Regex::new(&re).map(|r| r.is_match(input)).unwrap_or(false)
+}
+
+/// Check whether a full yaml path in a diff is covered by ignore rules
+fn is_ignored_path(path: &[String], ignore: &DiffIgnore) -> bool {
+    let joined = path.join(".");
+    if ignore.paths.iter().any(|rule| glob_match(rule, &joined)) {
+        return true;
+    }
+    if let Some((key, parents)) = path.split_last() {
+        let under_labels = matches!(parents.last(), Some(p) if p == "labels" || p == "matchLabels");
+        if under_labels && ignore.labels.contains(key) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Lines of context kept around the remaining changes in a filtered diff
+const FILTER_CONTEXT: usize = 3;
+
+/// Drop ignored changes from one hunk of a unified diff
+///
+/// Paths can only be worked out when the hunk starts at the top of the object,
+/// so changes in hunks that start further down are always kept.
+/// The remaining changes are returned as new hunks with a few lines of context.
+fn filter_hunk(start: (usize, usize), lines: &[&str], ignore: &DiffIgnore) -> Vec<String> {
+    let rooted = start.0 <= 1 && start.1 <= 1;
+    // (old line, new line, diff line, is a change)
+    let mut kept: Vec<(usize, usize, &str, bool)> = vec![];
+    let (mut old, mut new) = start;
+    let mut stack: Vec<(usize, String)> = vec![];
+    // indent of the key whose block scalar we are inside
+    let mut block: Option<usize> = None;
+    for l in lines {
+        if l.starts_with('\\') {
+            // "\ No newline at end of file"
+            kept.push((old, new, l, false));
+            continue;
+        }
+        let (marker, text) = if l.is_empty() { (" ", "") } else { l.split_at(1) };
+        let pos = (old, new);
+        match marker {
+            "-" => old += 1,
+            "+" => new += 1,
+            _ => {
+                old += 1;
+                new += 1;
+            }
+        }
+        let is_change = marker == "-" || marker == "+";
+        let mut indent = text.len() - text.trim_start().len();
+        let mut content = text.trim_start();
+        let in_block = match block {
+            Some(b) => content.is_empty() || indent > b,
+            None => false,
+        };
+        let mut key = None;
+        if !in_block {
+            block = None;
+            while content.starts_with("- ") {
+                indent += 2;
+                content = &content[2..];
+            }
+            let value;
+            if content.ends_with(':') {
+                key = Some(content.trim_end_matches(':'));
+                value = "";
+            } else if let Some(i) = content.find(": ") {
+                key = Some(&content[..i]);
+                value = content[i + 2..].trim();
+            } else {
+                value = "";
+            }
+            if key.is_some() || !content.is_empty() {
+                while matches!(stack.last(), Some((i, _)) if *i >= indent) {
+                    stack.pop();
+                }
+            }
+            if key.is_some() && (value.starts_with('|') || value.starts_with('>')) {
+                block = Some(indent);
+            }
+        }
+        let mut path = stack.iter().map(|(_, k)| k.clone()).collect::<Vec<_>>();
+        if let Some(k) = key {
+            let k = k.trim_matches('"').to_string();
+            path.push(k.clone());
+            stack.push((indent, k));
+        }
+        if is_change && rooted && !path.is_empty() && is_ignored_path(&path, ignore) {
+            trace!("ignoring diff line: {}", l);
+            continue;
+        }
+        kept.push((pos.0, pos.1, l, is_change));
+    }
+
+    // re-hunk what is left around the remaining changes
+    let changes = kept
+        .iter()
+        .enumerate()
+        .filter(|(_, k)| k.3)
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let near = |i: usize| {
+        changes
+            .iter()
+            .any(|c| i + FILTER_CONTEXT >= *c && i <= c + FILTER_CONTEXT)
+    };
+    let mut res = vec![];
+    let mut i = 0;
+    while i < kept.len() {
+        if !near(i) {
+            i += 1;
+            continue;
+        }
+        let first = i;
+        while i < kept.len() && near(i) {
+            i += 1;
+        }
+        let hunk = &kept[first..i];
+        let old_len = hunk
+            .iter()
+            .filter(|k| !k.2.starts_with('+') && !k.2.starts_with('\\'))
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|k| !k.2.starts_with('-') && !k.2.starts_with('\\'))
+            .count();
+        res.push(format!(
+            "@@ -{},{} +{},{} @@",
+            hunk[0].0, old_len, hunk[0].1, new_len
+        ));
+        res.extend(hunk.iter().map(|k| k.2.to_string()));
+    }
+    res
+}
+
+/// Strip changes covered by ignore rules from kubectl diff output
+///
+/// Works on the raw output of `kubectl diff` (before minify), which should be
+/// generated with full context (see `kubectl::diff`) so that paths can be matched in full.
+/// Objects of ignored kinds are removed entirely, and changed lines under ignored paths or labels are dropped.
+/// Objects with no remaining changes are removed, so an empty string means no diff.
+pub fn filter_ignored(diff: &str, ignore: &DiffIgnore) -> String {
+    if ignore.is_empty() {
+        return diff.to_string();
+    }
+    let kind_line = Regex::new(r"^--- /tmp/LIVE-[a-zA-Z0-9]+/([\w\.]+)").unwrap();
+    let hunk_line = Regex::new(r"^@@ -(\d+)(?:,\d+)? \+(\d+)(?:,\d+)? @@").unwrap();
+
+    let mut res: Vec<String> = vec![];
+    // header lines of the current object, and its filtered hunks
+    let mut header: Vec<&str> = vec![];
+    let mut hunks: Vec<String> = vec![];
+    let mut ignored_kind = false;
+    // start and lines of the current hunk
+    let mut hunk: Option<((usize, usize), Vec<&str>)> = None;
+    for l in diff.lines() {
+        let new_object =
+            l.starts_with("diff ") || (l.starts_with("--- ") && header.iter().any(|s| s.starts_with("--- ")));
+        let new_hunk = hunk_line.captures(l);
+        if new_object || new_hunk.is_some() {
+            if let Some((start, lines)) = hunk.take() {
+                hunks.extend(filter_hunk(start, &lines, ignore));
+            }
+        }
+        if new_object {
+            if !ignored_kind && !hunks.is_empty() {
+                res.extend(header.iter().map(|h| h.to_string()));
+                res.append(&mut hunks);
+            }
+            header.clear();
+            hunks.clear();
+            ignored_kind = false;
+        }
+        if let Some(cap) = new_hunk {
+            let start = (cap[1].parse().unwrap_or(0), cap[2].parse().unwrap_or(0));
+            hunk = Some((start, vec![]));
+        } else if let Some((_, lines)) = hunk.as_mut() {
+            lines.push(l);
+        } else {
+            if let Some(cap) = kind_line.captures(l) {
+                // e.g. apps.v1.Deployment.dev.name - kind is the capitalised segment
+                let kind = cap[1].split('.').find(|s| s.starts_with(char::is_uppercase));
+                ignored_kind =
+                    matches!(kind, Some(k) if ignore.kinds.iter().any(|i| i.eq_ignore_ascii_case(k)));
+            }
+            header.push(l);
+        }
+    }
+    if let Some((start, lines)) = hunk.take() {
+        hunks.extend(filter_hunk(start, &lines, ignore));
+    }
+    if !ignored_kind && !hunks.is_empty() {
+        res.extend(header.iter().map(|h| h.to_string()));
+        res.append(&mut hunks);
+    }
+    res.join("\n")
+}
+
 /// Check if a diff contains only version related changes
 pub fn is_version_only(diff: &str, vers: (&str, &str)) -> bool {
     let smalldiff = minify(diff);
@@ -267,7 +486,8 @@ pub fn obfuscate_secrets(input: String, secrets: Vec<String>) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::{infer_version_change, is_version_only, minify};
+    use super::{filter_ignored, infer_version_change, is_version_only, minify, unified_diff};
+    use shipcat_definitions::DiffIgnore;
 
     #[test]
     fn version_change_test() {
@@ -341,6 +561,14 @@ mod tests {
         assert!(is_version_only(input, (&new, &old)));
     }
 
+    #[test]
+    fn unified_diff_labels() {
+        assert_eq!(unified_diff("a: 1\n", "a: 1\n", "before", "after").unwrap(), "");
+        let diff = unified_diff("a: 1\n", "a: 2\n", "services/a/manifest.yml", "after").unwrap();
+        assert!(diff.starts_with("--- services/a/manifest.yml\n+++ after\n"));
+        assert!(diff.contains("-a: 1\n+a: 2\n"));
+    }
+
     #[test]
     fn kubectl_diff_minify_test() {
         let input = "--- /tmp/LIVE-A9/apps.v1.Deployment.dev.raftcat   2019-09-11 16:12:26.819641578 +0100
@@ -449,4 +677,169 @@ autoscaling.v2beta2.HorizontalPodAutoscaler.dev.raftcat has changed:
 +  maxReplicas: 4"
         );
     }
+
+    #[test]
+    fn kubectl_diff_ignore_rules() {
+        // full context diffs, as generated by kubectl::diff when rules are set
+        let input = r#"diff -u -N /tmp/LIVE-547038353/apps.v1.Deployment.dev.raftcat /tmp/MERGED-191875772/apps.v1.Deployment.dev.raftcat
+--- /tmp/LIVE-547038353/apps.v1.Deployment.dev.raftcat   2020-04-07 12:25:26.255493075 +0100
++++ /tmp/MERGED-191875772/apps.v1.Deployment.dev.raftcat 2020-04-07 12:25:26.312159054 +0100
+@@ -1,18 +1,18 @@
+ apiVersion: apps/v1
+ kind: Deployment
+ metadata:
+   annotations:
+     kubectl.kubernetes.io/last-applied-configuration: |
+       {"apiVersion": "apps/v1", "generation": 1}
+   creationTimestamp: "2019-11-18T22:47:05Z"
+-  generation: 227
++  generation: 228
+   labels:
+     app: raftcat
+ spec:
+   template:
+     metadata:
+       annotations:
+         checksum/config: 01ba4719c80b6fe911b091a7c05124b64eeece964e09c058ef8f9805daca546b
+-        checksum/secrets: 3e6c8c2098228c15554026bef845a1e121c6d32a315ecbba953d92e8b4d26bed
++        checksum/secrets: fd7f339fc814c7067c66213705ff7bfc78a81723e50c1083bd3a29df272d34e7
+         kubectl.kubernetes.io/restartedAt: "2020-03-18T18:40:01Z"
+       creationTimestamp: null
+diff -u -N /tmp/LIVE-422759316/v1.Secret.dev.raftcat-secrets /tmp/MERGED-101659107/v1.Secret.dev.raftcat-secrets
+--- /tmp/LIVE-422759316/v1.Secret.dev.raftcat-secrets   2020-04-07 12:26:32.618020569 +0100
++++ /tmp/MERGED-101659107/v1.Secret.dev.raftcat-secrets 2020-04-07 12:26:32.664686669 +0100
+@@ -1,5 +1,6 @@
+ apiVersion: v1
+ data:
+-  SENTRY_DSN: aGVsbG8gd29ybGQK==
++  SENTRY_DSN: YUdWc2JHOGdkMjl5YkdRPQ==
++  WOOT: aGk=
+ kind: Secret
+ metadata:
+diff -u -N /tmp/LIVE-167159470/autoscaling.v2beta2.HorizontalPodAutoscaler.dev.raftcat /tmp/MERGED-264369205/autoscaling.v2beta2.HorizontalPodAutoscaler.dev.raftcat
+--- /tmp/LIVE-167159470/autoscaling.v2beta2.HorizontalPodAutoscaler.dev.raftcat 2020-04-07 13:02:15.788649534 +0100
++++ /tmp/MERGED-264369205/autoscaling.v2beta2.HorizontalPodAutoscaler.dev.raftcat   2020-04-07 13:02:15.875315149 +0100
+@@ -1,6 +1,6 @@
+ kind: HorizontalPodAutoscaler
+ spec:
+-  maxReplicas: 3
++  maxReplicas: 4
+   metrics:
+   - resource:
+       name: cpu"#;
+
+        let ignore = DiffIgnore {
+            kinds: vec!["HorizontalPodAutoscaler".into()],
+            paths: vec![
+                "metadata.generation".into(),
+                "spec.template.metadata.annotations.checksum/*".into(),
+            ],
+            labels: vec![],
+        };
+        assert_eq!(
+            minify(&filter_ignored(input, &ignore)),
+            "Change to v1.Secret.dev.raftcat elided for security"
+        );
+        // no rules => untouched
+        assert_eq!(filter_ignored(input, &DiffIgnore::default()), input);
+
+        let labels = r#"diff -u -N /tmp/LIVE-1/v1.Service.dev.raftcat /tmp/MERGED-2/v1.Service.dev.raftcat
+--- /tmp/LIVE-1/v1.Service.dev.raftcat   2020-04-07 12:25:26.255493075 +0100
++++ /tmp/MERGED-2/v1.Service.dev.raftcat 2020-04-07 12:25:26.312159054 +0100
+@@ -1,8 +1,8 @@
+ apiVersion: v1
+ kind: Service
+ metadata:
+   labels:
+-    app.kubernetes.io/version: 1.0.0
++    app.kubernetes.io/version: 1.1.0
+   name: raftcat
+-  version: 1.0.0
++  version: 1.1.0"#;
+        let ignore = DiffIgnore {
+            labels: vec!["app.kubernetes.io/version".into()],
+            ..DiffIgnore::default()
+        };
+        // only labels are ignored, not other keys with the same value
+        assert_eq!(
+            minify(&filter_ignored(labels, &ignore)),
+            "v1.Service.dev.raftcat has changed:\n-  version: 1.0.0\n+  version: 1.1.0"
+        );
+        let ignore = DiffIgnore {
+            paths: vec!["metadata.version".into()],
+            ..ignore
+        };
+        assert_eq!(filter_ignored(labels, &ignore), "");
+    }
+
+    #[test]
+    fn kubectl_diff_ignore_full_paths() {
+        let input = r#"diff -u -N /tmp/LIVE-1/apps.v1.Deployment.dev.raftcat /tmp/MERGED-2/apps.v1.Deployment.dev.raftcat
+--- /tmp/LIVE-1/apps.v1.Deployment.dev.raftcat   2020-04-07 12:25:26.255493075 +0100
++++ /tmp/MERGED-2/apps.v1.Deployment.dev.raftcat 2020-04-07 12:25:26.312159054 +0100
+@@ -1,16 +1,16 @@
+ kind: Deployment
+ metadata:
+-  generation: 1
++  generation: 2
+   labels:
+-    version: 1.0.0
++    version: 1.1.0
+ spec:
+   generation: 3
+   template:
+     metadata:
+       labels:
+-        version: 1.0.0
++        version: 1.1.0
+     spec:
+       containers:
+       - name: raftcat
+-        generation: 1
++        generation: 2"#;
+        // same named keys deeper in the object are not covered by the rule
+        let ignore = DiffIgnore {
+            paths: vec!["metadata.generation".into(), "metadata.labels.version".into()],
+            ..DiffIgnore::default()
+        };
+        assert_eq!(
+            filter_ignored(input, &ignore),
+            r#"diff -u -N /tmp/LIVE-1/apps.v1.Deployment.dev.raftcat /tmp/MERGED-2/apps.v1.Deployment.dev.raftcat
+--- /tmp/LIVE-1/apps.v1.Deployment.dev.raftcat   2020-04-07 12:25:26.255493075 +0100
++++ /tmp/MERGED-2/apps.v1.Deployment.dev.raftcat 2020-04-07 12:25:26.312159054 +0100
+@@ -8,8 +8,8 @@
+   template:
+     metadata:
+       labels:
+-        version: 1.0.0
++        version: 1.1.0
+     spec:
+       containers:
+       - name: raftcat
+-        generation: 1
++        generation: 2"#
+        );
+        // labels rules apply to every labels map
+        let ignore = DiffIgnore {
+            labels: vec!["version".into()],
+            ..ignore
+        };
+        let filtered = filter_ignored(input, &ignore);
+        assert!(!filtered.contains("version: 1."));
+        assert!(filtered.contains("-        generation: 1\n+        generation: 2"));
+
+        // hunks that do not start at the top of the object cannot be matched, so are kept
+        let unrooted = r#"--- /tmp/LIVE-A9/apps.v1.Deployment.dev.raftcat   2019-09-11 16:12:26.819641578 +0100
++++ /tmp/MERGED-B0/apps.v1.Deployment.dev.raftcat 2019-09-11 16:12:26.852974183 +0100
+@@ -6,6 +6,6 @@
+       labels:
+         version: 1.0.0
+   creationTimestamp: "2019-09-11T14:49:14Z"
+-  generation: 5
++  generation: 6
+   labels:
+-    version: 1.0.0
++    version: 1.1.0"#;
+        assert_eq!(filter_ignored(unrooted, &ignore), unrooted);
+    }
 }
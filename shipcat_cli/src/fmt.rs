@@ -0,0 +1,62 @@
+use super::Result;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Manifest and override files of services, or of every service if none are given
+pub(crate) fn source_files(services: &[String]) -> Result<Vec<PathBuf>> {
+    let root = Path::new(".").join("services");
+    let services = if services.is_empty() {
+        let mut all = fs::read_dir(&root)?
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_dir())
+            .filter_map(|e| e.file_name().to_str().map(String::from))
+            .collect::<Vec<_>>();
+        all.sort();
+        all
+    } else {
+        services.to_vec()
+    };
+    let mut files = vec![];
+    for svc in services {
+        let dir = root.join(&svc);
+        if !dir.is_dir() {
+            bail!("Service folder {} does not exist", dir.display());
+        }
+        let mut svcfiles = fs::read_dir(&dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file() && p.extension() == Some("yml".as_ref()))
+            .collect::<Vec<_>>();
+        svcfiles.sort();
+        files.extend(svcfiles);
+    }
+    Ok(files)
+}
+
+/// Format the manifest and override files of services in place
+///
+/// With `check`, files are left alone and it is an error if any are not formatted.
+pub fn format(services: &[String], check: bool) -> Result<()> {
+    let mut unformatted = vec![];
+    for pth in source_files(services)? {
+        let filename = pth.file_name().and_then(|f| f.to_str()).unwrap_or_default();
+        let data = fs::read_to_string(&pth)?;
+        let formatted = shipcat_filebacked::format_source(filename, &data)?;
+        if formatted == data {
+            continue;
+        }
+        if check {
+            warn!("{} is not formatted", pth.display());
+        } else {
+            info!("Formatted {}", pth.display());
+            fs::write(&pth, formatted)?;
+        }
+        unformatted.push(pth);
+    }
+    if check && !unformatted.is_empty() {
+        bail!("{} files are not formatted, run shipcat fmt", unformatted.len());
+    }
+    Ok(())
+}
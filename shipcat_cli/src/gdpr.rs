@@ -1,5 +1,6 @@
-use super::{Config, Region};
-use std::collections::BTreeMap;
+use super::{Config, Error, Manifest, Region};
+use shipcat_filebacked::ManifestCache;
+use std::{collections::BTreeMap, str::FromStr};
 
 use super::{structs::security::DataHandling, Result};
 
@@ -26,12 +27,11 @@ pub async fn show(svc: Option<String>, conf: &Config, region: &Region) -> Result
     } else {
         let mut mappings = BTreeMap::new();
         let mut services = vec![];
-        for s in shipcat_filebacked::available(conf, region).await? {
-            let mf = shipcat_filebacked::load_manifest(&s.base.name, conf, region).await?;
+        for mf in ManifestCache::load(conf, region).await? {
             if let Some(dh) = mf.dataHandling {
-                mappings.insert(s.base.name.clone(), dh);
+                mappings.insert(mf.name.clone(), dh);
             }
-            services.push(s.base.name);
+            services.push(mf.name);
         }
         let data = GdprOutput { mappings, services };
         serde_yaml::to_string(&data)?
@@ -39,3 +39,187 @@ pub async fn show(svc: Option<String>, conf: &Config, region: &Region) -> Result
     println!("{}", out);
     Ok(())
 }
+
+/// Format of the record of processing report
+pub enum ReportFormat {
+    Markdown,
+    Csv,
+}
+
+impl FromStr for ReportFormat {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        match input {
+            "markdown" => Ok(Self::Markdown),
+            "csv" => Ok(Self::Csv),
+            _ => bail!("Report format must be markdown or csv"),
+        }
+    }
+}
+
+const REPORT_HEADER: &[&str] = &[
+    "Service",
+    "Team",
+    "Backend",
+    "Classification",
+    "Field",
+    "Encrypted",
+    "Cipher",
+    "Key rotator",
+    "Retention",
+    "Sources",
+];
+
+/// One row per stored field (or per store when it lists no fields)
+fn report_rows(mfs: &[Manifest]) -> Vec<Vec<String>> {
+    let opt = |o: &Option<String>| o.clone().unwrap_or_else(|| "-".into());
+    let mut rows = vec![];
+    for mf in mfs {
+        let dh = match &mf.dataHandling {
+            Some(dh) => dh,
+            None => continue,
+        };
+        let team = mf.metadata.as_ref().map(|md| md.team.clone()).unwrap_or_default();
+        for store in &dh.stores {
+            let class = store
+                .informationClassification
+                .as_ref()
+                .map(|c| c.to_string())
+                .unwrap_or_else(|| "-".into());
+            let mut row = |field: &str, encrypted: Option<bool>, cipher, rotator, retention| {
+                let sources = dh
+                    .processes
+                    .iter()
+                    .filter(|p| p.field == field)
+                    .map(|p| p.source.clone())
+                    .collect::<Vec<_>>();
+                rows.push(vec![
+                    mf.name.clone(),
+                    team.clone(),
+                    store.backend.clone(),
+                    class.clone(),
+                    field.to_string(),
+                    encrypted.unwrap_or(false).to_string(),
+                    opt(cipher),
+                    opt(rotator),
+                    opt(retention),
+                    if sources.is_empty() {
+                        "-".into()
+                    } else {
+                        sources.join(" ")
+                    },
+                ]);
+            };
+            if store.fields.is_empty() {
+                row(
+                    "-",
+                    store.encrypted,
+                    &store.cipher,
+                    &store.keyRotator,
+                    &store.retentionPeriod,
+                );
+            }
+            for f in &store.fields {
+                row(&f.name, f.encrypted, &f.cipher, &f.keyRotator, &f.retentionPeriod);
+            }
+        }
+    }
+    rows
+}
+
+fn markdown(rows: &[Vec<String>]) -> String {
+    let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+    let mut out = line(REPORT_HEADER.iter().map(|h| h.to_string()).collect());
+    out += &line(REPORT_HEADER.iter().map(|h| "-".repeat(h.len())).collect());
+    for r in rows {
+        out += &line(r.iter().map(|c| c.replace('|', "\\|")).collect());
+    }
+    out
+}
+
+fn csv(rows: &[Vec<String>]) -> String {
+    let cell = |c: &str| {
+        if c.contains(&[',', '"', '\n'][..]) {
+            format!("\"{}\"", c.replace('"', "\"\""))
+        } else {
+            c.to_string()
+        }
+    };
+    let mut out = format!("{}\n", REPORT_HEADER.join(","));
+    for r in rows {
+        out += &format!("{}\n", r.iter().map(|c| cell(c)).collect::<Vec<_>>().join(","));
+    }
+    out
+}
+
+/// Print a record of processing table across all services in a region
+pub async fn report(fmt: ReportFormat, conf: &Config, region: &Region) -> Result<()> {
+    let mfs = ManifestCache::load(conf, region)
+        .await?
+        .into_iter()
+        .collect::<Vec<_>>();
+    let rows = report_rows(&mfs);
+    match fmt {
+        ReportFormat::Markdown => print!("{}", markdown(&rows)),
+        ReportFormat::Csv => print!("{}", csv(&rows)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{csv, markdown, report_rows};
+    use shipcat_definitions::{structs::security::DataHandling, Manifest};
+
+    #[test]
+    fn record_of_processing() {
+        let mut dh: DataHandling = serde_yaml::from_str(
+            r#"
+stores:
+- backend: S3
+  informationClassification: protectedInternal
+  cipher: AES256
+  encrypted: true
+  fields:
+  - name: EmailAddress
+    encrypted: false
+  - name: DateOfBirth
+    keyRotator: 2w
+- backend: MySQL
+processes:
+- field: EmailAddress
+  source: fake-ask
+"#,
+        )
+        .unwrap();
+        dh.implicits();
+        let mf = Manifest {
+            name: "fake-storage".into(),
+            dataHandling: Some(dh),
+            ..Manifest::default()
+        };
+        let rows = report_rows(&[mf]);
+        assert_eq!(rows.len(), 3);
+
+        let md = markdown(&rows);
+        let lines = md.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "| Service | Team | Backend | Classification | Field | Encrypted | Cipher | Key rotator | Retention | Sources |");
+        assert_eq!(
+            lines[2],
+            "| fake-storage |  | S3 | protectedInternal | EmailAddress | false | AES256 | - | - | fake-ask |"
+        );
+        assert_eq!(
+            lines[4],
+            "| fake-storage |  | MySQL | - | - | false | - | - | - | - |"
+        );
+
+        let mut rows = rows;
+        rows[0][1] = "data, \"core\"".into();
+        let out = csv(&rows);
+        assert_eq!(
+            out.lines().nth(1).unwrap(),
+            "fake-storage,\"data, \"\"core\"\"\",S3,protectedInternal,EmailAddress,false,AES256,-,-,fake-ask"
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use super::{Config, Region, Result};
 use semver::Version;
 use shipcat_definitions::Environment;
+use shipcat_filebacked::ManifestCache;
 /// This file contains the `shipcat get` subcommand
 use std::collections::BTreeMap;
 
@@ -12,10 +13,10 @@ use std::collections::BTreeMap;
 /// Services without a hardcoded version are not returned.
 pub async fn versions(conf: &Config, region: &Region) -> Result<BTreeMap<String, Version>> {
     let mut output = BTreeMap::new();
-    for mf in shipcat_filebacked::available(conf, region).await? {
+    for mf in ManifestCache::load(conf, region).await? {
         if let Some(v) = mf.version {
             if let Ok(sv) = Version::parse(&v) {
-                output.insert(mf.base.name, sv);
+                output.insert(mf.name, sv);
             }
         }
     }
@@ -28,9 +29,9 @@ pub async fn versions(conf: &Config, region: &Region) -> Result<BTreeMap<String,
 /// Services without a hardcoded image will assume the shipcat.conf specific default
 pub async fn images(conf: &Config, region: &Region) -> Result<BTreeMap<String, String>> {
     let mut output = BTreeMap::new();
-    for mf in shipcat_filebacked::available(conf, region).await? {
+    for mf in ManifestCache::load(conf, region).await? {
         if let Some(i) = mf.image {
-            output.insert(mf.base.name, i);
+            output.insert(mf.name, i);
         }
     }
     println!("{}", serde_json::to_string_pretty(&output)?);
@@ -192,8 +193,7 @@ pub async fn apistatus(conf: &Config, reg: &Region) -> Result<()> {
     };
 
     // Get API Info from Manifests
-    for svc in shipcat_filebacked::available(conf, reg).await? {
-        let mf = shipcat_filebacked::load_manifest(&svc.base.name, &conf, &reg).await?;
+    for mf in ManifestCache::load(conf, reg).await? {
         for k in mf.kongApis {
             let mut params = APIServiceParams {
                 uris: k.uris.unwrap_or("".into()),
@@ -251,8 +251,7 @@ pub async fn eventstreams(conf: &Config, reg: &Region) -> Result<()> {
     let mut eventstreams = BTreeMap::new();
 
     // Get eventstream Info from Manifests
-    for svc in shipcat_filebacked::available(conf, reg).await? {
-        let mf = shipcat_filebacked::load_manifest(&svc.base.name, &conf, &reg).await?;
+    for mf in ManifestCache::load(conf, reg).await? {
         for k in mf.eventStreams {
             eventstreams.insert(k.name.clone(), k);
         }
@@ -317,11 +316,10 @@ pub async fn kafkausers(conf: &Config, reg: &Region) -> Result<()> {
     let mut krusers = BTreeMap::new();
 
     // Get kafka users from eventstreams struct
-    for svc in shipcat_filebacked::available(conf, reg).await? {
-        let mf = shipcat_filebacked::load_manifest(&svc.base.name, &conf, &reg).await?;
+    for mf in ManifestCache::load(conf, reg).await? {
         for k in mf.eventStreams {
             let params = EventStreamKafkaUsersParams {
-                service: String::from(&svc.base.name),
+                service: String::from(&mf.name),
                 producers: k.producers,
                 consumers: k.consumers,
             };
@@ -331,7 +329,7 @@ pub async fn kafkausers(conf: &Config, reg: &Region) -> Result<()> {
         if let Some(kr) = mf.kafkaResources {
             for user in kr.users {
                 let params = KafkaResourceUserParams {
-                    service: String::from(&svc.base.name),
+                    service: String::from(&mf.name),
                     acls: user.acls,
                 };
                 krusers.insert(user.name, params);
@@ -368,13 +366,11 @@ pub async fn kafkatopics(conf: &Config, reg: &Region) -> Result<()> {
     let mut kafkaTopics = BTreeMap::new();
 
     // Get eventstream Info from Manifests
-    for svc in shipcat_filebacked::available(conf, reg).await? {
-        let mf = shipcat_filebacked::load_manifest(&svc.base.name, &conf, &reg).await?;
-
+    for mf in ManifestCache::load(conf, reg).await? {
         // get kafka topics from eventstream struct
         for topic in mf.eventStreams {
             let params = KafkaTopicParams {
-                service: String::from(&svc.base.name),
+                service: String::from(&mf.name),
                 topicType: "EventStream".to_string(),
                 partitions: topic
                     .config
@@ -390,7 +386,7 @@ pub async fn kafkatopics(conf: &Config, reg: &Region) -> Result<()> {
         if let Some(kr) = mf.kafkaResources {
             for topic in kr.topics {
                 let params = KafkaTopicParams {
-                    service: String::from(&svc.base.name),
+                    service: String::from(&mf.name),
                     topicType: "KafkaResource".to_string(),
                     partitions: topic.partitions.to_string(),
                     replicas: topic.replicas.to_string(),
@@ -405,3 +401,101 @@ pub async fn kafkatopics(conf: &Config, reg: &Region) -> Result<()> {
     println!("{}", serde_json::to_string_pretty(&output)?);
     Ok(())
 }
+
+// ----------------------------------------------------------------------------
+// Prometheus rules reducer
+use shipcat_definitions::structs::PrometheusAlert;
+
+#[derive(Serialize)]
+struct PrometheusRuleMetadata {
+    name: String,
+    namespace: String,
+    labels: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct PrometheusRuleGroup {
+    name: String,
+    rules: Vec<PrometheusRuleAlert>,
+}
+
+#[derive(Serialize)]
+struct PrometheusRuleAlert {
+    alert: String,
+    expr: String,
+    #[serde(rename = "for")]
+    duration: String,
+    labels: BTreeMap<String, serde_json::Value>,
+    annotations: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct PrometheusRuleSpec {
+    groups: Vec<PrometheusRuleGroup>,
+}
+
+#[derive(Serialize)]
+struct PrometheusRule {
+    apiVersion: String,
+    kind: String,
+    metadata: PrometheusRuleMetadata,
+    spec: PrometheusRuleSpec,
+}
+
+fn prometheus_rule_alert(svc: &str, team: Option<&str>, pa: PrometheusAlert) -> Result<PrometheusRuleAlert> {
+    let mut labels = BTreeMap::new();
+    labels.insert("severity".to_string(), serde_json::to_value(&pa.severity)?);
+    labels.insert("service".to_string(), svc.into());
+    if let Some(t) = team {
+        labels.insert("team".to_string(), t.into());
+    }
+    let mut annotations = BTreeMap::new();
+    annotations.insert("summary".to_string(), pa.summary);
+    annotations.insert("description".to_string(), pa.description);
+    Ok(PrometheusRuleAlert {
+        alert: pa.name,
+        expr: pa.expr,
+        duration: pa.min_duration,
+        labels,
+        annotations,
+    })
+}
+
+/// Reduce the prometheusAlerts of a region into a single PrometheusRule
+///
+/// Every service with alerts gets its own rule group.
+pub async fn prometheusrules(conf: &Config, reg: &Region) -> Result<()> {
+    let mut groups = vec![];
+    for mf in ManifestCache::load(conf, reg).await? {
+        if mf.prometheusAlerts.is_empty() {
+            continue;
+        }
+        let name = mf.name;
+        let team = mf.metadata.map(|md| md.team);
+        let rules = mf
+            .prometheusAlerts
+            .into_iter()
+            .map(|pa| prometheus_rule_alert(&name, team.as_deref(), pa))
+            .collect::<Result<_>>()?;
+        groups.push(PrometheusRuleGroup {
+            name: format!("{}.alerts", name),
+            rules,
+        });
+    }
+
+    let mut labels = BTreeMap::new();
+    labels.insert("app.kubernetes.io/managed-by".to_string(), "shipcat".to_string());
+    labels.insert("region".to_string(), reg.name.clone());
+    let output = PrometheusRule {
+        apiVersion: "monitoring.coreos.com/v1".into(),
+        kind: "PrometheusRule".into(),
+        metadata: PrometheusRuleMetadata {
+            name: format!("shipcat-{}", reg.name),
+            namespace: reg.namespace.clone(),
+            labels,
+        },
+        spec: PrometheusRuleSpec { groups },
+    };
+    print!("{}", serde_yaml::to_string(&output)?);
+    Ok(())
+}
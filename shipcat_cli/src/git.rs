@@ -26,31 +26,6 @@ pub fn merge_base() -> Result<String> {
 }
 
 
-// Are there local changes in the index or working copy?
-pub fn needs_stash() -> bool {
-    exec(&["diff", "--quiet", "--exit-code"]).is_err()
-        || exec(&["diff", "--cached", "--quiet", "--exit-code"]).is_err()
-}
-
-
-// git stash
-pub fn stash_push() -> Result<String> {
-    exec(&["stash", "--quiet"])
-}
-
-
-// git stash pop
-pub fn stash_pop() -> Result<String> {
-    exec(&["stash", "pop", "--quiet"])
-}
-
-
-// git checkout <ref>
-pub fn checkout(reference: &str) -> Result<String> {
-    exec(&["checkout", reference, "--quiet"])
-}
-
-
 // git diff --name-only <ref>
 pub fn diff_filenames(reference: &str) -> Result<String> {
     exec(&["diff", "--name-only", reference])
@@ -1,7 +1,9 @@
 use petgraph::{
+    algo::tarjan_scc,
     dot,
     graph::{DiGraph, NodeIndex},
 };
+use shipcat_filebacked::ManifestCache;
 use std::fmt::{self, Debug};
 
 use super::{
@@ -72,6 +74,55 @@ pub fn nodeidx_from_name(name: &str, graph: &CatGraph) -> Option<NodeIndex> {
     None
 }
 
+/// Build the dependency graph of a set of already loaded manifests
+///
+/// Dependencies outside the set become leaf nodes with only a name.
+pub fn from_manifests(mfs: &[Manifest]) -> CatGraph {
+    let mut graph: CatGraph = DiGraph::<_, _>::new();
+    for mf in mfs {
+        if nodeidx_from_name(&mf.name, &graph).is_none() {
+            graph.add_node(ManifestNode::new(mf));
+        }
+    }
+    for mf in mfs {
+        let idx = nodeidx_from_name(&mf.name, &graph).unwrap();
+        for dep in &mf.dependencies {
+            let depidx = nodeidx_from_name(&dep.name, &graph).unwrap_or_else(|| {
+                graph.add_node(ManifestNode {
+                    name: dep.name.clone(),
+                })
+            });
+            graph.update_edge(idx, depidx, DepEdge::new(dep));
+        }
+    }
+    graph
+}
+
+/// Find cycles of synchronous (http or grpc) dependencies
+///
+/// Returns the sorted service names of every strongly connected component
+/// that contains a cycle. Message passing dependencies are ignored.
+pub fn sync_cycles(graph: &CatGraph) -> Vec<Vec<String>> {
+    let sync = graph.filter_map(
+        |_, n| Some(n.name.clone()),
+        |_, e| match e.protocol {
+            DependencyProtocol::Http | DependencyProtocol::Grpc => Some(()),
+            _ => None,
+        },
+    );
+    let mut cycles = tarjan_scc(&sync)
+        .into_iter()
+        .filter(|scc| scc.len() > 1 || sync.find_edge(scc[0], scc[0]).is_some())
+        .map(|scc| {
+            let mut names = scc.into_iter().map(|i| sync[i].clone()).collect::<Vec<_>>();
+            names.sort();
+            names
+        })
+        .collect::<Vec<_>>();
+    cycles.sort();
+    cycles
+}
+
 fn recurse_manifest(
     idx: NodeIndex,
     mf: &Manifest,
@@ -131,11 +182,11 @@ pub async fn generate(service: &str, conf: &Config, reg: &Region, dot: bool) ->
 /// But it would require: TODO: optionally filter edges around node(s)
 pub async fn full(dot: bool, conf: &Config, reg: &Region) -> Result<CatGraph> {
     let mut graph: CatGraph = DiGraph::<_, _>::new();
-    for svc in shipcat_filebacked::available(conf, reg).await? {
-        debug!("Scanning service {:?}", svc);
+    let cache = ManifestCache::load(conf, reg).await?;
+    for mf in cache.manifests() {
+        debug!("Scanning service {:?}", mf.name);
 
-        let mf = shipcat_filebacked::load_manifest(&svc.base.name, conf, reg).await?;
-        let node = ManifestNode::new(&mf);
+        let node = ManifestNode::new(mf);
         let idx = graph.add_node(node);
 
         for dep in &mf.dependencies {
@@ -144,8 +195,11 @@ pub async fn full(dot: bool, conf: &Config, reg: &Region) -> Result<CatGraph> {
                 id
             } else {
                 trace!("Found dependency new in graph: {}", dep.name);
-                let depmf = shipcat_filebacked::load_manifest(&dep.name, conf, reg).await?;
-                let depnode = ManifestNode::new(&depmf);
+                let depnode = if let Some(depmf) = cache.get(&dep.name) {
+                    ManifestNode::new(depmf)
+                } else {
+                    ManifestNode::new(&shipcat_filebacked::load_manifest(&dep.name, conf, reg).await?)
+                };
                 graph.add_node(depnode) // depidx
             };
             graph.update_edge(idx, subidx, DepEdge::new(&dep));
@@ -164,13 +218,54 @@ pub async fn full(dot: bool, conf: &Config, reg: &Region) -> Result<CatGraph> {
 /// Generate first level reverse dependencies for a service
 pub async fn reverse(service: &str, conf: &Config, reg: &Region) -> Result<Vec<String>> {
     let mut res = vec![];
-    for svc in shipcat_filebacked::available(conf, reg).await? {
-        let mf = shipcat_filebacked::load_manifest(&svc.base.name, conf, reg).await?;
+    for mf in ManifestCache::load(conf, reg).await? {
         if mf.dependencies.into_iter().any(|d| d.name == service) {
-            res.push(svc.base.name)
+            res.push(mf.name)
         }
     }
     let out = serde_yaml::to_string(&res)?;
     println!("{}", out);
     Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{from_manifests, sync_cycles};
+    use shipcat_definitions::{
+        structs::{Dependency, DependencyProtocol},
+        Manifest,
+    };
+
+    fn manifest(name: &str, deps: &[(&str, DependencyProtocol)]) -> Manifest {
+        let dependencies = deps
+            .iter()
+            .map(|(d, protocol)| Dependency {
+                name: d.to_string(),
+                protocol: protocol.clone(),
+                ..Dependency::default()
+            })
+            .collect();
+        Manifest {
+            name: name.into(),
+            dependencies,
+            ..Manifest::default()
+        }
+    }
+
+    #[test]
+    fn synchronous_cycles() {
+        use DependencyProtocol::*;
+        let mfs = vec![
+            manifest("a", &[("b", Http)]),
+            manifest("b", &[("c", Grpc), ("external", Http)]),
+            manifest("c", &[("a", Http)]),
+            // cycles through message passing are fine
+            manifest("d", &[("e", Kafka)]),
+            manifest("e", &[("d", Http)]),
+            manifest("f", &[("f", Grpc)]),
+        ];
+        let graph = from_manifests(&mfs);
+        assert_eq!(graph.node_count(), 7);
+        assert_eq!(sync_cycles(&graph), vec![vec!["a", "b", "c"], vec!["f"]]);
+    }
+}
@@ -88,21 +88,31 @@ pub async fn values(mf: &Manifest, output: &str) -> Result<()> {
 ///
 /// Generates helm values to disk, then passes it to helm template
 pub async fn template(mf: &Manifest, output: Option<PathBuf>) -> Result<String> {
+    template_from(mf, output, Path::new("charts")).await
+}
+
+/// Analogue of helm template using the charts in another directory
+///
+/// Used to template with the charts of an older revision.
+/// Charts from git are always cloned into `charts/` as they pin their own ref.
+pub async fn template_from(mf: &Manifest, output: Option<PathBuf>, charts: &Path) -> Result<String> {
     let hfile = format!("{}.helm.gen.yml", mf.name);
     values(&mf, &hfile).await?;
 
     let chart = mf.chart.clone().unwrap();
+    let mut charts = charts;
     if chart.starts_with("git@") {
         let (_tpl, tplerr, success) = clone_chart(&chart).await?;
         if !success {
             warn!("{} stderr: {}", chart, tplerr);
             bail!("helm failed to fetch template");
         }
+        charts = Path::new("charts");
     }
     // helm template with correct params
     let tplvec = vec![
         "template".into(),
-        format!("charts/{}", mf.chart.clone().unwrap()),
+        format!("{}/{}", charts.display(), chart),
         "-f".into(),
         hfile.clone(),
     ];
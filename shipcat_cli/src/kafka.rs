@@ -0,0 +1,301 @@
+use super::{
+    structs::kafkaresources::{
+        AclDefinition, KafkaUserOperation, KafkaUserPatternType, KafkaUserResourceType,
+    },
+    Config, Manifest, Region, Result,
+};
+use shipcat_definitions::Diagnostics;
+use shipcat_filebacked::ManifestCache;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What a kafka user may do with a topic
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Access {
+    /// Service defining the kafka user
+    pub service: String,
+    pub read: bool,
+    pub write: bool,
+}
+
+/// Topics of a region
+///
+/// Both `kafkaResources` topics and `eventStreams` (which become topics of the same name).
+fn topics(mfs: &[Manifest]) -> BTreeSet<String> {
+    let mut topics = BTreeSet::new();
+    for mf in mfs {
+        topics.extend(mf.eventStreams.iter().map(|es| es.name.clone()));
+        if let Some(kr) = &mf.kafkaResources {
+            topics.extend(kr.topics.iter().map(|t| t.name.clone()));
+        }
+    }
+    topics
+}
+
+/// Whether a topic ACL covers a topic name
+fn covers(acl: &AclDefinition, topic: &str) -> bool {
+    if !matches!(acl.resource_type, Some(KafkaUserResourceType::Topic)) {
+        return false;
+    }
+    match acl.pattern_type {
+        Some(KafkaUserPatternType::Prefix) => topic.starts_with(&acl.resource_name),
+        _ => acl.resource_name == "*" || acl.resource_name == topic,
+    }
+}
+
+fn reads(acl: &AclDefinition) -> bool {
+    matches!(
+        acl.operation,
+        Some(KafkaUserOperation::Read) | Some(KafkaUserOperation::All)
+    )
+}
+
+fn writes(acl: &AclDefinition) -> bool {
+    matches!(
+        acl.operation,
+        Some(KafkaUserOperation::Write) | Some(KafkaUserOperation::All)
+    )
+}
+
+/// Which kafka user can read or write which topic
+///
+/// Keyed by topic, then by kafka user name. Users without access to a topic are left out.
+pub fn access_matrix(mfs: &[Manifest]) -> BTreeMap<String, BTreeMap<String, Access>> {
+    let mut matrix = BTreeMap::new();
+    for topic in topics(mfs) {
+        let mut users = BTreeMap::new();
+        for mf in mfs {
+            for user in mf.kafkaResources.iter().flat_map(|kr| &kr.users) {
+                let acls = user
+                    .acls
+                    .iter()
+                    .filter(|acl| covers(acl, &topic))
+                    .collect::<Vec<_>>();
+                let access = Access {
+                    service: mf.name.clone(),
+                    read: acls.iter().any(|acl| reads(acl)),
+                    write: acls.iter().any(|acl| writes(acl)),
+                };
+                if access.read || access.write {
+                    users.insert(user.name.clone(), access);
+                }
+            }
+        }
+        matrix.insert(topic, users);
+    }
+    matrix
+}
+
+/// Whether an event stream producer or consumer has some access to a topic
+///
+/// Names resolve to the kafka user of that name, or to any kafka user defined by the service of that name.
+fn has_access(access: &BTreeMap<String, Access>, name: &str, check: fn(&Access) -> bool) -> bool {
+    access
+        .iter()
+        .any(|(user, a)| (user == name || a.service == name) && check(a))
+}
+
+/// Cross reference event stream users and kafka user ACLs of a region
+///
+/// Producers and consumers of an event stream need a kafka user with write or read
+/// access on its topic, and topic ACLs must refer to a topic defined by some manifest.
+/// Returns warnings by service.
+pub fn diagnose(mfs: &[Manifest]) -> BTreeMap<String, Diagnostics> {
+    let matrix = access_matrix(mfs);
+    let topics = topics(mfs);
+    // kafka users and the services defining them
+    let mut known = BTreeSet::new();
+    for mf in mfs {
+        for user in mf.kafkaResources.iter().flat_map(|kr| &kr.users) {
+            known.insert(user.name.clone());
+            known.insert(mf.name.clone());
+        }
+    }
+    let unknown = |name: &str, role: &str| {
+        format!(
+            "{} {} is neither a kafka user nor a service defining kafka users",
+            role, name
+        )
+    };
+    let mut res = BTreeMap::new();
+    for mf in mfs {
+        let mut d = Diagnostics::default();
+        for (i, es) in mf.eventStreams.iter().enumerate() {
+            let access = &matrix[&es.name];
+            for (j, p) in es.producers.iter().enumerate() {
+                let path = format!("eventStreams[{}].producers[{}]", i, j);
+                if !known.contains(p) {
+                    d.warn(&path, unknown(p, "Producer"));
+                } else if !has_access(access, p, |a| a.write) {
+                    d.warn(
+                        &path,
+                        format!(
+                            "Producer {} has no kafka user with Write access to {}",
+                            p, es.name
+                        ),
+                    );
+                }
+            }
+            for (j, c) in es.consumers.iter().enumerate() {
+                let path = format!("eventStreams[{}].consumers[{}]", i, j);
+                if !known.contains(c) {
+                    d.warn(&path, unknown(c, "Consumer"));
+                } else if !has_access(access, c, |a| a.read) {
+                    d.warn(
+                        &path,
+                        format!("Consumer {} has no kafka user with Read access to {}", c, es.name),
+                    );
+                }
+            }
+        }
+        for (i, user) in mf.kafkaResources.iter().flat_map(|kr| &kr.users).enumerate() {
+            for (j, acl) in user.acls.iter().enumerate() {
+                let is_topic = matches!(acl.resource_type, Some(KafkaUserResourceType::Topic));
+                if is_topic && acl.resource_name != "*" && !topics.iter().any(|t| covers(acl, t)) {
+                    d.warn(
+                        &format!("kafkaResources.users[{}].acls[{}]", i, j),
+                        format!(
+                            "Kafka user {} has an ACL for {} which matches no topic",
+                            user.name, acl.resource_name
+                        ),
+                    );
+                }
+            }
+        }
+        if !d.items.is_empty() {
+            res.insert(mf.name.clone(), d);
+        }
+    }
+    res
+}
+
+#[derive(Serialize)]
+struct KafkaAccessOutput {
+    region: String,
+    topics: BTreeMap<String, BTreeMap<String, Access>>,
+}
+
+/// Print the kafka access matrix of a region
+pub async fn access(conf: &Config, reg: &Region) -> Result<()> {
+    let mfs = ManifestCache::load(conf, reg)
+        .await?
+        .into_iter()
+        .collect::<Vec<_>>();
+    let output = KafkaAccessOutput {
+        region: reg.name.clone(),
+        topics: access_matrix(&mfs),
+    };
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{access_matrix, diagnose, Access};
+    use shipcat_definitions::{structs::KafkaResources, Manifest};
+
+    fn manifest(name: &str, streams: &str, resources: &str) -> Manifest {
+        Manifest {
+            name: name.into(),
+            eventStreams: serde_yaml::from_str(streams).unwrap(),
+            kafkaResources: serde_yaml::from_str::<Option<KafkaResources>>(resources).unwrap(),
+            ..Manifest::default()
+        }
+    }
+
+    fn manifests() -> Vec<Manifest> {
+        let orders = manifest(
+            "orders",
+            r#"
+- name: orders
+  producers: [orders]
+  consumers: [billing, shipping, invoicing]
+  eventDefinitions:
+  - key: id
+    value: order
+"#,
+            r#"
+users:
+- name: orders
+  acls:
+  - resourceName: orders
+    resourceType: topic
+    patternType: literal
+    operation: All
+"#,
+        );
+        let billing = manifest(
+            "billing",
+            "[]",
+            r#"
+topics:
+- name: billing-invoices
+  partitions: 1
+  replicas: 3
+users:
+- name: billing
+  acls:
+  - resourceName: orders
+    resourceType: topic
+    operation: Read
+  - resourceName: billing-
+    resourceType: topic
+    patternType: prefix
+    operation: Write
+  - resourceName: refunds
+    resourceType: topic
+    operation: Read
+  - resourceName: billing
+    resourceType: group
+    operation: Read
+"#,
+        );
+        // consumes through a kafka user named differently from the service
+        let invoicing = manifest(
+            "invoicing",
+            "[]",
+            r#"
+users:
+- name: invoicing-reader
+  acls:
+  - resourceName: orders
+    resourceType: topic
+    operation: Read
+"#,
+        );
+        vec![orders, billing, invoicing]
+    }
+
+    #[test]
+    fn matrix() {
+        let matrix = access_matrix(&manifests());
+        assert_eq!(matrix.keys().collect::<Vec<_>>(), vec![
+            "billing-invoices",
+            "orders"
+        ]);
+        assert_eq!(matrix["orders"]["orders"], Access {
+            service: "orders".into(),
+            read: true,
+            write: true
+        });
+        assert!(matrix["orders"]["billing"].read && !matrix["orders"]["billing"].write);
+        assert!(matrix["billing-invoices"]["billing"].write);
+        assert!(!matrix["billing-invoices"].contains_key("orders"));
+    }
+
+    #[test]
+    fn diagnose_acls() {
+        let found = diagnose(&manifests());
+        assert!(found.values().all(|d| !d.has_errors()));
+        let orders = &found["orders"];
+        assert_eq!(orders.items.len(), 1);
+        assert_eq!(orders.items[0].path, "eventStreams[0].consumers[1]");
+        assert_eq!(
+            orders.items[0].message,
+            "Consumer shipping is neither a kafka user nor a service defining kafka users"
+        );
+        let billing = &found["billing"];
+        assert_eq!(billing.items.len(), 1);
+        assert_eq!(billing.items[0].path, "kafkaResources.users[0].acls[2]");
+        assert!(!found.contains_key("invoicing"));
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
     io::{self, Write},
 };
 
@@ -10,6 +11,7 @@ use super::{
     },
     Config, KongConfig, Region, Result,
 };
+use shipcat_filebacked::ManifestCache;
 
 /// KongOutput matches the format expected by the Kong Configurator script
 #[derive(Serialize)]
@@ -73,9 +75,9 @@ pub async fn generate_kong_output(conf: &Config, region: &Region) -> Result<Kong
     let mut apis = BTreeMap::new();
     if let Some(kong) = &region.kong {
         // Generate list of APIs to feed to Kong
-        for mf in shipcat_filebacked::available(conf, region).await? {
-            debug!("Scanning service {:?}", mf);
-            for k in mf.kong_apis {
+        for mf in ManifestCache::load(conf, region).await? {
+            debug!("Scanning service {}", mf.name);
+            for k in mf.kongApis {
                 if let Some(clash) = apis.insert(k.name.clone(), k) {
                     bail!("A Kong API named {:?} is already defined", clash.name);
                 }
@@ -131,3 +133,356 @@ pub fn config_url(region: &Region) -> Result<()> {
     }
     Ok(())
 }
+
+// ----------------------------------------------------------------------------
+// Route conflicts
+
+/// Service name used for routes from `Region::kong` extra apis
+const EXTRA_APIS: &str = "kong.extra_apis";
+
+/// A single route of a Kong API
+///
+/// APIs with several comma separated `uris` get one route per uri.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Route {
+    pub service: String,
+    pub api: String,
+    /// Uri prefix without trailing slashes (None when routing on hosts only)
+    pub uri: Option<String>,
+    pub hosts: Vec<String>,
+    pub strip_uri: bool,
+}
+
+impl Route {
+    fn from_api(service: &str, api: &Kong) -> Vec<Route> {
+        let route = |uri: Option<String>| Route {
+            service: service.to_string(),
+            api: api.name.clone(),
+            uri,
+            hosts: api.hosts.clone(),
+            strip_uri: api.strip_uri,
+        };
+        let uris = api
+            .uris
+            .iter()
+            .flat_map(|u| u.split(','))
+            .map(|u| u.trim())
+            .filter(|u| !u.is_empty())
+            .map(|u| {
+                let trimmed = u.trim_end_matches('/');
+                if trimmed.is_empty() { "/" } else { trimmed }.to_string()
+            })
+            .collect::<Vec<_>>();
+        if uris.is_empty() {
+            vec![route(None)]
+        } else {
+            uris.into_iter().map(|u| route(Some(u))).collect()
+        }
+    }
+
+    /// Hosts both routes can receive requests for
+    ///
+    /// Routes without hosts match every host.
+    fn shared_hosts(&self, other: &Route) -> Option<Vec<String>> {
+        match (self.hosts.is_empty(), other.hosts.is_empty()) {
+            (true, true) => Some(vec![]),
+            (true, false) => Some(other.hosts.clone()),
+            (false, true) => Some(self.hosts.clone()),
+            (false, false) => {
+                let shared = self
+                    .hosts
+                    .iter()
+                    .filter(|h| other.hosts.contains(h))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if shared.is_empty() {
+                    None
+                } else {
+                    Some(shared)
+                }
+            }
+        }
+    }
+}
+
+/// How two routes conflict
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConflictKind {
+    /// Both routes claim the same uri
+    Exact,
+    /// One uri is a path prefix of the other, so the shorter one can shadow the longer one
+    ///
+    /// Routes that disagree on `strip_uri` are an error.
+    /// Kong prefers the longest match, so an overlap across services that strips consistently
+    /// is only a warning rather than failing like other conflicts.
+    Prefix,
+    /// Both routes claim a host without any uri to distinguish them
+    Host,
+}
+
+/// A pair of conflicting routes
+#[derive(Clone, Debug)]
+pub struct RouteConflict {
+    pub kind: ConflictKind,
+    pub first: Route,
+    pub second: Route,
+    pub hosts: Vec<String>,
+}
+
+impl RouteConflict {
+    /// Whether the conflict only shadows part of a route, rather than making routing ambiguous
+    pub fn is_warning(&self) -> bool {
+        self.kind == ConflictKind::Prefix
+            && self.first.service != self.second.service
+            && self.first.strip_uri == self.second.strip_uri
+    }
+
+    fn new(kind: ConflictKind, first: &Route, second: &Route, hosts: Vec<String>) -> Self {
+        RouteConflict {
+            kind,
+            first: first.clone(),
+            second: second.clone(),
+            hosts,
+        }
+    }
+}
+
+impl fmt::Display for RouteConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (a, b) = (&self.first, &self.second);
+        let on = if self.hosts.is_empty() {
+            "".to_string()
+        } else {
+            format!(" on {}", self.hosts.join(","))
+        };
+        let uri = |r: &Route| r.uri.clone().unwrap_or_else(|| "*".into());
+        match self.kind {
+            ConflictKind::Exact => write!(f, "{} and {} both route {}{}", a.api, b.api, uri(a), on)?,
+            ConflictKind::Prefix => write!(
+                f,
+                "{} route {} overlaps {} route {}{}",
+                a.api,
+                uri(a),
+                b.api,
+                uri(b),
+                on
+            )?,
+            ConflictKind::Host => write!(f, "{} and {} both claim host{}", a.api, b.api, on)?,
+        }
+        if a.strip_uri != b.strip_uri {
+            write!(f, " with inconsistent strip_uri")?;
+        }
+        write!(f, " ({} vs {})", a.service, b.service)
+    }
+}
+
+/// Build the full route table for a region from the kong apis of services
+///
+/// Extra apis from `Region::kong` are included.
+pub fn route_table(services: Vec<(String, Vec<Kong>)>, region: &Region) -> Vec<Route> {
+    let mut routes = vec![];
+    for (svc, apis) in services {
+        for api in &apis {
+            routes.extend(Route::from_api(&svc, api));
+        }
+    }
+    if let Some(kong) = &region.kong {
+        for (name, api) in &kong.extra_apis {
+            let mut api = api.clone();
+            api.name = name.clone();
+            routes.extend(Route::from_api(EXTRA_APIS, &api));
+        }
+    }
+    routes
+}
+
+/// Whether `prefix` covers `uri` as a whole number of path segments
+fn is_path_prefix(prefix: &str, uri: &str) -> bool {
+    uri.starts_with(&format!("{}/", prefix.trim_end_matches('/')))
+}
+
+/// Find routes that make Kong routing ambiguous
+///
+/// Uris are compared by whole path segments on any shared host, so `/a` overlaps `/a/b` but not `/abc`.
+/// Prefix overlaps within a service are only conflicts when they disagree on `strip_uri`.
+/// Hosts only conflict when neither route has a uri, as Kong routes a more specific uri first.
+/// Routes of one api are never compared with each other, but apis of the same name in different services are.
+pub fn route_conflicts(routes: &[Route]) -> Vec<RouteConflict> {
+    let mut conflicts = vec![];
+    for (i, a) in routes.iter().enumerate() {
+        for b in &routes[i + 1..] {
+            if a.api == b.api && a.service == b.service {
+                continue;
+            }
+            let hosts = match a.shared_hosts(b) {
+                Some(h) => h,
+                None => continue,
+            };
+            let consistent = a.service == b.service && a.strip_uri == b.strip_uri;
+            match (&a.uri, &b.uri) {
+                (Some(x), Some(y)) if x == y => {
+                    conflicts.push(RouteConflict::new(ConflictKind::Exact, a, b, hosts))
+                }
+                (Some(x), Some(y)) if !consistent && (is_path_prefix(y, x) || is_path_prefix(x, y)) => {
+                    let (short, long) = if x.len() < y.len() { (a, b) } else { (b, a) };
+                    conflicts.push(RouteConflict::new(ConflictKind::Prefix, short, long, hosts))
+                }
+                (None, None) if !a.hosts.is_empty() && !b.hosts.is_empty() => {
+                    conflicts.push(RouteConflict::new(ConflictKind::Host, a, b, hosts))
+                }
+                _ => {}
+            }
+        }
+    }
+    conflicts
+}
+
+/// Check the kong route table of a region for conflicts
+///
+/// Prints every conflict and fails with the services in conflicts that are not just warnings.
+pub async fn check(conf: &Config, region: &Region) -> Result<()> {
+    if region.kong.is_none() {
+        bail!("kong not available in {}", region.name)
+    }
+    let mut services = vec![];
+    for mf in ManifestCache::load(conf, region).await? {
+        services.push((mf.name, mf.kongApis));
+    }
+    let routes = route_table(services, region);
+    let conflicts = route_conflicts(&routes);
+    if conflicts.is_empty() {
+        info!(
+            "{} kong routes in {} have no conflicts",
+            routes.len(),
+            region.name
+        );
+        return Ok(());
+    }
+    for c in &conflicts {
+        if c.is_warning() {
+            warn!("{}", c);
+        } else {
+            println!("{}", c);
+        }
+    }
+    let errors = conflicts.iter().filter(|c| !c.is_warning()).collect::<Vec<_>>();
+    if errors.is_empty() {
+        info!(
+            "{} kong routes in {} only have prefix overlaps between services",
+            routes.len(),
+            region.name
+        );
+        return Ok(());
+    }
+    let services = errors
+        .iter()
+        .flat_map(|c| vec![c.first.service.clone(), c.second.service.clone()])
+        .collect::<BTreeSet<_>>();
+    bail!(
+        "{} kong route conflicts between {}",
+        errors.len(),
+        services.into_iter().collect::<Vec<_>>().join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{route_conflicts, ConflictKind, Route};
+    use shipcat_definitions::structs::Kong;
+
+    fn api(name: &str, uris: Option<&str>, hosts: &[&str], strip_uri: bool) -> Kong {
+        Kong {
+            name: name.into(),
+            uris: uris.map(String::from),
+            hosts: hosts.iter().map(|h| h.to_string()).collect(),
+            strip_uri,
+            ..Kong::default()
+        }
+    }
+
+    fn conflicts(apis: Vec<(&str, Kong)>) -> Vec<(ConflictKind, String, String)> {
+        let routes = apis
+            .iter()
+            .flat_map(|(svc, k)| Route::from_api(svc, k))
+            .collect::<Vec<_>>();
+        route_conflicts(&routes)
+            .into_iter()
+            .map(|c| (c.kind, c.first.api, c.second.api))
+            .collect()
+    }
+
+    #[test]
+    fn route_splitting() {
+        let routes = Route::from_api("a", &api("a", Some("/a/, /b/v1/"), &[], false));
+        let uris = routes.into_iter().map(|r| r.uri.unwrap()).collect::<Vec<_>>();
+        assert_eq!(uris, vec!["/a", "/b/v1"]);
+    }
+
+    #[test]
+    fn detect_conflicts() {
+        // distinct routes, except a route without hosts claiming the uri on every host
+        let found = conflicts(vec![
+            ("a", api("a", Some("/a"), &[], false)),
+            ("b", api("b", Some("/b"), &[], false)),
+            ("c", api("c", None, &["c.example.com"], false)),
+            ("d", api("d", Some("/a"), &["d.example.com"], false)),
+        ]);
+        assert_eq!(found, vec![(
+            ConflictKind::Exact,
+            "a".to_string(),
+            "d".to_string()
+        )]);
+
+        // exact, prefix and host conflicts, but a uri on a claimed host is more specific
+        let found = conflicts(vec![
+            ("a", api("a", Some("/a,/shared"), &[], false)),
+            ("b", api("b", Some("/shared/"), &[], false)),
+            ("c", api("c", Some("/a/bc"), &[], true)),
+            ("f", api("f", Some("/abc"), &[], false)),
+            ("d", api("d", None, &["d.example.com"], false)),
+            ("e", api("e", Some("/e"), &["d.example.com"], false)),
+            ("g", api("g", None, &["d.example.com", "g.example.com"], false)),
+        ]);
+        assert_eq!(found, vec![
+            (ConflictKind::Prefix, "a".to_string(), "c".to_string()),
+            (ConflictKind::Exact, "a".to_string(), "b".to_string()),
+            (ConflictKind::Host, "d".to_string(), "g".to_string()),
+        ]);
+
+        // prefix overlaps across services are errors when stripping differently
+        let routes = [
+            Route::from_api("a", &api("a", Some("/a"), &[], false)),
+            Route::from_api("b", &api("b", Some("/a/b"), &[], false)),
+            Route::from_api("c", &api("c", Some("/a/c"), &[], true)),
+        ]
+        .concat();
+        let found = route_conflicts(&routes);
+        assert_eq!(found.len(), 2);
+        assert!(found[0].is_warning());
+        assert!(!found[1].is_warning());
+
+        // nested apis of one service only conflict when stripping differently
+        assert!(conflicts(vec![
+            ("a", api("a", Some("/a"), &[], false)),
+            ("a", api("a-v2", Some("/a/v2"), &[], false)),
+        ])
+        .is_empty());
+        let routes = [
+            Route::from_api("a", &api("a", Some("/a"), &[], false)),
+            Route::from_api("a", &api("a-v2", Some("/a/v2"), &[], true)),
+        ]
+        .concat();
+        let found = route_conflicts(&routes);
+        assert_eq!(found.len(), 1);
+        assert!(!found[0].is_warning());
+
+        // apis of the same name in different services still conflict
+        assert_eq!(
+            conflicts(vec![
+                ("a", api("shared", Some("/a"), &[], false)),
+                ("b", api("shared", Some("/a"), &[], false)),
+            ]),
+            vec![(ConflictKind::Exact, "shared".to_string(), "shared".to_string())]
+        );
+    }
+}
@@ -1,16 +1,18 @@
 use crate::{ErrorKind, Manifest, Result};
 use k8s_openapi::api::{
     apps::v1::{Deployment, ReplicaSet, StatefulSet},
-    core::v1::Pod,
+    batch::v1beta1::CronJob,
+    core::v1::{Event, Pod},
 };
 use kube::{
-    api::{Api, DeleteParams, ListParams, LogParams, Object, ObjectList, PatchParams, Resource},
+    api::{Api, DeleteParams, ListParams, LogParams, Object, ObjectList, ObjectMeta, PatchParams, Resource},
     client::APIClient,
 };
 use shipcat_definitions::{
     manifest::ShipcatManifest,
     status::{Applier, ManifestStatus},
 };
+use std::collections::BTreeMap;
 
 /// Client creator
 ///
@@ -40,6 +42,7 @@ pub struct ShipKube {
     api: Api<ShipcatManifest>,
     name: String,
     namespace: String,
+    workload: String,
 }
 
 /// Entry points for shipcat::apply, and shipcat::status
@@ -53,6 +56,7 @@ impl ShipKube {
         Ok(Self {
             name: svc.to_string(),
             namespace: ns.to_string(),
+            workload: svc.to_string(),
             applier: Applier::infer(),
             api,
             client,
@@ -64,6 +68,26 @@ impl ShipKube {
         Self::new_within(&mf.name, &mf.namespace).await
     }
 
+    /// Name of the workload the workload getters are scoped to
+    pub fn workload(&self) -> &str {
+        &self.workload
+    }
+
+    /// Interface scoped to a secondary workload of the same service (e.g. a worker)
+    ///
+    /// Workload getters use the workload name, but the crd calls still target the service.
+    pub fn scoped(&self, workload: &str) -> Self {
+        Self {
+            name: self.name.clone(),
+            namespace: self.namespace.clone(),
+            workload: workload.to_string(),
+            applier: self.applier.clone(),
+            api: self.api.clone(),
+            client: self.client.clone(),
+            mfs: self.mfs.clone(),
+        }
+    }
+
     /// Apply a Manifest (e.g. it's CRD wrapper)
     pub async fn apply(&self, mf: Manifest) -> Result<bool> {
         assert!(mf.version.is_some()); // ensure crd is in right state w/o secrets
@@ -129,18 +153,25 @@ impl ShipKube {
     pub async fn get_pods(&self) -> Result<ObjectList<Pod>> {
         let api: Api<Pod> = Api::namespaced(self.client.clone(), &self.namespace);
         let lp = ListParams {
-            label_selector: Some(format!("app={}", self.name)),
+            label_selector: Some(format!("app={}", self.workload)),
             ..Default::default()
         };
         let pods = api.list(&lp).await.map_err(ErrorKind::KubeError)?;
         Ok(pods)
     }
 
+    // helper to get a single pod
+    pub async fn get_pod(&self, podname: &str) -> Result<Pod> {
+        let api: Api<Pod> = Api::namespaced(self.client.clone(), &self.namespace);
+        let pod = api.get(podname).await.map_err(ErrorKind::KubeError)?;
+        Ok(pod)
+    }
+
     // helper to get pods by pod hash
     pub async fn get_pods_by_template_hash(&self, hash: &str) -> Result<ObjectList<Pod>> {
         let api: Api<Pod> = Api::namespaced(self.client.clone(), &self.namespace);
         let lp = ListParams {
-            label_selector: Some(format!("app={},pod-template-hash={}", self.name, hash)),
+            label_selector: Some(format!("app={},pod-template-hash={}", self.workload, hash)),
             ..Default::default()
         };
         let pods = api.list(&lp).await.map_err(ErrorKind::KubeError)?;
@@ -163,7 +194,7 @@ impl ShipKube {
     pub async fn get_rs(&self) -> Result<ObjectList<ReplicaSet>> {
         let api: Api<ReplicaSet> = Api::namespaced(self.client.clone(), &self.namespace);
         let lp = ListParams {
-            label_selector: Some(format!("app={}", self.name)),
+            label_selector: Some(format!("app={}", self.workload)),
             ..Default::default()
         };
         let rs = api.list(&lp).await.map_err(ErrorKind::KubeError)?;
@@ -174,7 +205,7 @@ impl ShipKube {
     pub async fn get_rs_by_template_hash(&self, hash: &str) -> Result<Option<ReplicaSet>> {
         let api: Api<ReplicaSet> = Api::namespaced(self.client.clone(), &self.namespace);
         let lp = ListParams {
-            label_selector: Some(format!("app={},pod-template-hash={}", self.name, hash)),
+            label_selector: Some(format!("app={},pod-template-hash={}", self.workload, hash)),
             ..Default::default()
         };
         let rs = api.list(&lp).await.map_err(ErrorKind::KubeError)?;
@@ -187,13 +218,13 @@ impl ShipKube {
         let replicasets: Api<ReplicaSet> = Api::namespaced(self.client.clone(), &self.namespace);
 
         // Get owning deployment and its revision annotation
-        let dep = deps.get(&self.name).await.map_err(ErrorKind::KubeError)?;
+        let dep = deps.get(&self.workload).await.map_err(ErrorKind::KubeError)?;
         let mut rev = None;
         if let Some(meta) = dep.metadata {
             if let Some(annot) = meta.annotations {
                 if let Some(r) = annot.get("deployment.kubernetes.io/revision") {
                     rev = Some(r.clone());
-                    debug!("Desired deployment revision for {} is {}", self.name, r);
+                    debug!("Desired deployment revision for {} is {}", self.workload, r);
                 }
             }
         }
@@ -202,7 +233,7 @@ impl ShipKube {
         if let Some(desired) = rev {
             // Find all replicasets with our app label
             let lp = ListParams {
-                label_selector: Some(format!("app={}", self.name)),
+                label_selector: Some(format!("app={}", self.workload)),
                 ..Default::default()
             };
             let rs = replicasets.list(&lp).await.map_err(ErrorKind::KubeError)?;
@@ -215,7 +246,7 @@ impl ShipKube {
                         if let Some(annot) = &meta.annotations {
                             if let Some(found) = annot.get("deployment.kubernetes.io/revision") {
                                 if found == &desired {
-                                    debug!("Tracking replicaset revision {} for {}", found, self.name);
+                                    debug!("Tracking replicaset revision {} for {}", found, self.workload);
                                     return true;
                                 }
                             }
@@ -234,17 +265,96 @@ impl ShipKube {
         }
     }
 
+    // helper to get warning events in the namespace
+    //
+    // Events are not labelled, so callers filter on the involved object.
+    pub async fn get_warning_events(&self) -> Result<ObjectList<Event>> {
+        let api: Api<Event> = Api::namespaced(self.client.clone(), &self.namespace);
+        let lp = ListParams {
+            field_selector: Some("type=Warning".into()),
+            ..Default::default()
+        };
+        let events = api.list(&lp).await.map_err(ErrorKind::KubeError)?;
+        Ok(events)
+    }
+
     // helper to get deployment data
     pub async fn get_deploy(&self) -> Result<Deployment> {
         let api: Api<Deployment> = Api::namespaced(self.client.clone(), &self.namespace);
-        let deps = api.get(&self.name).await.map_err(ErrorKind::KubeError)?;
+        let deps = api.get(&self.workload).await.map_err(ErrorKind::KubeError)?;
         Ok(deps)
     }
 
+    // helper to get cronjob data
+    pub async fn get_cronjob(&self, name: &str) -> Result<CronJob> {
+        let api: Api<CronJob> = Api::namespaced(self.client.clone(), &self.namespace);
+        let cj = api.get(name).await.map_err(ErrorKind::KubeError)?;
+        Ok(cj)
+    }
+
     // helper to get statefulset data
     pub async fn get_statefulset(&self) -> Result<StatefulSet> {
         let api: Api<StatefulSet> = Api::namespaced(self.client.clone(), &self.namespace);
-        let ssets = api.get(&self.name).await.map_err(ErrorKind::KubeError)?;
+        let ssets = api.get(&self.workload).await.map_err(ErrorKind::KubeError)?;
         Ok(ssets)
     }
+
+    // helper to move the rolling update partition of a statefulset
+    pub async fn set_statefulset_partition(&self, partition: u32) -> Result<()> {
+        let api: Api<StatefulSet> = Api::namespaced(self.client.clone(), &self.namespace);
+        let data = serde_json::json!({
+            "spec": {
+                "updateStrategy": {
+                    "type": "RollingUpdate",
+                    "rollingUpdate": { "partition": partition }
+                }
+            }
+        });
+        let pp = PatchParams::default();
+        api.patch(&self.workload, &pp, serde_json::to_vec(&data)?)
+            .await
+            .map_err(ErrorKind::KubeError)?;
+        Ok(())
+    }
+}
+
+/// Current usage of a container as reported by the metrics api
+#[derive(Deserialize)]
+pub struct ContainerMetrics {
+    pub name: String,
+    /// Raw quantities keyed by resource name (cpu, memory)
+    pub usage: BTreeMap<String, String>,
+}
+
+/// Current usage of a pod as reported by the metrics api
+#[derive(Deserialize)]
+pub struct PodMetrics {
+    pub metadata: ObjectMeta,
+    pub containers: Vec<ContainerMetrics>,
+}
+
+#[derive(Deserialize)]
+struct PodMetricsList {
+    items: Vec<PodMetrics>,
+}
+
+/// Fetch current pod usage in a namespace from metrics.k8s.io
+///
+/// Requires metrics-server (or equivalent) to be installed in the cluster.
+pub async fn get_pod_metrics(ns: &str) -> Result<Vec<PodMetrics>> {
+    let client = make_client().await?;
+    // metrics.k8s.io has no k8s_openapi type, but the url is built the same way
+    let res = Resource {
+        api_version: "metrics.k8s.io/v1beta1".into(),
+        group: "metrics.k8s.io".into(),
+        kind: "Pod".into(),
+        version: "v1beta1".into(),
+        namespace: Some(ns.to_string()),
+    };
+    let req = res.list(&ListParams::default()).map_err(ErrorKind::KubeError)?;
+    let list = client
+        .request::<PodMetricsList>(req)
+        .await
+        .map_err(ErrorKind::KubeError)?;
+    Ok(list.items)
 }
@@ -317,9 +317,13 @@ async fn find_all_manifest_crds(ns: &str) -> Result<Vec<String>> {
     Ok(out.split(' ').map(String::from).collect())
 }
 
-use std::path::PathBuf;
+use std::{env, path::PathBuf};
 // Kubectl diff experiment (ignores secrets)
-pub async fn diff(pth: PathBuf, ns: &str) -> Result<(String, String, bool)> {
+//
+// With `full_context`, whole objects are diffed so yaml paths can be worked out from the output.
+// This needs a kubectl that accepts arguments in KUBECTL_EXTERNAL_DIFF (1.18+),
+// and is skipped if KUBECTL_EXTERNAL_DIFF is already set.
+pub async fn diff(pth: PathBuf, ns: &str, full_context: bool) -> Result<(String, String, bool)> {
     let args = vec![
         "diff".into(),
         format!("-n={}", ns),
@@ -328,7 +332,11 @@ pub async fn diff(pth: PathBuf, ns: &str) -> Result<(String, String, bool)> {
     // need the error code here so re-implent - and discard stderr
     debug!("kubectl {}", args.join(" "));
 
-    let s = Command::new("kubectl").args(&args).output().await?;
+    let mut cmd = Command::new("kubectl");
+    if full_context && env::var_os("KUBECTL_EXTERNAL_DIFF").is_none() {
+        cmd.env("KUBECTL_EXTERNAL_DIFF", "diff -u -N --unified=1000000");
+    }
+    let s = cmd.args(&args).output().await?;
     let out: String = String::from_utf8_lossy(&s.stdout).into();
     let err: String = String::from_utf8_lossy(&s.stderr).into();
     trace!("out: {}, err: {}", out, err);
@@ -69,6 +69,10 @@ error_chain! {
             description("upgrade timed out")
             display("{} upgrade timed out waiting {}s for deployment(s) to come online", &svc, secs)
         }
+        WorkloadFailure(svc: String, workload: String) {
+            description("workload failed to roll out")
+            display("{} upgrade failed: {} is unhealthy", &svc, &workload)
+        }
         SlackSendFailure(hook: String) {
             description("slack message send failed")
             display("Failed to send the slack message to '{}' ", &hook)
@@ -144,6 +148,24 @@ pub use top::{OutputFormat, ResourceOrder};
 /// Diffing module for values
 pub mod diff;
 
+/// Cross-region manifest comparison
+pub mod compare;
+
+/// Markdown summaries of manifest changes for pull requests
+pub mod prsummary;
+
+/// Language server for manifests
+pub mod lsp;
+
+/// Cron job schedules and upcoming runs
+pub mod cronjobs;
+
+/// Kafka access across services
+pub mod kafka;
+
+/// Container registry lookups
+pub mod registry;
+
 /// Git stuff
 pub mod git;
 
@@ -157,6 +179,15 @@ pub use webhooks::UpgradeState;
 /// Simple printers
 pub mod show;
 
+/// Canonical formatting of manifest files
+pub mod fmt;
+
+/// Rewrites of deprecated manifest and config syntax
+pub mod migrate;
+
+/// New services from scaffolds
+pub mod scaffold;
+
 /// Cluster auth
 pub mod auth;
 
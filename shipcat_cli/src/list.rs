@@ -1,5 +1,6 @@
 /// This file contains all the hidden `shipcat list-*` subcommands
 use super::{Config, Region, Result};
+use shipcat_filebacked::ManifestCache;
 
 /// Print the supported regions
 pub fn regions(conf: &Config) -> Result<()> {
@@ -20,9 +21,8 @@ pub fn locations(conf: &Config) -> Result<()> {
 /// Print supported services in a region
 /// TODO: this one needs to do the guess outside in main!
 pub async fn services(conf: &Config, region: &Region) -> Result<()> {
-    let services = shipcat_filebacked::available(conf, region).await?;
-    for svc in services {
-        println!("{}", &svc.base.name);
+    for svc in ManifestCache::load(conf, region).await?.names() {
+        println!("{}", svc);
     }
     Ok(())
 }
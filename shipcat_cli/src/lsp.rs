@@ -0,0 +1,583 @@
+use super::{Config, Manifest, Region, Result};
+use crate::error_chain::ChainedError;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use tokio::io::{self, AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use url::Url;
+
+// ----------------------------------------------------------------------------------
+// json-rpc framing
+
+/// Read a single `Content-Length` framed message
+///
+/// Returns None when the client closed the stream.
+async fn read_message<R: AsyncBufRead + Unpin>(r: &mut R) -> Result<Option<Value>> {
+    let mut len = None;
+    loop {
+        let mut header = String::new();
+        if r.read_line(&mut header).await? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(v) = header.strip_prefix("Content-Length:") {
+            len = Some(v.trim().parse::<usize>()?);
+        }
+    }
+    let len = match len {
+        Some(l) => l,
+        None => bail!("lsp message without Content-Length"),
+    };
+    let mut body = vec![0; len];
+    r.read_exact(&mut body).await?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+async fn write_message<W: AsyncWrite + Unpin>(w: &mut W, msg: &Value) -> Result<()> {
+    let body = serde_json::to_string(msg)?;
+    let framed = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+    w.write_all(framed.as_bytes()).await?;
+    w.flush().await?;
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------------
+// yaml helpers
+
+/// Indentation and content of a yaml line, with list markers stripped
+///
+/// Returns the indentation of every list marker, and of the content itself.
+fn split_line(line: &str) -> (Vec<usize>, usize, &str) {
+    let mut indent = line.len() - line.trim_start().len();
+    let mut content = line.trim_start();
+    let mut markers = vec![];
+    while content.starts_with("- ") || content == "-" {
+        markers.push(indent);
+        let rest = content[1..].trim_start();
+        indent += content.len() - rest.len();
+        content = rest;
+    }
+    (markers, indent, content)
+}
+
+/// The key of a yaml `key: value` line (if any)
+fn line_key(content: &str) -> Option<&str> {
+    if content.starts_with('#') {
+        return None;
+    }
+    let end = if content.ends_with(':') {
+        content.len() - 1
+    } else {
+        content.find(": ")?
+    };
+    Some(content[..end].trim_matches('"').trim_matches('\''))
+}
+
+/// The value of a yaml `key: value` line (if any)
+fn line_value(line: &str) -> Option<&str> {
+    let (_, _, content) = split_line(line);
+    let v = content[content.find(": ")? + 2..].trim();
+    Some(v.trim_matches('"').trim_matches('\''))
+}
+
+/// Path of keys leading to the given line, using indentation
+///
+/// List items are represented as `[]` segments, so the name of the second dependency is
+/// `["dependencies", "[]", "name"]`.
+fn key_path(text: &str, line: usize) -> Vec<String> {
+    let mut stack: Vec<(usize, String)> = vec![];
+    for l in text.lines().take(line + 1) {
+        let (markers, indent, content) = split_line(l);
+        if content.is_empty() && markers.is_empty() || content.starts_with('#') {
+            continue;
+        }
+        for m in markers {
+            // sequences may sit at the same indent as their parent key
+            while let Some((i, s)) = stack.last() {
+                if *i < m || (*i == m && s != "[]") {
+                    break;
+                }
+                stack.pop();
+            }
+            stack.push((m, "[]".into()));
+        }
+        if let Some(k) = line_key(content) {
+            while let Some((i, _)) = stack.last() {
+                if *i < indent {
+                    break;
+                }
+                stack.pop();
+            }
+            stack.push((indent, k.to_string()));
+        }
+    }
+    stack.into_iter().map(|(_, k)| k).collect()
+}
+
+/// Best effort line and column for an error message
+///
+/// Looks for the first backticked or quoted name in the message that is a key or value in the file.
+fn locate(text: &str, msg: &str) -> Option<(usize, usize)> {
+    let quoted = Regex::new(r#"[`'"]([\w\-\./]+)[`'"]"#).unwrap();
+    for cap in quoted.captures_iter(msg) {
+        let name = &cap[1];
+        for (i, l) in text.lines().enumerate() {
+            let (_, indent, content) = split_line(l);
+            if line_key(content) == Some(name) {
+                return Some((i, indent));
+            }
+            if line_value(l) == Some(name) {
+                return Some((i, l.rfind(name).unwrap_or(indent)));
+            }
+        }
+    }
+    None
+}
+
+// ----------------------------------------------------------------------------------
+// schema helpers
+
+/// Follow `$ref`s and pick the non-null alternative of optional types
+fn resolve<'a>(root: &'a Value, node: &'a Value) -> &'a Value {
+    if let Some(r) = node["$ref"].as_str() {
+        let name = r.trim_start_matches("#/definitions/");
+        return resolve(root, &root["definitions"][name]);
+    }
+    for k in &["anyOf", "allOf", "oneOf"] {
+        if let Some(alts) = node[*k].as_array() {
+            if let Some(alt) = alts.iter().find(|a| a["type"] != "null") {
+                return resolve(root, alt);
+            }
+        }
+    }
+    node
+}
+
+/// Documentation for the schema property at a key path
+fn describe(root: &Value, path: &[String]) -> Option<String> {
+    let mut node = root;
+    for seg in path {
+        let parent = resolve(root, node);
+        node = if seg == "[]" {
+            &parent["items"]
+        } else if parent["properties"][seg].is_object() {
+            &parent["properties"][seg]
+        } else if parent["additionalProperties"].is_object() {
+            &parent["additionalProperties"]
+        } else {
+            return None;
+        };
+    }
+    node["description"]
+        .as_str()
+        .or_else(|| resolve(root, node)["description"].as_str())
+        .map(String::from)
+}
+
+// ----------------------------------------------------------------------------------
+// lsp structures
+
+fn position(line: usize, character: usize) -> Value {
+    json!({ "line": line, "character": character })
+}
+
+/// Column of a byte offset in a line, in the utf-16 code units lsp counts in
+fn utf16_column(line: &str, byte: usize) -> usize {
+    line.get(..byte).unwrap_or(line).encode_utf16().count()
+}
+
+/// Diagnostic from a line and a byte offset into that line
+fn diagnostic(text: &str, line: usize, byte: usize, message: String) -> Value {
+    let l = text.lines().nth(line).unwrap_or_default();
+    let (start, end) = (utf16_column(l, byte), utf16_column(l, l.len()));
+    json!({
+        "range": { "start": position(line, start), "end": position(line, end.max(start)) },
+        "severity": 1,
+        "source": "shipcat",
+        "message": message,
+    })
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    Url::parse(uri).ok()?.to_file_path().ok()
+}
+
+fn path_to_uri(pth: &Path) -> Option<String> {
+    Url::from_file_path(pth).ok().map(|u| u.to_string())
+}
+
+/// Service name and file name for a file in `services/{svc}/`
+fn service_file(uri: &str) -> Option<(String, String)> {
+    let pth = uri_to_path(uri)?;
+    let file = pth.file_name()?.to_str()?.to_string();
+    let dir = pth.parent()?;
+    if dir.parent()?.file_name()? != "services" || !(file.ends_with(".yml") || file.ends_with(".yaml")) {
+        return None;
+    }
+    Some((dir.file_name()?.to_str()?.to_string(), file))
+}
+
+// ----------------------------------------------------------------------------------
+// server
+
+/// Language server state
+pub struct Server {
+    conf: Config,
+    manifest_schema: Value,
+    overrides_schema: Value,
+    docs_schema: Value,
+    docs: BTreeMap<String, String>,
+    root: PathBuf,
+}
+
+impl Server {
+    pub fn new(conf: Config) -> Result<Self> {
+        Ok(Server {
+            conf,
+            manifest_schema: serde_json::to_value(shipcat_filebacked::manifest_schema())?,
+            overrides_schema: serde_json::to_value(shipcat_filebacked::overrides_schema())?,
+            docs_schema: serde_json::to_value(Manifest::schema())?,
+            docs: BTreeMap::new(),
+            root: std::env::current_dir()?,
+        })
+    }
+
+    /// Syntax diagnostics for an open document
+    fn parse_diagnostics(&self, file: &str, text: &str) -> Vec<Value> {
+        match shipcat_filebacked::check_source(file, text) {
+            Ok(_) => vec![],
+            Err(e) => {
+                // flattened structs lose their locations, so prefer a named field
+                let (line, col) = match (locate(text, &e.to_string()), e.location()) {
+                    (Some(pos), _) => pos,
+                    (None, Some(loc)) => {
+                        // yaml columns count characters
+                        let line = loc.line().saturating_sub(1);
+                        let l = text.lines().nth(line).unwrap_or_default();
+                        let col = l
+                            .char_indices()
+                            .nth(loc.column().saturating_sub(1))
+                            .map_or(l.len(), |(i, _)| i);
+                        (line, col)
+                    }
+                    (None, None) => (0, 0),
+                };
+                vec![diagnostic(text, line, col, e.to_string())]
+            }
+        }
+    }
+
+    /// Regions the service is deployed to according to its manifest on disk
+    fn service_regions(&self, svc: &str) -> Vec<String> {
+        let pth = self.root.join("services").join(svc).join("manifest.yml");
+        let regions = std::fs::read_to_string(pth)
+            .ok()
+            .and_then(|data| serde_yaml::from_str::<serde_yaml::Value>(&data).ok())
+            .and_then(|v| serde_yaml::from_value::<Vec<String>>(v["regions"].clone()).ok());
+        regions.unwrap_or_default()
+    }
+
+    /// Full manifest verification diagnostics for a saved document
+    ///
+    /// Loads the service in every region it's deployed to (from disk) and verifies it.
+    async fn verify_diagnostics(&self, svc: &str, text: &str) -> Vec<Value> {
+        let mut res = vec![];
+        for r in self.service_regions(svc) {
+            let reg = match self.conf.get_region_unchecked(&r) {
+                Some(reg) => reg,
+                None => continue, // validate catches bad region names
+            };
+            let verified = match shipcat_filebacked::load_manifest(svc, &self.conf, reg).await {
+                Ok(mf) => match mf.stub(reg).await {
+                    Ok(mf) => mf.verify(&self.conf, reg),
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e),
+            };
+            if let Err(e) = verified {
+                let msg = e.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(": ");
+                debug!("{}", e.display_chain());
+                let (line, col) = locate(text, &msg).unwrap_or((0, 0));
+                res.push(diagnostic(text, line, col, format!("{}: {}", r, msg)));
+            }
+        }
+        res
+    }
+
+    fn publish(uri: &str, diagnostics: Vec<Value>) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        })
+    }
+
+    fn schema_for(&self, file: &str) -> &Value {
+        if file == "manifest.yml" {
+            &self.manifest_schema
+        } else {
+            &self.overrides_schema
+        }
+    }
+
+    /// Region a service file applies to, falling back to the first region of the service
+    fn file_region(&self, svc: &str, file: &str) -> Option<&Region> {
+        let stem = file.trim_end_matches(".yml").trim_end_matches(".yaml");
+        let regions = self.service_regions(svc);
+        regions
+            .iter()
+            .filter_map(|r| self.conf.get_region_unchecked(r))
+            .find(|r| {
+                r.name == stem || r.environment.to_string() == stem || r.locations.iter().any(|l| l == stem)
+            })
+            .or_else(|| regions.iter().find_map(|r| self.conf.get_region_unchecked(r)))
+    }
+
+    fn hover(&self, uri: &str, line: usize) -> Value {
+        let (text, svc, file) = match (self.docs.get(uri), service_file(uri)) {
+            (Some(t), Some((s, f))) => (t, s, f),
+            _ => return Value::Null,
+        };
+        let path = key_path(text, line);
+        // sources are mostly undocumented, the completed manifest has the docs
+        let mut desc = describe(self.schema_for(&file), &path).or_else(|| describe(&self.docs_schema, &path));
+        // vault secrets link to the vault ui for the region of the file (or the first region)
+        let segs = path.iter().map(String::as_str).collect::<Vec<_>>();
+        let value = text.lines().nth(line).and_then(line_value);
+        if let (["env", _], Some("IN_VAULT")) | (["secretFiles", _], Some("IN_VAULT")) =
+            (segs.as_slice(), value)
+        {
+            if let Some(reg) = self.file_region(&svc, &file) {
+                let link = format!("[{} secrets in vault]({})", reg.name, reg.vault_url(&svc));
+                desc = Some(desc.map_or(link.clone(), |d| format!("{}\n\n{}", d, link)));
+            }
+        }
+        match desc {
+            Some(desc) => json!({
+                "contents": { "kind": "markdown", "value": format!("**{}**\n\n{}", path.join("."), desc) }
+            }),
+            None => Value::Null,
+        }
+    }
+
+    fn definition(&self, uri: &str, line: usize) -> Value {
+        let (text, svc, file) = match (self.docs.get(uri), service_file(uri)) {
+            (Some(t), Some((s, f))) => (t, s, f),
+            _ => return Value::Null,
+        };
+        let path = key_path(text, line);
+        let value = text.lines().nth(line).and_then(line_value);
+        let segs = path.iter().map(String::as_str).collect::<Vec<_>>();
+        match (segs.as_slice(), value) {
+            // dependencies jump to the manifest of the dependency
+            ([.., "dependencies", "[]", "name"], Some(dep)) => {
+                let pth = self.root.join("services").join(dep).join("manifest.yml");
+                match path_to_uri(&pth) {
+                    Some(target) if pth.is_file() => json!({
+                        "uri": target,
+                        "range": { "start": position(0, 0), "end": position(0, 0) }
+                    }),
+                    _ => Value::Null,
+                }
+            }
+            // vault secrets jump to the secret in the vault ui for the region of the file
+            (["env", key], Some("IN_VAULT")) | (["secretFiles", key], Some("IN_VAULT")) => {
+                match self.file_region(&svc, &file) {
+                    Some(reg) => json!({
+                        "uri": reg.vault_secret_url(&svc, key),
+                        "range": { "start": position(0, 0), "end": position(0, 0) }
+                    }),
+                    None => Value::Null,
+                }
+            }
+            _ => Value::Null,
+        }
+    }
+
+    /// Handle a single incoming message
+    ///
+    /// Returns the messages to send back, and whether the client asked us to exit.
+    pub async fn handle(&mut self, msg: &Value) -> (Vec<Value>, bool) {
+        let method = msg["method"].as_str().unwrap_or_default();
+        let params = &msg["params"];
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let line = params["position"]["line"].as_u64().unwrap_or(0) as usize;
+        let mut out = vec![];
+        let result = match method {
+            "initialize" => Some(json!({
+                "capabilities": {
+                    "textDocumentSync": { "openClose": true, "change": 1, "save": { "includeText": false } },
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                },
+                "serverInfo": { "name": "shipcat", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => Some(Value::Null),
+            "exit" => return (out, true),
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                let text = if method == "textDocument/didOpen" {
+                    params["textDocument"]["text"].as_str()
+                } else {
+                    // full sync: last change has the full text
+                    params["contentChanges"]
+                        .as_array()
+                        .and_then(|c| c.last())
+                        .and_then(|c| c["text"].as_str())
+                };
+                if let (Some(text), Some((_, file))) = (text, service_file(&uri)) {
+                    out.push(Self::publish(&uri, self.parse_diagnostics(&file, text)));
+                    self.docs.insert(uri.clone(), text.to_string());
+                }
+                None
+            }
+            "textDocument/didSave" => {
+                if let (Some(text), Some((svc, file))) = (self.docs.get(&uri), service_file(&uri)) {
+                    let mut diags = self.parse_diagnostics(&file, text);
+                    if diags.is_empty() {
+                        diags = self.verify_diagnostics(&svc, text).await;
+                    }
+                    out.push(Self::publish(&uri, diags));
+                }
+                None
+            }
+            "textDocument/didClose" => {
+                self.docs.remove(&uri);
+                out.push(Self::publish(&uri, vec![]));
+                None
+            }
+            "textDocument/hover" => Some(self.hover(&uri, line)),
+            "textDocument/definition" => Some(self.definition(&uri, line)),
+            _ => {
+                if !msg["id"].is_null() {
+                    out.push(json!({
+                        "jsonrpc": "2.0",
+                        "id": msg["id"],
+                        "error": { "code": -32601, "message": format!("{} not supported", method) },
+                    }));
+                }
+                None
+            }
+        };
+        if let Some(r) = result {
+            out.push(json!({ "jsonrpc": "2.0", "id": msg["id"], "result": r }));
+        }
+        (out, false)
+    }
+}
+
+/// Entry point for `shipcat lsp`
+///
+/// Serves the language server protocol over stdio until the client exits.
+/// Must be started from the root of the manifests repository.
+pub async fn serve(conf: Config) -> Result<()> {
+    let mut server = Server::new(conf)?;
+    let mut input = BufReader::new(io::stdin());
+    let mut output = io::stdout();
+    while let Some(msg) = read_message(&mut input).await? {
+        let (replies, exit) = server.handle(&msg).await;
+        for r in replies {
+            write_message(&mut output, &r).await?;
+        }
+        if exit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        describe, diagnostic, key_path, locate, path_to_uri, read_message, service_file, uri_to_path,
+        write_message, Manifest,
+    };
+    use serde_json::json;
+    use std::{io::Cursor, path::Path};
+
+    #[tokio::test]
+    async fn framing() {
+        let mut buf = vec![];
+        let msg = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize"});
+        write_message(&mut buf, &msg).await.unwrap();
+        write_message(&mut buf, &msg).await.unwrap();
+        let mut rdr = Cursor::new(buf);
+        assert_eq!(read_message(&mut rdr).await.unwrap(), Some(msg.clone()));
+        assert_eq!(read_message(&mut rdr).await.unwrap(), Some(msg));
+        assert_eq!(read_message(&mut rdr).await.unwrap(), None);
+    }
+
+    #[test]
+    fn yaml_key_paths() {
+        let text = "name: fake-ask
+# comment
+dependencies:
+- name: fake-storage
+  api: v1
+- name: other
+env:
+  FOO: IN_VAULT
+kongApis:
+  - name: x
+    uris: /x
+";
+        assert_eq!(key_path(text, 0), vec!["name"]);
+        assert_eq!(key_path(text, 3), vec!["dependencies", "[]", "name"]);
+        assert_eq!(key_path(text, 4), vec!["dependencies", "[]", "api"]);
+        assert_eq!(key_path(text, 5), vec!["dependencies", "[]", "name"]);
+        assert_eq!(key_path(text, 7), vec!["env", "FOO"]);
+        assert_eq!(key_path(text, 10), vec!["kongApis", "[]", "uris"]);
+        assert_eq!(locate(text, "unknown field `api`"), Some((4, 2)));
+        assert_eq!(locate(text, "dependency 'other' not found"), Some((5, 8)));
+        assert_eq!(locate(text, "something else"), None);
+    }
+
+    #[test]
+    fn utf16_ranges() {
+        let text = "# café ☕\nenv:\n  GREETING: \"héllo 👋\"\n";
+        let d = diagnostic(text, 2, 4, "x".into());
+        assert_eq!(d["range"]["start"], json!({"line": 2, "character": 4}));
+        // é is one code unit, 👋 is two
+        assert_eq!(d["range"]["end"], json!({"line": 2, "character": 22}));
+        let d = diagnostic(text, 0, "# café ".len(), "x".into());
+        assert_eq!(d["range"]["start"], json!({"line": 0, "character": 7}));
+        assert_eq!(d["range"]["end"], json!({"line": 0, "character": 8}));
+    }
+
+    #[test]
+    fn schema_hover() {
+        let schema = serde_json::to_value(shipcat_filebacked::manifest_schema()).unwrap();
+        let path = |p: &[&str]| p.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let deps = describe(&schema, &path(&["dependencies", "[]", "name"])).unwrap();
+        assert!(deps.contains("Name of service"), "got {}", deps);
+        assert!(describe(&schema, &path(&["metadata", "team"])).is_some());
+        assert!(describe(&schema, &path(&["nonexistent"])).is_none());
+
+        let docs = serde_json::to_value(Manifest::schema()).unwrap();
+        let name = describe(&docs, &path(&["name"])).unwrap();
+        assert!(name.starts_with("Name of the service"), "got {}", name);
+    }
+
+    #[test]
+    fn service_files() {
+        let f = service_file("file:///repo/services/fake-ask/dev-uk.yml");
+        assert_eq!(f, Some(("fake-ask".into(), "dev-uk.yml".into())));
+        assert_eq!(service_file("file:///repo/shipcat.conf"), None);
+        assert_eq!(service_file("file:///repo/services/fake-ask/README.md"), None);
+
+        let pth = uri_to_path("file:///my%20repo/caf%C3%A9/services/a/manifest.yml").unwrap();
+        assert_eq!(pth, Path::new("/my repo/café/services/a/manifest.yml"));
+        assert_eq!(
+            path_to_uri(&pth).unwrap(),
+            "file:///my%20repo/caf%C3%A9/services/a/manifest.yml"
+        );
+    }
+}
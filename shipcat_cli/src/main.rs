@@ -64,6 +64,54 @@ fn build_cli() -> App<'static, 'static> {
                 .possible_values(&Shell::variants())
                 .help("Shell to generate completions for (zsh or bash)")))
 
+        .subcommand(SubCommand::with_name("schema")
+            .about("Generate JSON Schema for shipcat files for editor validation")
+            .arg(Arg::with_name("kind")
+                .required(true)
+                .possible_values(&["manifest", "overrides", "config"])
+                .help("File to generate a schema for (manifest.yml, region/environment overrides, or shipcat.conf)")))
+
+        .subcommand(SubCommand::with_name("fmt")
+            .about("Format manifest and override files into canonical key order and quoting")
+            .arg(Arg::with_name("check")
+                .long("check")
+                .help("Fail if any files are not formatted instead of rewriting them"))
+            .arg(Arg::with_name("services")
+                .multiple(true)
+                .help("Services to format (all of them if none are given)")))
+
+        .subcommand(SubCommand::with_name("new")
+            .about("Create a service folder from a scaffold in the manifests repo")
+            .arg(Arg::with_name("name")
+                .required(true)
+                .help("Name of the new service"))
+            .arg(Arg::with_name("team")
+                .long("team")
+                .takes_value(true)
+                .required(true)
+                .help("Owning squad in teams.yml"))
+            .arg(Arg::with_name("template")
+                .long("template")
+                .takes_value(true)
+                .required(true)
+                .help("Scaffold to use from the scaffolds folder (e.g. java-http, worker, cron)"))
+            .arg(Arg::with_name("regions")
+                .long("regions")
+                .takes_value(true)
+                .use_delimiter(true)
+                .help("Comma separated regions to create the service in (defaults to the region)")))
+
+        .subcommand(SubCommand::with_name("migrate")
+            .about("Rewrite deprecated syntax in shipcat.conf and manifests (lists migrations without a name)")
+            .arg(Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Print a diff of the changes instead of writing them"))
+            .arg(Arg::with_name("name")
+                .help("Name of the migration to run")))
+
+        .subcommand(SubCommand::with_name("lsp")
+            .about("Run a language server for manifests over stdio (from the manifests repo root)"))
+
         .subcommand(SubCommand::with_name("shell")
             .about("Shell into pods for a service described in a manifest")
             .arg(Arg::with_name("service")
@@ -103,6 +151,16 @@ fn build_cli() -> App<'static, 'static> {
                 .short("s")
                 .long("secrets")
                 .help("Verifies secrets exist everywhere"))
+              .arg(Arg::with_name("images")
+                .long("images")
+                .help("Verifies image tags exist in the region's registry"))
+              .arg(Arg::with_name("output")
+                .takes_value(true)
+                .default_value("human")
+                .possible_values(&["human", "json", "sarif"])
+                .long("output")
+                .short("o")
+                .help("Report format for validation findings"))
               .about("Validate the shipcat manifest"))
 
         .subcommand(SubCommand::with_name("verify")
@@ -130,7 +188,18 @@ fn build_cli() -> App<'static, 'static> {
 
         .subcommand(SubCommand::with_name("gdpr")
               .arg(Arg::with_name("service")
+                .conflicts_with("report")
                 .help("Service names to show"))
+              .arg(Arg::with_name("report")
+                .long("report")
+                .help("Print a record of processing table across all services"))
+              .arg(Arg::with_name("output")
+                .takes_value(true)
+                .possible_values(&["markdown", "csv"])
+                .long("output")
+                .short("o")
+                .requires("report")
+                .help("Report format [default: markdown]"))
               .about("Reduce data handling structs"))
 
         .subcommand(SubCommand::with_name("get")
@@ -150,6 +219,10 @@ fn build_cli() -> App<'static, 'static> {
                 .help("Reduce kafkaUser info"))
               .subcommand(SubCommand::with_name("kafkatopics")
                 .help("Reduce KafkaTopic info"))
+              .subcommand(SubCommand::with_name("kafkaaccess")
+                .help("Reduce which kafka users can read or write which topics"))
+              .subcommand(SubCommand::with_name("prometheusrules")
+                .help("Reduce prometheusAlerts into a PrometheusRule"))
               .subcommand(SubCommand::with_name("codeowners")
                 .help("Generate CODEOWNERS syntax for manifests based on team ownership"))
               .subcommand(SubCommand::with_name("vault-policy")
@@ -170,7 +243,9 @@ fn build_cli() -> App<'static, 'static> {
                 .long("crd")
                 .help("Produce an experimental custom resource values for this kubernetes region"))
             .subcommand(SubCommand::with_name("config-url")
-                .help("Generate Kong config URL")))
+                .help("Generate Kong config URL"))
+            .subcommand(SubCommand::with_name("check")
+                .help("Check the Kong routes of the region for conflicts")))
         // Statuscake helper
         .subcommand(SubCommand::with_name("statuscake")
             .about("Generate Statuscake config"))
@@ -250,6 +325,10 @@ fn build_cli() -> App<'static, 'static> {
                 .short("s")
                 .long("secrets")
                 .help("Use actual secrets from vault"))
+              .arg(Arg::with_name("explain")
+                .long("explain")
+                .conflicts_with("secrets")
+                .help("Show which file set each value of the merged manifest sources"))
               .arg(Arg::with_name("service")
                 .required(true)
                 .help("Service to generate values for"))
@@ -327,7 +406,7 @@ fn build_cli() -> App<'static, 'static> {
               .arg(Arg::with_name("git")
                 .long("git")
                 .global(true)
-                .help("Comparing with master using a temporary git stash and git checkout"))
+                .help("Comparing with master, read from git without checking it out"))
               .arg(Arg::with_name("with-region")
                 .long("with-region")
                 .global(true)
@@ -353,6 +432,9 @@ fn build_cli() -> App<'static, 'static> {
                 .short("m")
                 .long("minify")
                 .help("Minify the diff context"))
+              .arg(Arg::with_name("no-ignore")
+                .long("no-ignore")
+                .help("Show changes covered by diffIgnore rules"))
               .arg(Arg::with_name("obfuscate")
                 .long("obfuscate")
                 .requires("secrets")
@@ -383,6 +465,42 @@ fn build_cli() -> App<'static, 'static> {
                 .short("f")
                 .help("Remove the old tsh state file to force a login")))
 
+        .subcommand(SubCommand::with_name("cronjobs")
+            .about("List cron jobs in a region with their upcoming runs")
+            .arg(Arg::with_name("service")
+                .required_unless("all")
+                .help("Service to show cron jobs for"))
+            .arg(Arg::with_name("all")
+                .long("all")
+                .short("a")
+                .conflicts_with("service")
+                .help("Show cron jobs for every service in the region"))
+            .arg(Arg::with_name("count")
+                .long("count")
+                .short("n")
+                .takes_value(true)
+                .default_value("5")
+                .help("Number of upcoming runs to show per job")))
+
+        .subcommand(SubCommand::with_name("compare")
+              .arg(Arg::with_name("regions")
+                .long("regions")
+                .takes_value(true)
+                .required(true)
+                .help("Regions to compare (comma separated)"))
+              .arg(Arg::with_name("service")
+                .required_unless("all")
+                .help("Service to compare"))
+              .arg(Arg::with_name("all")
+                .long("all")
+                .conflicts_with("service")
+                .help("Compare all services available in any of the regions"))
+              .about("Show fields of a completed manifest that differ between regions"))
+        .subcommand(SubCommand::with_name("pr-summary")
+              .arg(Arg::with_name("no-template")
+                .long("no-template")
+                .help("Skip helm template diffs (faster, no charts needed)"))
+              .about("Summarise manifest changes since the merge-base as markdown"))
         .subcommand(SubCommand::with_name("top")
             .about("Show top requests from manifests on disk")
             .arg(Arg::with_name("upper")
@@ -413,7 +531,16 @@ fn build_cli() -> App<'static, 'static> {
                 .default_value("cpu")
                 .long("sort")
                 .short("s")
-                .help("Resource type to sort by")));
+                .help("Resource type to sort by"))
+            .arg(Arg::with_name("actual")
+                .long("actual")
+                .conflicts_with_all(&["world", "squads", "tribes", "upper"])
+                .help("Recommend resource changes from actual usage in the cluster"))
+            .arg(Arg::with_name("prometheus")
+                .long("prometheus")
+                .takes_value(true)
+                .requires("actual")
+                .help("Prometheus url to read usage from instead of the metrics api")));
 
     if cfg!(feature = "self-upgrade") {
         app = app.subcommand(SubCommand::with_name("self-upgrade")
@@ -554,6 +681,33 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
     } else if let Some(a) = args.subcommand_matches("login") {
         let (conf, region) = resolve_config(a, ConfigState::Base).await?;
         return shipcat::auth::login(&conf, &region, a.is_present("force")).await;
+    } else if let Some(a) = args.subcommand_matches("fmt") {
+        let services = a
+            .values_of("services")
+            .map(|v| v.map(String::from).collect::<Vec<_>>())
+            .unwrap_or_default();
+        return shipcat::fmt::format(&services, a.is_present("check"));
+    } else if let Some(a) = args.subcommand_matches("new") {
+        let regions = match a.values_of("regions") {
+            Some(rs) => rs.map(String::from).collect::<Vec<_>>(),
+            None => a.value_of("region").map(String::from).into_iter().collect(),
+        };
+        return shipcat::scaffold::create(
+            a.value_of("name").unwrap(),
+            a.value_of("team").unwrap(),
+            a.value_of("template").unwrap(),
+            &regions,
+        )
+        .await;
+    } else if let Some(a) = args.subcommand_matches("migrate") {
+        return match a.value_of("name") {
+            Some(name) => shipcat::migrate::migrate(name, a.is_present("dry-run")).await,
+            None => shipcat::migrate::list(),
+        };
+    } else if args.subcommand_matches("lsp").is_some() {
+        return shipcat::lsp::serve(Config::read().await?).await;
+    } else if let Some(a) = args.subcommand_matches("schema") {
+        return shipcat::show::schema(a.value_of("kind").unwrap());
     } else if let Some(a) = args.subcommand_matches("self-upgrade") {
         let tag = if let Some(v) = a.value_of("tag") {
             Some(semver::Version::parse(v).expect("tag must be valid semver"))
@@ -601,10 +755,53 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
         if let Some(_) = a.subcommand_matches("kafkatopics") {
             return shipcat::get::kafkatopics(&conf, &region).await;
         }
+        if let Some(_) = a.subcommand_matches("kafkaaccess") {
+            return shipcat::kafka::access(&conf, &region).await;
+        }
+        if let Some(_) = a.subcommand_matches("prometheusrules") {
+            return shipcat::get::prometheusrules(&conf, &region).await;
+        }
+    } else if let Some(a) = args.subcommand_matches("compare") {
+        let regions = a
+            .value_of("regions")
+            .unwrap()
+            .split(',')
+            .map(String::from)
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+        let rawconf = Config::read().await?;
+        let differs = if let Some(svc) = a.value_of("service") {
+            shipcat::compare::service(svc, &rawconf, &regions).await?
+        } else {
+            shipcat::compare::all(&rawconf, &regions).await?
+        };
+        process::exit(if differs { 1 } else { 0 });
+    } else if let Some(a) = args.subcommand_matches("cronjobs") {
+        let (conf, region) = resolve_config(a, ConfigState::Base).await?;
+        let count = a.value_of("count").unwrap().parse::<usize>()?;
+        return if let Some(svc) = a.value_of("service") {
+            shipcat::cronjobs::service(svc, &conf, &region, count).await
+        } else {
+            shipcat::cronjobs::all(&conf, &region, count).await
+        };
+    } else if let Some(a) = args.subcommand_matches("pr-summary") {
+        let md = shipcat::prsummary::report(!a.is_present("no-template")).await?;
+        println!("{}", md);
+        return Ok(());
     } else if let Some(a) = args.subcommand_matches("top") {
         let sort = top::ResourceOrder::from_str(a.value_of("sort").unwrap())?;
         let fmt = top::OutputFormat::from_str(a.value_of("output").unwrap())?;
         let ub = a.is_present("upper");
+        if a.is_present("actual") {
+            let (conf, region) = resolve_config(a, ConfigState::Base).await?;
+            let source = match a.value_of("prometheus") {
+                Some(url) => top::UsageSource::Prometheus(url.to_string()),
+                None => top::UsageSource::MetricsApi,
+            };
+            return shipcat::top::region_actual(source, fmt, &conf, &region)
+                .await
+                .map(void);
+        }
         return if a.is_present("world") {
             let rawconf = Config::read().await?;
             if a.is_present("squads") {
@@ -709,7 +906,8 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
             ConfigState::Base
         };
         let (conf, region) = resolve_config(a, ss).await?;
-        return shipcat::validate::manifest(services, &conf, &region, a.is_present("secrets")).await;
+        let fmt = shipcat::validate::ReportFormat::from_str(a.value_of("output").unwrap_or("markdown"))?;
+        return shipcat::validate::manifest_report(services, &conf, &region, a.is_present("secrets"), a.is_present("images"), fmt).await;
     } else if let Some(a) = args.subcommand_matches("verify") {
         return if a.value_of("region").is_some() {
             let (conf, region) = resolve_config(a, ConfigState::Base).await?;
@@ -726,6 +924,9 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
             ConfigState::Base
         };
         let (conf, region) = resolve_config(a, ss).await?;
+        if a.is_present("explain") {
+            return shipcat::show::explain(&svc, &conf, &region).await;
+        }
 
         let mf = if a.is_present("secrets") {
             shipcat_filebacked::load_manifest(&svc, &conf, &region)
@@ -849,12 +1050,21 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
                 let crd = s.get().await?;
                 mf.version = mf.version.or(crd.spec.version);
                 mf.uid = crd.metadata.uid;
+                // render pinned images like apply does
+                if let Some(registry) = region.registry.as_ref().filter(|r| r.pinDigests) {
+                    shipcat::apply::resolve_image(&mut mf, registry).await?;
+                }
             } else {
                 // ensure valid chart
                 mf.uid = Some("FAKE-GUID".to_string());
                 mf.version = mf.version.or(Some("latest".to_string()));
             }
-            let diff = shipcat::diff::template_vs_kubectl(&mf).await?;
+            let ignore = if a.is_present("no-ignore") {
+                Default::default()
+            } else {
+                conf.diff_ignore(&region)
+            };
+            let diff = shipcat::diff::template_vs_kubectl(&mf, &ignore).await?;
             if let Some(mut out) = diff {
                 if a.is_present("obfuscate") {
                     out = shipcat::diff::obfuscate_secrets(out, mf.get_secrets())
@@ -873,6 +1083,8 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
         let (conf, region) = resolve_config(a, ConfigState::Base).await?;
         return if let Some(_b) = a.subcommand_matches("config-url") {
             shipcat::kong::config_url(&region)
+        } else if let Some(_b) = a.subcommand_matches("check") {
+            shipcat::kong::check(&conf, &region).await
         } else {
             let mode = if a.is_present("crd") {
                 kong::KongOutputMode::Crd
@@ -1000,9 +1212,29 @@ async fn dispatch_commands(args: &ArgMatches<'_>) -> Result<()> {
         return shipcat::slack::send_dumb(msg).await;
     } else if let Some(a) = args.subcommand_matches("gdpr") {
         let (conf, region) = resolve_config(args, ConfigState::Base).await?;
+        if a.is_present("report") {
+            let fmt = shipcat::gdpr::ReportFormat::from_str(a.value_of("output").unwrap_or("markdown"))?;
+            return shipcat::gdpr::report(fmt, &conf, &region).await;
+        }
         let svc = a.value_of("service").map(String::from);
         return shipcat::gdpr::show(svc, &conf, &region).await;
     }
 
     unreachable!("Subcommand valid, but not implemented");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::build_cli;
+
+    #[test]
+    fn gdpr_args() {
+        let parse = |args: &[&str]| build_cli().get_matches_from_safe(args);
+        let m = parse(&["shipcat", "gdpr", "fake-ask"]).unwrap();
+        let gdpr = m.subcommand_matches("gdpr").unwrap();
+        assert_eq!(gdpr.value_of("service"), Some("fake-ask"));
+        assert!(parse(&["shipcat", "gdpr"]).is_ok());
+        assert!(parse(&["shipcat", "gdpr", "--report", "-o", "csv"]).is_ok());
+        assert!(parse(&["shipcat", "gdpr", "-o", "csv"]).is_err());
+    }
+}
@@ -0,0 +1,70 @@
+use super::{diff, fmt, Config, Result, ResultExt};
+use shipcat_filebacked::{MigrationTarget, MIGRATIONS};
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+/// Print the registered migrations
+pub fn list() -> Result<()> {
+    for m in MIGRATIONS {
+        println!("{}: {}", m.name, m.description);
+    }
+    Ok(())
+}
+
+/// Run a migration over the files it applies to
+///
+/// Rewritten services are checked against their merged manifests in every region before anything is written.
+/// With `dry_run`, a diff of every change is printed and nothing is written.
+pub async fn migrate(name: &str, dry_run: bool) -> Result<()> {
+    let migration = shipcat_filebacked::migration(name)?;
+    let files = match migration.target {
+        MigrationTarget::Config => vec![PathBuf::from("shipcat.conf")],
+        MigrationTarget::Services => fmt::source_files(&[])?,
+    };
+    // source files and rewrites of each service
+    let mut sources: BTreeMap<String, BTreeMap<PathBuf, String>> = BTreeMap::new();
+    let mut rewrites: BTreeMap<String, BTreeMap<PathBuf, String>> = BTreeMap::new();
+    let mut changes = vec![];
+    for pth in files {
+        let data = fs::read_to_string(&pth)?;
+        let svc = match migration.target {
+            MigrationTarget::Config => String::new(),
+            MigrationTarget::Services => pth
+                .parent()
+                .and_then(|p| p.file_name())
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        };
+        let migrated = migration
+            .apply(&svc, &data)
+            .chain_err(|| format!("Failed to migrate {}", pth.display()))?;
+        if migrated != data {
+            rewrites
+                .entry(svc.clone())
+                .or_default()
+                .insert(pth.clone(), migrated.clone());
+            changes.push((pth.clone(), data.clone(), migrated));
+        }
+        sources.entry(svc).or_default().insert(pth, data);
+    }
+    if migration.target == MigrationTarget::Services && !rewrites.is_empty() {
+        let conf = Config::read().await?;
+        for (svc, rewritten) in &rewrites {
+            migration
+                .check_service(svc, &sources[svc], rewritten, &conf)
+                .await?;
+        }
+    }
+    for (pth, data, migrated) in &changes {
+        if dry_run {
+            let name = pth.strip_prefix(".").unwrap_or(pth).display().to_string();
+            print!("{}", diff::unified_diff(data, migrated, &name, &name)?);
+        } else {
+            info!("Migrated {}", pth.display());
+            fs::write(pth, migrated)?;
+        }
+    }
+    if changes.is_empty() {
+        info!("Nothing to migrate for {}", name);
+    }
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use super::Result;
+use crate::{compare, diff, git, helm, validate};
+use shipcat_filebacked::Tree;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    path::Path,
+};
+
+/// Comparable state of a service in a region at one git revision
+#[derive(Default)]
+struct SvcState {
+    fields: compare::Fields,
+    vault_keys: BTreeSet<String>,
+    template: String,
+}
+
+/// All states found at a revision, keyed by (service, region)
+type States = BTreeMap<(String, String), SvcState>;
+
+/// Load the state of every given service in every region it is deployed to
+///
+/// Templates use the charts of the same revision, exported to a temporary directory.
+async fn load_states(svcs: &BTreeSet<String>, templates: bool, tree: &Tree) -> Result<States> {
+    let conf = tree.config()?;
+    let charts = if templates {
+        let dir = tempfile::tempdir()?;
+        tree.export(Path::new("charts"), dir.path())?;
+        Some(dir)
+    } else {
+        None
+    };
+    let mut res = States::new();
+    for svc in svcs {
+        if !tree.is_file(&Path::new("services").join(svc).join("manifest.yml")) {
+            debug!("{} does not exist at this revision", svc);
+            continue;
+        }
+        for r in conf.list_regions() {
+            let reg = conf.get_region_unchecked(&r).unwrap();
+            let mf = shipcat_filebacked::load_manifest_at(tree, svc, &conf, reg).await?;
+            if !mf.regions.contains(&r) || mf.disabled || mf.external {
+                continue;
+            }
+            let vault_keys = mf.vault_keys();
+            let mf = mf.stub(reg).await?;
+            let template = match &charts {
+                Some(dir) => helm::template_from(&mf, None, dir.path()).await?,
+                None => String::new(),
+            };
+            let state = SvcState {
+                fields: compare::comparable_fields(&mf)?,
+                vault_keys,
+                template,
+            };
+            res.insert((svc.clone(), r), state);
+        }
+    }
+    Ok(res)
+}
+
+/// Markdown report of the changes between two sets of states
+fn render(before: &States, after: &States, conf_changed: bool) -> Result<String> {
+    let mut out = String::new();
+    writeln!(out, "## shipcat summary")?;
+    writeln!(out)?;
+    if conf_changed {
+        writeln!(
+            out,
+            ":warning: `shipcat.conf` changed; only services touched directly are summarised below."
+        )?;
+        writeln!(out)?;
+    }
+    let keys = before
+        .keys()
+        .chain(after.keys())
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    let mut by_svc: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    let mut sections = String::new();
+    let empty = SvcState::default();
+    for (svc, region) in &keys {
+        let b = before.get(&(svc.clone(), region.clone()));
+        let a = after.get(&(svc.clone(), region.clone()));
+        let rows = compare::differing_rows(&[b.map(|s| s.fields.clone()), a.map(|s| s.fields.clone())]);
+        let (bs, as_) = (b.unwrap_or(&empty), a.unwrap_or(&empty));
+        let tpl = diff::unified_diff(&bs.template, &as_.template, "before", "after")?;
+        let new_secrets = as_.vault_keys.difference(&bs.vault_keys).collect::<Vec<_>>();
+        if rows.is_empty() && tpl.is_empty() && new_secrets.is_empty() {
+            continue;
+        }
+        let status = match (b, a) {
+            (None, Some(_)) => "added",
+            (Some(_), None) => "removed",
+            _ => "changed",
+        };
+        by_svc
+            .entry(svc)
+            .or_default()
+            .push(format!("{} ({})", region, status));
+
+        writeln!(sections, "### {} in {}", svc, region)?;
+        writeln!(sections)?;
+        if !rows.is_empty() {
+            writeln!(sections, "| field | before | after |")?;
+            writeln!(sections, "|-------|--------|-------|")?;
+            for (k, vals) in &rows {
+                writeln!(sections, "| `{}` | `{}` | `{}` |", k, vals[0], vals[1])?;
+            }
+            writeln!(sections)?;
+        }
+        if !new_secrets.is_empty() {
+            writeln!(sections, "New secrets required in vault:")?;
+            for s in new_secrets {
+                writeln!(sections, "- `{}`", s)?;
+            }
+            writeln!(sections)?;
+        }
+        if !tpl.is_empty() {
+            writeln!(sections, "<details><summary>Template diff</summary>")?;
+            writeln!(sections)?;
+            writeln!(sections, "```diff")?;
+            write!(sections, "{}", tpl)?;
+            writeln!(sections, "```")?;
+            writeln!(sections, "</details>")?;
+            writeln!(sections)?;
+        }
+    }
+    if by_svc.is_empty() {
+        writeln!(out, "No changes to any service in any region.")?;
+        return Ok(out);
+    }
+    writeln!(out, "| service | regions |")?;
+    writeln!(out, "|---------|---------|")?;
+    for (svc, regions) in &by_svc {
+        writeln!(out, "| {} | {} |", svc, regions.join(", "))?;
+    }
+    writeln!(out)?;
+    out += &sections;
+    Ok(out)
+}
+
+/// Markdown summary of the services changed between the merge-base and HEAD
+///
+/// Loads every touched service in every region it is deployed to, both at the merge-base
+/// and at HEAD, and reports changed fields, new vault secrets, and
+/// (optionally) the helm template diff. Suitable for posting as a PR comment.
+///
+/// Both revisions are read straight from git, so uncommitted changes are left out.
+pub async fn report(templates: bool) -> Result<String> {
+    let merge_base = git::merge_base()?;
+    let changed = git::diff_filenames(&format!("{}..HEAD", merge_base))?;
+    let svcs = validate::changed_services(&changed)
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+    let conf_changed = changed.lines().any(|l| l == "shipcat.conf");
+    if svcs.is_empty() {
+        return render(&States::new(), &States::new(), conf_changed);
+    }
+    let after = load_states(&svcs, templates, &Tree::revision("HEAD")?).await?;
+    let before = load_states(&svcs, templates, &Tree::revision(&merge_base)?).await?;
+    render(&before, &after, conf_changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, States, SvcState};
+
+    fn state(version: &str, secrets: &[&str]) -> SvcState {
+        let mut s = SvcState::default();
+        s.fields.insert("version".into(), version.into());
+        s.vault_keys = secrets.iter().map(|s| s.to_string()).collect();
+        s
+    }
+
+    #[test]
+    fn summary_markdown() {
+        let mut before = States::new();
+        let mut after = States::new();
+        let key = |r: &str| ("fake-ask".to_string(), r.to_string());
+        before.insert(key("dev-uk"), state("1.0.0", &["DB_PASS"]));
+        after.insert(key("dev-uk"), state("1.1.0", &["DB_PASS", "API_KEY"]));
+        before.insert(key("prod-uk"), state("1.0.0", &[]));
+        after.insert(key("prod-uk"), state("1.0.0", &[]));
+        after.insert(key("staging-uk"), state("1.1.0", &[]));
+
+        let md = render(&before, &after, false).unwrap();
+        assert!(md.contains("| fake-ask | dev-uk (changed), staging-uk (added) |"));
+        assert!(md.contains("| `version` | `1.0.0` | `1.1.0` |"));
+        assert!(md.contains("- `API_KEY`"));
+        assert!(!md.contains("- `DB_PASS`"));
+        assert!(!md.contains("prod-uk"));
+
+        let md = render(&before, &before, true).unwrap();
+        assert!(md.contains("shipcat.conf"));
+        assert!(md.contains("No changes"));
+    }
+}
@@ -0,0 +1,206 @@
+use super::{ErrorKind, Result, ResultExt};
+use reqwest::{header, Client, StatusCode, Url};
+use shipcat_definitions::RegistryConfig;
+
+/// Media types we accept for image manifests
+///
+/// Both single platform manifests and multi-platform indexes.
+const MANIFEST_TYPES: &str = "application/vnd.oci.image.manifest.v1+json, \
+                              application/vnd.oci.image.index.v1+json, \
+                              application/vnd.docker.distribution.manifest.v2+json, \
+                              application/vnd.docker.distribution.manifest.list.v2+json";
+
+/// What the registry knows about an image tag
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageInfo {
+    /// Immutable content digest, e.g. `sha256:abc..`
+    pub digest: String,
+    /// Compressed size of config and layers in bytes
+    pub size: u64,
+}
+
+impl ImageInfo {
+    /// Size in MB, rounded up, as used by `imageSize`
+    pub fn size_mb(&self) -> u32 {
+        ((self.size + (1 << 20) - 1) >> 20) as u32
+    }
+}
+
+#[derive(Deserialize)]
+struct Descriptor {
+    #[serde(default)]
+    digest: String,
+    #[serde(default)]
+    size: u64,
+    #[serde(default)]
+    platform: Option<Platform>,
+}
+
+#[derive(Deserialize)]
+struct Platform {
+    architecture: String,
+}
+
+/// The parts of an image manifest or index we care about
+#[derive(Deserialize)]
+struct ImageManifest {
+    #[serde(default)]
+    config: Option<Descriptor>,
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+    /// Only set for indexes
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+}
+
+impl ImageManifest {
+    fn size(&self) -> u64 {
+        self.config.as_ref().map_or(0, |c| c.size) + self.layers.iter().map(|l| l.size).sum::<u64>()
+    }
+
+    /// Manifest of an index to take the size from (amd64 preferred)
+    fn platform_manifest(&self) -> Option<&Descriptor> {
+        self.manifests
+            .iter()
+            .find(|m| matches!(&m.platform, Some(p) if p.architecture == "amd64"))
+            .or_else(|| self.manifests.first())
+    }
+}
+
+/// Repository name of an image within a registry
+///
+/// Strips the registry host from the image if it is there.
+/// Images without a registry host are assumed to be in the registry,
+/// and images on a different registry host return `None`.
+pub fn repository(image: &str, url: &str) -> Option<String> {
+    let host = url.split("://").last().unwrap_or(url).trim_end_matches('/');
+    let mut parts = image.splitn(2, '/');
+    match (parts.next(), parts.next()) {
+        // like docker, the first segment is a host if it looks like one
+        (Some(first), Some(rest)) if first.contains('.') || first.contains(':') || first == "localhost" => {
+            if first.eq_ignore_ascii_case(host) {
+                Some(rest.into())
+            } else {
+                None
+            }
+        }
+        _ => Some(image.into()),
+    }
+}
+
+/// Fetch a manifest, authenticating with the static `REGISTRY_TOKEN` bearer token if set
+///
+/// There is no support for the registry token challenge flow.
+async fn fetch(client: &Client, url: Url) -> Result<Option<(Option<String>, ImageManifest)>> {
+    let mut req = client.get(url.clone()).header(header::ACCEPT, MANIFEST_TYPES);
+    if let Ok(token) = std::env::var("REGISTRY_TOKEN") {
+        req = req.bearer_auth(token);
+    }
+    let res = req.send().await.chain_err(|| ErrorKind::Url(url.clone()))?;
+    if res.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !res.status().is_success() {
+        bail!("Registry returned {} for {}", res.status(), url);
+    }
+    let digest = res
+        .headers()
+        .get("Docker-Content-Digest")
+        .and_then(|d| d.to_str().ok())
+        .map(String::from);
+    let mf = res.json().await.chain_err(|| ErrorKind::Url(url.clone()))?;
+    Ok(Some((digest, mf)))
+}
+
+/// Look up an image tag in a registry via the OCI Distribution API
+///
+/// Returns `None` if the registry does not have the tag.
+/// Fails for images hosted on a different registry.
+pub async fn lookup(reg: &RegistryConfig, image: &str, tag: &str) -> Result<Option<ImageInfo>> {
+    let repo = match repository(image, &reg.url) {
+        Some(r) => r,
+        None => bail!("Image {} is not hosted on the registry {}", image, reg.url),
+    };
+    let base = format!("{}/v2/{}/manifests/", reg.url, repo);
+    let url = Url::parse(&format!("{}{}", base, tag))?;
+    let client = Client::new();
+    let (digest, mf) = match fetch(&client, url.clone()).await? {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+    let digest = match digest {
+        Some(d) => d,
+        None => bail!("Registry did not return a digest for {}", url),
+    };
+    let size = match mf.platform_manifest() {
+        Some(m) => {
+            let purl = Url::parse(&format!("{}{}", base, m.digest))?;
+            match fetch(&client, purl).await? {
+                Some((_, pmf)) => pmf.size(),
+                None => bail!("Registry index for {} refers to missing {}", url, m.digest),
+            }
+        }
+        None => mf.size(),
+    };
+    Ok(Some(ImageInfo { digest, size }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{repository, ImageInfo, ImageManifest};
+
+    #[test]
+    fn repositories() {
+        let url = "https://quay.io";
+        assert_eq!(
+            repository("quay.io/babylonhealth/fake-ask", url).unwrap(),
+            "babylonhealth/fake-ask"
+        );
+        assert_eq!(
+            repository("babylonhealth/fake-ask", url).unwrap(),
+            "babylonhealth/fake-ask"
+        );
+        assert_eq!(repository("fake-ask", url).unwrap(), "fake-ask");
+        // images on other registries are not looked up
+        assert!(repository("localhost:5000/fake-ask", url).is_none());
+        assert!(repository("docker.io/library/nginx", url).is_none());
+        assert!(repository("localhost/fake-ask", url).is_none());
+        assert_eq!(
+            repository("localhost:5000/fake-ask", "http://localhost:5000/").unwrap(),
+            "fake-ask"
+        );
+    }
+
+    #[test]
+    fn manifest_sizes() {
+        let mf: ImageManifest = serde_json::from_str(
+            r#"{
+              "schemaVersion": 2,
+              "config": { "digest": "sha256:c", "size": 1000 },
+              "layers": [
+                { "digest": "sha256:a", "size": 3145728 },
+                { "digest": "sha256:b", "size": 1048576 }
+              ]
+            }"#,
+        )
+        .unwrap();
+        assert!(mf.platform_manifest().is_none());
+        let info = ImageInfo {
+            digest: "sha256:x".into(),
+            size: mf.size(),
+        };
+        assert_eq!(info.size_mb(), 5);
+
+        let idx: ImageManifest = serde_json::from_str(
+            r#"{
+              "schemaVersion": 2,
+              "manifests": [
+                { "digest": "sha256:arm", "size": 10, "platform": { "architecture": "arm64", "os": "linux" } },
+                { "digest": "sha256:amd", "size": 10, "platform": { "architecture": "amd64", "os": "linux" } }
+              ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(idx.platform_manifest().unwrap().digest, "sha256:amd");
+    }
+}
@@ -0,0 +1,145 @@
+use super::{
+    validate::{self, ReportFormat},
+    Config, Manifest, Region, Result, ResultExt,
+};
+use shipcat_definitions::{template::render_file_data, ConfigState};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use tera::Context;
+
+/// Scaffolds available in `scaffolds/` of the manifests repo
+fn available() -> Vec<String> {
+    let mut res = fs::read_dir(Path::new(".").join("scaffolds"))
+        .map(|rd| {
+            rd.filter_map(|e| e.ok())
+                .filter(|e| e.path().is_dir())
+                .filter_map(|e| e.file_name().to_str().map(String::from))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    res.sort();
+    res
+}
+
+/// Service metadata prefilled from the owning squad in `teams.yml`
+fn metadata(name: &str, team: &str, conf: &Config) -> Result<serde_yaml::Value> {
+    let squad = match conf.owners.squads.get(team) {
+        Some(s) => s,
+        None => bail!("Squad {} does not exist in teams.yml", team),
+    };
+    let mut md = serde_yaml::Mapping::new();
+    let repo = format!("https://github.com/{}/{}", conf.github.organisation, name);
+    md.insert("repo".into(), repo.into());
+    md.insert("team".into(), team.into());
+    if !squad.owners.is_empty() {
+        md.insert("maintainers".into(), serde_yaml::to_value(&squad.owners)?);
+    }
+    if let Some(c) = &squad.slack.support {
+        md.insert("support".into(), serde_yaml::to_value(c)?);
+    }
+    if let Some(c) = &squad.slack.notifications {
+        md.insert("notifications".into(), serde_yaml::to_value(c)?);
+    }
+    Ok(md.into())
+}
+
+/// Render the files of a new service from `scaffolds/{template}/`
+///
+/// `manifest.yml.j2` becomes `services/{name}/manifest.yml`, and `region.yml.j2`, when the scaffold
+/// has one, becomes an override file for each region. Templates get `name`, `team`, `regions`,
+/// `metadata` (yaml prefilled from `teams.yml`) and, for overrides, `region`.
+/// Rendered files are checked and put in canonical form, but nothing is written.
+pub fn render(
+    name: &str,
+    team: &str,
+    template: &str,
+    regions: &[Region],
+    conf: &Config,
+) -> Result<BTreeMap<PathBuf, String>> {
+    if let Some(e) = Manifest::name_errors(name).first() {
+        bail!("Invalid service name {}: {}", name, e);
+    }
+    let dir = Path::new(".").join("services").join(name);
+    if dir.exists() {
+        bail!("Service folder {} already exists", dir.display());
+    }
+    let scaffold = Path::new(".").join("scaffolds").join(template);
+    if !scaffold.is_dir() {
+        bail!(
+            "Scaffold {} does not exist, expected one of: {}",
+            template,
+            available().join(", ")
+        );
+    }
+    if regions.is_empty() {
+        bail!("A new service needs at least one region, pass them with --regions");
+    }
+    let metadata = serde_yaml::to_string(&metadata(name, team, conf)?)?;
+
+    let mut ctx = Context::new();
+    ctx.insert("name", &name);
+    ctx.insert("team", &team);
+    ctx.insert(
+        "regions",
+        &regions.iter().map(|r| r.name.clone()).collect::<Vec<_>>(),
+    );
+    ctx.insert("metadata", &metadata.trim_start_matches("---").trim());
+
+    let mut files = BTreeMap::new();
+    let mut targets = vec![("manifest.yml.j2", "manifest.yml".to_string(), None)];
+    if scaffold.join("region.yml.j2").is_file() {
+        for r in regions {
+            targets.push(("region.yml.j2", format!("{}.yml", r.name), Some(&r.name)));
+        }
+    }
+    for (tmpl, filename, region) in targets {
+        let pth = scaffold.join(tmpl);
+        let data = fs::read_to_string(&pth).chain_err(|| format!("Failed to read {}", pth.display()))?;
+        let mut ctx = ctx.clone();
+        if let Some(r) = region {
+            ctx.insert("region", r);
+        }
+        let mut rendered = render_file_data(data, &ctx)?;
+        rendered.push('\n');
+        let formatted = shipcat_filebacked::format_source(&filename, &rendered)
+            .chain_err(|| format!("{} rendered an invalid {}", pth.display(), filename))?;
+        files.insert(dir.join(filename), formatted);
+    }
+    Ok(files)
+}
+
+/// Create a new service from a scaffold and validate it in its regions
+///
+/// Files are kept when validation fails so they can be fixed by hand.
+pub async fn create(name: &str, team: &str, template: &str, regions: &[String]) -> Result<()> {
+    let conf = Config::read().await?;
+    let regions = regions
+        .iter()
+        .map(|r| conf.get_region(r))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let files = render(name, team, template, &regions, &conf)?;
+    for (pth, data) in &files {
+        if let Some(dir) = pth.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(pth, data)?;
+        info!("Created {}", pth.display());
+    }
+    for r in &regions {
+        let (conf, reg) = Config::new(ConfigState::Base, &r.name).await?;
+        validate::manifest_report(
+            vec![name.to_string()],
+            &conf,
+            &reg,
+            false,
+            false,
+            ReportFormat::Human,
+        )
+        .await
+        .chain_err(|| format!("New service {} does not validate in {}", name, reg.name))?;
+    }
+    Ok(())
+}
@@ -18,6 +18,29 @@ pub fn config_crd(conf: Config) -> Result<()> {
     Ok(())
 }
 
+/// Print the merged manifest sources of a service with where each value came from
+///
+/// Values are shown before shipcat computes implicit values, so e.g. `imagePrefix`
+/// is explained rather than `image`.
+pub async fn explain(svc: &str, conf: &Config, reg: &Region) -> Result<()> {
+    // leaves are scalars or lists, so these always serialize
+    let show = |v: &serde_yaml::Value| serde_json::to_string(v).unwrap_or_default();
+    for p in shipcat_filebacked::explain(svc, conf, reg).await? {
+        let mut line = format!("{}: {}  # {}", p.path, show(&p.value), p.layer);
+        let overrode = p
+            .overrode
+            .iter()
+            .rev()
+            .map(|(layer, v)| format!("{} from {}", show(v), layer))
+            .collect::<Vec<_>>();
+        if !overrode.is_empty() {
+            line += &format!(", overriding {}", overrode.join(", "));
+        }
+        println!("{}", line);
+    }
+    Ok(())
+}
+
 // TODO: deprecate
 pub async fn manifest_crd(svc: &str, conf: &Config, reg: &Region) -> Result<()> {
     let mf = shipcat_filebacked::load_manifest(svc, conf, reg).await?;
@@ -28,3 +51,17 @@ pub async fn manifest_crd(svc: &str, conf: &Config, reg: &Region) -> Result<()>
     println!("{}", serde_yaml::to_string(&crd)?);
     Ok(())
 }
+
+/// Print a JSON Schema for one of the file types shipcat reads
+///
+/// Editors can use these for autocompletion and validation of manifests while typing.
+pub fn schema(kind: &str) -> Result<()> {
+    let schema = match kind {
+        "manifest" => shipcat_filebacked::manifest_schema(),
+        "overrides" => shipcat_filebacked::overrides_schema(),
+        "config" => Config::schema(),
+        _ => bail!("Schema must be one of manifest, overrides or config"),
+    };
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
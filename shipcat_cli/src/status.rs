@@ -46,7 +46,12 @@ pub async fn show(svc: &str, conf: &Config, reg: &Region) -> Result<()> {
     let mf = shipcat_filebacked::load_manifest(svc, conf, reg).await?;
     let api = ShipKube::new(&mf).await?;
     let crd = api.get().await?;
-    let pod_res = api.get_pods().await;
+    let mut pod_res = api.get_pods().await;
+    for w in &mf.workers {
+        if let (Ok(pods), Ok(wpods)) = (&mut pod_res, api.scoped(&w.container.name).get_pods().await) {
+            pods.items.extend(wpods.items);
+        }
+    }
 
     let md = mf.metadata.clone().expect("need metadata");
     let ver = crd.spec.version.expect("need version");
@@ -94,6 +99,17 @@ pub async fn show(svc: &str, conf: &Config, reg: &Region) -> Result<()> {
         if let Some(ro) = &conds.rolledout {
             println!("RolledOut {}", format_condition(ro)?);
         }
+        if !stat.workloads.is_empty() {
+            println!();
+            println!("==> WORKLOADS");
+            for (wl, cond) in &stat.workloads {
+                let mut s = format_condition(cond)?;
+                if let (true, Some(msg)) = (cond.status, &cond.message) {
+                    s += &format!(" ({})", msg); // e.g. cronjob schedule info
+                }
+                println!("{} {}", wl, s);
+            }
+        }
     }
     println!();
 
@@ -1,5 +1,6 @@
 use super::{Config, Region, Result};
-use shipcat_definitions::{structs::Kong, BaseManifest};
+use shipcat_definitions::{structs::Kong, Manifest};
+use shipcat_filebacked::ManifestCache;
 
 /// One Statuscake object
 #[derive(Serialize)]
@@ -16,8 +17,8 @@ struct StatuscakeTest {
 }
 
 impl StatuscakeTest {
-    fn new(region: &Region, mf: &BaseManifest, external_svc: String, kong: Kong) -> Option<Self> {
-        let md = &mf.metadata;
+    fn new(region: &Region, mf: &Manifest, external_svc: String, kong: Kong) -> Option<Self> {
+        let md = mf.metadata.as_ref().expect("metadata exists");
         let squad = md.squad.as_ref().expect("squad exists");
         let tribe = md.tribe.as_ref().expect("tribe exists");
         // StatusCake alerts forwarded to pagerduty only includes this name
@@ -77,18 +78,18 @@ async fn generate_statuscake_output(conf: &Config, region: &Region) -> Result<Ve
     if let Some(external_svc) = region.base_urls.get("external_services") {
         debug!("Using base_url.external_services {:?}", external_svc);
         // Generate list of APIs to feed to Statuscake
-        for mf in shipcat_filebacked::available(conf, region).await? {
-            debug!("Found service {:?}", mf);
-            for k in mf.kong_apis.clone() {
-                if k.name != mf.base.name {
+        for mf in ManifestCache::load(conf, region).await? {
+            debug!("Found service {}", mf.name);
+            for k in mf.kongApis.clone() {
+                if k.name != mf.name {
                     debug!(
-                        "{:?} has an additional kong configuration ({:?}), skipping",
-                        mf, k.name
+                        "{} has an additional kong configuration ({:?}), skipping",
+                        mf.name, k.name
                     );
                     continue;
                 }
-                debug!("{:?} has a main kong configuration, adding", mf);
-                if let Some(t) = StatuscakeTest::new(region, &mf.base, external_svc.to_string(), k) {
+                debug!("{} has a main kong configuration, adding", mf.name);
+                if let Some(t) = StatuscakeTest::new(region, &mf, external_svc.to_string(), k) {
                     tests.push(t);
                 }
             }
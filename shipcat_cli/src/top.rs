@@ -1,6 +1,11 @@
 use super::{Config, Error, Manifest, Region, Result};
 use futures::stream::{self, StreamExt};
-use shipcat_definitions::{math::ResourceTotals, BaseManifest};
+use shipcat_definitions::{
+    math::ResourceTotals,
+    structs::{parse_cpu, parse_memory, resources::Resources, ResourceRequirements},
+    BaseManifest,
+};
+use shipcat_filebacked::ManifestCache;
 use std::{collections::BTreeMap, str::FromStr};
 
 use generic_array::{typenum::U4, GenericArray};
@@ -38,19 +43,16 @@ impl FromStr for ResourceOrder {
     }
 }
 
-async fn load_mf_req(svc: String, conf: &Config, reg: &Region) -> Result<(Manifest, ResourceTotals)> {
-    let mf = shipcat_filebacked::load_manifest(&svc, &conf, &reg)
-        .await?
-        .stub(&reg)
-        .await?;
+async fn load_mf_req(mf: Manifest, reg: &Region) -> Result<(Manifest, ResourceTotals)> {
+    let mf = mf.stub(reg).await?;
     let res = mf.compute_resource_totals()?;
     Ok((mf, res))
 }
 
 async fn calculate_manifest_requests(conf: &Config, reg: &Region) -> Result<Vec<(Manifest, ResourceTotals)>> {
-    let available = shipcat_filebacked::available(conf, &reg).await?;
-    let mut buffered = stream::iter(available)
-        .map(move |mf| load_mf_req(mf.base.name, conf, reg))
+    let cache = ManifestCache::load(conf, reg).await?;
+    let mut buffered = stream::iter(cache)
+        .map(move |mf| load_mf_req(mf, reg))
         .buffer_unordered(100);
     let mut mfs = vec![];
     while let Some(r) = buffered.next().await {
@@ -452,3 +454,335 @@ fn sort_and_print_team_resources(
     }
     Ok(reqs)
 }
+
+// ----------------------------------------------------------------------------------
+// actual usage
+
+/// Where to read actual resource usage from
+pub enum UsageSource {
+    /// The metrics.k8s.io api (metrics-server)
+    MetricsApi,
+    /// A prometheus compatible query endpoint scraping cadvisor metrics
+    Prometheus(String),
+}
+
+/// Headroom added on top of observed usage in recommendations
+const HEADROOM: f64 = 1.25;
+/// Relative change in requests below which we do not recommend anything
+const CHANGE_THRESHOLD: f64 = 0.2;
+
+/// Observed usage of a container, summed across its pods
+#[derive(Default, Clone, Debug)]
+pub struct Usage {
+    /// Cores
+    pub cpu: f64,
+    /// Bytes
+    pub memory: f64,
+    /// Number of pods the container was seen in
+    pub pods: u32,
+}
+
+impl Usage {
+    fn per_pod(&self) -> (f64, f64) {
+        let n = f64::from(self.pods.max(1));
+        (self.cpu / n, self.memory / n)
+    }
+}
+
+async fn metrics_api_usage(ns: &str) -> Result<BTreeMap<String, Usage>> {
+    let mut res = BTreeMap::<String, Usage>::new();
+    for pod in crate::kubeapi::get_pod_metrics(ns).await? {
+        for c in pod.containers {
+            let u = res.entry(c.name).or_default();
+            if let Some(cpu) = c.usage.get("cpu") {
+                u.cpu += parse_cpu(cpu)?;
+            }
+            if let Some(mem) = c.usage.get("memory") {
+                u.memory += parse_memory(mem)?;
+            }
+            u.pods += 1;
+        }
+    }
+    Ok(res)
+}
+
+async fn prometheus_query(url: &str, query: &str) -> Result<Vec<(String, f64)>> {
+    #[derive(Deserialize)]
+    struct Sample {
+        metric: BTreeMap<String, String>,
+        value: (f64, String),
+    }
+    #[derive(Deserialize)]
+    struct Data {
+        result: Vec<Sample>,
+    }
+    #[derive(Deserialize)]
+    struct Response {
+        data: Data,
+    }
+    let endpoint = format!("{}/api/v1/query", url.trim_end_matches('/'));
+    debug!("Querying {} for {}", endpoint, query);
+    let resp: Response = reqwest::Client::new()
+        .get(&endpoint)
+        .query(&[("query", query)])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let mut res = vec![];
+    for s in resp.data.result {
+        if let Some(c) = s.metric.get("container") {
+            // prometheus can return NaN and Inf samples
+            let v: f64 = s.value.1.parse()?;
+            if v.is_finite() {
+                res.push((c.clone(), v));
+            }
+        }
+    }
+    Ok(res)
+}
+
+async fn prometheus_usage(url: &str, ns: &str) -> Result<BTreeMap<String, Usage>> {
+    let sel = format!(r#"namespace="{}",container!="",container!="POD""#, ns);
+    let cpu = format!(
+        "sum by (pod, container) (rate(container_cpu_usage_seconds_total{{{}}}[5m]))",
+        sel
+    );
+    let mem = format!(
+        "sum by (pod, container) (container_memory_working_set_bytes{{{}}})",
+        sel
+    );
+    let mut res = BTreeMap::<String, Usage>::new();
+    for (c, v) in prometheus_query(url, &cpu).await? {
+        let u = res.entry(c).or_default();
+        u.cpu += v;
+        u.pods += 1;
+    }
+    for (c, v) in prometheus_query(url, &mem).await? {
+        res.entry(c).or_default().memory += v;
+    }
+    Ok(res)
+}
+
+/// A suggested change to the main container resources of a service
+#[derive(Serialize, Clone)]
+#[allow(non_snake_case)]
+pub struct Recommendation {
+    pub service: String,
+    pub region: String,
+    /// Observed usage per pod
+    pub usage: Resources<String>,
+    pub current: ResourceRequirements<String>,
+    /// The `resources` block to put in the manifest
+    pub resources: ResourceRequirements<String>,
+    /// Estimated daily savings from `ResourceTotals::daily_cost` (negative when increasing)
+    pub dailySavings: f64,
+}
+
+fn format_cpu(cores: f64) -> String {
+    format!("{}m", (cores * 1000.0).round() as u64)
+}
+
+fn format_memory(bytes: f64) -> String {
+    format!("{}Mi", (bytes / (1024.0 * 1024.0)).round() as u64)
+}
+
+/// Round up to a multiple of `step`, but never below `step`
+fn ceil_to(x: f64, step: f64) -> f64 {
+    ((x / step).ceil() * step).max(step)
+}
+
+/// Recommend resources for a manifest given its observed usage
+///
+/// Requests are set to the per-pod usage plus headroom, and limits keep the manifest's
+/// current limit to request ratio. Returns None when the requests are already close enough.
+pub fn recommend(mf: &Manifest, region: &str, usage: &Usage) -> Result<Option<Recommendation>> {
+    let current = match &mf.resources {
+        Some(r) => r.clone(),
+        None => return Ok(None),
+    };
+    if usage.pods == 0 {
+        return Ok(None);
+    }
+    let cur = current.normalised()?;
+    let (cpu, memory) = usage.per_pod();
+    let req_cpu = ceil_to(cpu * HEADROOM, 0.01);
+    let req_mem = ceil_to(memory * HEADROOM, 16.0 * 1024.0 * 1024.0);
+    let changed = |new: f64, old: f64| old <= 0.0 || ((new - old) / old).abs() > CHANGE_THRESHOLD;
+    if !changed(req_cpu, cur.requests.cpu) && !changed(req_mem, cur.requests.memory) {
+        return Ok(None);
+    }
+    let ratio = |limit: f64, request: f64| {
+        if request > 0.0 {
+            (limit / request).max(1.0)
+        } else {
+            1.0
+        }
+    };
+    let lim_cpu = ceil_to(req_cpu * ratio(cur.limits.cpu, cur.requests.cpu), 0.01);
+    let lim_mem = ceil_to(
+        req_mem * ratio(cur.limits.memory, cur.requests.memory),
+        16.0 * 1024.0 * 1024.0,
+    );
+    let resources = ResourceRequirements {
+        requests: Resources {
+            cpu: format_cpu(req_cpu),
+            memory: format_memory(req_mem),
+        },
+        limits: Resources {
+            cpu: format_cpu(lim_cpu),
+            memory: format_memory(lim_mem),
+        },
+    };
+    let mut recommended = mf.clone();
+    recommended.resources = Some(resources.clone());
+    let before = mf.compute_resource_totals()?.normalise().daily_cost().0;
+    let after = recommended.compute_resource_totals()?.normalise().daily_cost().0;
+    Ok(Some(Recommendation {
+        service: mf.name.clone(),
+        region: region.to_string(),
+        usage: Resources {
+            cpu: format_cpu(cpu),
+            memory: format_memory(memory),
+        },
+        current,
+        resources,
+        dailySavings: before - after,
+    }))
+}
+
+/// Resource recommendations for a single region based on actual usage
+///
+/// Compares the main container usage of every service (from the metrics api or prometheus)
+/// with its manifest requests, and prints the recommended changes.
+/// The yaml output is a list of `resources` patches for the manifests.
+pub async fn region_actual(
+    source: UsageSource,
+    fmt: OutputFormat,
+    conf: &Config,
+    reg: &Region,
+) -> Result<Vec<Recommendation>> {
+    let mfs = calculate_manifest_requests(conf, reg).await?;
+    let mut usages = BTreeMap::<String, BTreeMap<String, Usage>>::new();
+    for (mf, _) in &mfs {
+        if mf.disabled || mf.external || usages.contains_key(&mf.namespace) {
+            continue;
+        }
+        let usage = match &source {
+            UsageSource::MetricsApi => metrics_api_usage(&mf.namespace).await?,
+            UsageSource::Prometheus(url) => prometheus_usage(url, &mf.namespace).await?,
+        };
+        usages.insert(mf.namespace.clone(), usage);
+    }
+    let mut recs = vec![];
+    for (mf, _) in &mfs {
+        let usage = usages.get(&mf.namespace).and_then(|u| u.get(&mf.name));
+        if let Some(u) = usage {
+            if let Some(r) = recommend(mf, &reg.name, u)? {
+                recs.push(r);
+            }
+        } else if !mf.disabled && !mf.external {
+            debug!("No usage found for {}", mf.name);
+        }
+    }
+    recs.sort_by(|a, b| b.dailySavings.total_cmp(&a.dailySavings));
+
+    match fmt {
+        OutputFormat::Table => {
+            println!(
+                "{0:<40} {1:<20} {2:<20} {3:<20} {4:>8}",
+                "SERVICE", "USAGE", "REQUESTS", "RECOMMENDED", "SAVINGS"
+            );
+            for r in &recs {
+                println!(
+                    "{0:<40} {1:<20} {2:<20} {3:<20} {4:>8}",
+                    r.service,
+                    format!("{}/{}", r.usage.cpu, r.usage.memory),
+                    format!("{}/{}", r.current.requests.cpu, r.current.requests.memory),
+                    format!("{}/{}", r.resources.requests.cpu, r.resources.requests.memory),
+                    format!("${:.0}", r.dailySavings),
+                );
+            }
+            let total: f64 = recs.iter().map(|r| r.dailySavings).sum();
+            println!("Estimated daily savings: ${:.0}", total);
+        }
+        OutputFormat::Yaml => {
+            #[derive(Serialize)]
+            #[allow(non_snake_case)]
+            struct Patch<'a> {
+                service: &'a str,
+                region: &'a str,
+                dailySavings: f64,
+                patch: BTreeMap<&'a str, &'a ResourceRequirements<String>>,
+            }
+            let patches = recs
+                .iter()
+                .map(|r| Patch {
+                    service: &r.service,
+                    region: &r.region,
+                    dailySavings: r.dailySavings,
+                    patch: vec![("resources", &r.resources)].into_iter().collect(),
+                })
+                .collect::<Vec<_>>();
+            println!("{}", serde_yaml::to_string(&patches)?);
+        }
+    }
+    Ok(recs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{recommend, Usage};
+    use shipcat_definitions::{
+        structs::{resources::Resources, ResourceRequirements},
+        Manifest,
+    };
+
+    fn manifest(cpu: &str, memory: &str) -> Manifest {
+        let mut mf = Manifest::default();
+        mf.name = "fake-ask".into();
+        mf.replicaCount = Some(2);
+        mf.resources = Some(ResourceRequirements {
+            requests: Resources {
+                cpu: cpu.into(),
+                memory: memory.into(),
+            },
+            limits: Resources {
+                cpu: "2".into(),
+                memory: "2Gi".into(),
+            },
+        });
+        mf
+    }
+
+    #[test]
+    fn recommend_from_usage() {
+        // 2 pods using 100m/200Mi each against requests of 1 core and 1Gi
+        let usage = Usage {
+            cpu: 0.2,
+            memory: 400.0 * 1024.0 * 1024.0,
+            pods: 2,
+        };
+        let r = recommend(&manifest("1", "1Gi"), "dev-uk", &usage)
+            .unwrap()
+            .unwrap();
+        assert_eq!(r.usage.cpu, "100m");
+        assert_eq!(r.usage.memory, "200Mi");
+        assert_eq!(r.resources.requests.cpu, "130m");
+        assert_eq!(r.resources.requests.memory, "256Mi");
+        // limits keep the 2x ratio
+        assert_eq!(r.resources.limits.cpu, "260m");
+        assert_eq!(r.resources.limits.memory, "512Mi");
+        assert!(r.dailySavings >= 0.0);
+
+        // requests close to usage with headroom: nothing to do
+        assert!(recommend(&manifest("130m", "256Mi"), "dev-uk", &usage)
+            .unwrap()
+            .is_none());
+        // no pods seen: nothing to do
+        assert!(recommend(&manifest("1", "1Gi"), "dev-uk", &Usage::default())
+            .unwrap()
+            .is_none());
+    }
+}
@@ -1,12 +1,16 @@
 //- kubeapi module to track upgrades
 use crate::{kubeapi::ShipKube, slack::short_ver, Result};
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use k8s_openapi::api::{
     apps::v1::{Deployment, ReplicaSet, StatefulSet},
-    core::v1::Pod,
+    batch::v1beta1::CronJob,
+    core::v1::{ContainerStatus, Event, Pod},
 };
 use kube::api::{Meta, ObjectList};
-use shipcat_definitions::{Manifest, PrimaryWorkload};
+use shipcat_definitions::{
+    structs::{PartitionGate, RollingUpdate, Worker},
+    Manifest, PrimaryWorkload,
+};
 use std::{
     convert::{TryFrom, TryInto},
     fmt::{self, Debug},
@@ -163,7 +167,11 @@ impl TryFrom<ReplicaSet> for ReplicaSetSummary {
 
 /// Debug why a workload is in the state it is in
 pub async fn debug(mf: &Manifest, kube: &ShipKube) -> Result<()> {
-    match mf.workload {
+    debug_workload(&mf.workload, kube).await
+}
+
+async fn debug_workload(workload: &PrimaryWorkload, kube: &ShipKube) -> Result<()> {
+    match workload {
         PrimaryWorkload::Deployment => debug_deployment(kube).await,
         PrimaryWorkload::Statefulset => debug_statefulset(kube).await,
     }
@@ -313,23 +321,68 @@ struct RolloutResult {
     ok: bool,
 }
 
+/// A Deployment or Statefulset to wait for
+struct Target {
+    name: String,
+    workload: PrimaryWorkload,
+    minimum: u32,
+    waittime: u32,
+    /// Rolling update settings for a partitioned Statefulset
+    partitioned: Option<RollingUpdate>,
+}
+
+impl Target {
+    fn primary(mf: &Manifest) -> Self {
+        Target {
+            name: mf.name.clone(),
+            workload: mf.workload.clone(),
+            minimum: mf.min_replicas(),
+            waittime: mf.estimate_wait_time(),
+            partitioned: match mf.workload {
+                PrimaryWorkload::Statefulset => mf.rollingUpdate.clone().filter(|ru| ru.partition.is_some()),
+                PrimaryWorkload::Deployment => None,
+            },
+        }
+    }
+
+    fn worker(mf: &Manifest, w: &Worker) -> Self {
+        let minimum = w
+            .autoScaling
+            .as_ref()
+            .map(|a| a.minReplicas)
+            .unwrap_or(w.replicaCount);
+        Target {
+            name: w.container.name.clone(),
+            workload: PrimaryWorkload::Deployment,
+            minimum,
+            waittime: mf.estimate_wait_time(), // same image as the main workload
+            partitioned: None,
+        }
+    }
+
+    /// Key used for this workload in the `.status.workloads` map
+    fn key(&self) -> String {
+        format!("{:?}/{}", self.workload, self.name)
+    }
+}
+
 /// Check if a rollout has completed
-async fn rollout_status(mf: &Manifest, kube: &ShipKube, hash: &Option<String>) -> Result<RolloutResult> {
-    match mf.workload {
+async fn rollout_status(t: &Target, kube: &ShipKube, hash: &Option<String>) -> Result<RolloutResult> {
+    match t.workload {
         PrimaryWorkload::Deployment => {
             // Get root data from Deployment status
             let deploy = kube.get_deploy().await?;
             let d = DeploySummary::try_from(deploy)?;
-            debug!("{}: {:?}", mf.name, d);
+            debug!("{}: {:?}", t.name, d);
             // Wait for at least the minimum number...
 
             let mut acurate_progress = None; // accurate progress number
-            let mut minimum = mf.min_replicas(); // minimum replicas we wait for
+            let mut minimum = t.minimum; // minimum replicas we wait for
             if let Some(tpl_hash) = hash {
                 // Infer from pinned ReplicaSet status (that was latest during apply)
                 if let Some(rs) = kube.get_rs_by_template_hash(&tpl_hash).await? {
                     let r = ReplicaSetSummary::try_from(rs)?;
-                    debug!("{}: {:?}", mf.name, r);
+                    debug!("{}: {:?}", t.name, r);
                     acurate_progress = Some(r.ready);
                     // rs might have scaled it up during rollout
                     minimum = std::cmp::max(minimum, r.replicas.try_into().unwrap_or(0));
@@ -378,7 +431,7 @@ async fn rollout_status(mf: &Manifest, kube: &ShipKube, hash: &Option<String>) -
         PrimaryWorkload::Statefulset => {
             let ss = kube.get_statefulset().await?;
             let s = StatefulSummary::try_from(ss)?;
-            let minimum = mf.min_replicas();
+            let minimum = t.minimum;
 
             let ok = s.updated_replicas >= minimum as i32
                 && s.updated_replicas == s.ready
@@ -411,15 +464,199 @@ async fn rollout_status(mf: &Manifest, kube: &ShipKube, hash: &Option<String>) -
     }
 }
 
+/// Classified cause of a failed rollout
+///
+/// Variants are ordered by how likely they are to be the root cause,
+/// so that e.g. an image pull failure beats the probe failures it leads to.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RolloutFailure {
+    /// Image could not be pulled (ImagePullBackOff / ErrImagePull)
+    ImagePull,
+    /// Container could not be created (missing secret or configmap keys)
+    ContainerConfig,
+    /// Pods could not be scheduled onto any node
+    Unschedulable,
+    /// Containers were killed for exceeding their memory limit
+    OutOfMemory,
+    /// Containers keep crashing after starting
+    CrashLoop,
+    /// Readiness or liveness probes are failing
+    ProbeFailure,
+}
+
+impl RolloutFailure {
+    /// Condition reason string for the failure
+    pub fn reason(&self) -> &'static str {
+        match self {
+            RolloutFailure::ImagePull => "ImagePullBackOff",
+            RolloutFailure::ContainerConfig => "CreateContainerConfigError",
+            RolloutFailure::Unschedulable => "FailedScheduling",
+            RolloutFailure::OutOfMemory => "OOMKilled",
+            RolloutFailure::CrashLoop => "CrashLoopBackOff",
+            RolloutFailure::ProbeFailure => "ProbeFailure",
+        }
+    }
+
+    /// Classify a kubernetes Warning event
+    pub fn from_event(reason: &str, message: &str) -> Option<Self> {
+        match reason {
+            "FailedScheduling" => Some(RolloutFailure::Unschedulable),
+            "Unhealthy" => Some(RolloutFailure::ProbeFailure),
+            "BackOff" if message.contains("pulling image") => Some(RolloutFailure::ImagePull),
+            "BackOff" if message.contains("restarting failed container") => Some(RolloutFailure::CrashLoop),
+            "Failed" | "ErrImagePull" | "ImagePullBackOff"
+                if message.contains("ImagePullBackOff")
+                    || message.contains("ErrImagePull")
+                    || message.contains("pull image") =>
+            {
+                Some(RolloutFailure::ImagePull)
+            }
+            _ => None,
+        }
+    }
+
+    /// Classify a reason from a container's waiting or terminated state
+    pub fn from_container_reason(reason: &str) -> Option<Self> {
+        match reason {
+            "ImagePullBackOff" | "ErrImagePull" | "InvalidImageName" => Some(RolloutFailure::ImagePull),
+            "CreateContainerConfigError" | "CreateContainerError" => Some(RolloutFailure::ContainerConfig),
+            "OOMKilled" => Some(RolloutFailure::OutOfMemory),
+            "CrashLoopBackOff" => Some(RolloutFailure::CrashLoop),
+            _ => None,
+        }
+    }
+}
+
+/// A classified rollout failure along with the object it was observed on
+#[derive(Debug, Clone)]
+pub struct RolloutDiagnosis {
+    pub cause: RolloutFailure,
+    /// Kind and name of the object, e.g. `Pod/webapp-7d9c8-x2x9z`
+    pub object: String,
+    /// Message from kubernetes explaining the failure
+    pub message: String,
+}
+
+impl fmt::Display for RolloutDiagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} on {}: {}", self.cause.reason(), self.object, self.message)
+    }
+}
+
+impl RolloutDiagnosis {
+    /// Pick the most likely root cause out of a set of findings
+    fn most_relevant(diags: Vec<RolloutDiagnosis>) -> Option<RolloutDiagnosis> {
+        diags.into_iter().min_by(|a, b| a.cause.cmp(&b.cause))
+    }
+}
+
+fn diagnose_container(pod: &str, cs: &ContainerStatus) -> Option<RolloutDiagnosis> {
+    let object = format!("Pod/{}", pod);
+    let state = cs.state.as_ref();
+    if let Some(w) = state.and_then(|s| s.waiting.as_ref()) {
+        if let Some(cause) = w
+            .reason
+            .as_ref()
+            .and_then(|r| RolloutFailure::from_container_reason(r))
+        {
+            let message = w.message.clone().unwrap_or_else(|| cause.reason().to_string());
+            return Some(RolloutDiagnosis {
+                cause,
+                object,
+                message,
+            });
+        }
+    }
+    // OOMKilled only shows up as the termination reason of the current or last state
+    let terminated = state
+        .and_then(|s| s.terminated.as_ref())
+        .or_else(|| cs.last_state.as_ref().and_then(|s| s.terminated.as_ref()));
+    if let Some(t) = terminated {
+        if let Some(RolloutFailure::OutOfMemory) = t
+            .reason
+            .as_ref()
+            .and_then(|r| RolloutFailure::from_container_reason(r))
+        {
+            let message = format!("container {} exceeded its memory limit", cs.name);
+            return Some(RolloutDiagnosis {
+                cause: RolloutFailure::OutOfMemory,
+                object,
+                message,
+            });
+        }
+    }
+    None
+}
+
+fn diagnose_event(ev: &Event, names: &[String], since: &DateTime<Utc>) -> Option<RolloutDiagnosis> {
+    let obj = &ev.involved_object;
+    let name = obj.name.as_ref()?;
+    if !names.contains(name) {
+        return None;
+    }
+    if let Some(ts) = ev
+        .last_timestamp
+        .as_ref()
+        .map(|t| t.0)
+        .or(ev.event_time.as_ref().map(|t| t.0))
+    {
+        if ts < *since {
+            return None; // stale event from a previous rollout
+        }
+    }
+    let message = ev.message.clone().unwrap_or_default();
+    let cause = RolloutFailure::from_event(ev.reason.as_ref()?, &message)?;
+    let kind = obj.kind.clone().unwrap_or_else(|| "Object".into());
+    Some(RolloutDiagnosis {
+        cause,
+        object: format!("{}/{}", kind, name),
+        message: message.trim().to_string(),
+    })
+}
+
+/// Classify why a rollout is not progressing
+///
+/// Looks at Warning events for the workload, its replicasets and its pods,
+/// as well as the container states of pods created since the rollout started.
+pub async fn diagnose_rollout(kube: &ShipKube, since: &DateTime<Utc>) -> Result<Option<RolloutDiagnosis>> {
+    let mut names = vec![kube.workload().to_string()];
+    let mut diags = vec![];
+    for rs in kube.get_rs().await? {
+        names.push(Meta::name(&rs));
+    }
+    for pod in kube.get_pods().await? {
+        let podname = Meta::name(&pod);
+        let created = Meta::meta(&pod).creation_timestamp.as_ref().map(|t| t.0);
+        if created.map(|c| c >= *since).unwrap_or(true) {
+            if let Some(status) = &pod.status {
+                for cs in status.container_statuses.as_ref().unwrap_or(&vec![]) {
+                    diags.extend(diagnose_container(&podname, cs));
+                }
+            }
+        }
+        names.push(podname);
+    }
+    for ev in kube.get_warning_events().await? {
+        diags.extend(diagnose_event(&ev, &names, since));
+    }
+    debug!("Rollout diagnostics for {}: {:?}", kube.workload(), diags);
+    Ok(RolloutDiagnosis::most_relevant(diags))
+}
+
 /// Track the rollout of the main workload
 pub async fn workload_rollout(mf: &Manifest, kube: &ShipKube) -> Result<bool> {
+    target_rollout(&Target::primary(mf), kube, &Utc::now()).await
+}
+
+/// Track the rollout of a single Deployment or Statefulset
+async fn target_rollout(t: &Target, kube: &ShipKube, started: &DateTime<Utc>) -> Result<bool> {
     use futures_timer::Delay;
     use indicatif::{ProgressBar, ProgressStyle};
-    let minimum = mf.min_replicas();
-    let waittime = mf.estimate_wait_time();
+    let minimum = t.minimum;
+    let waittime = t.waittime;
     let one_sec = std::time::Duration::from_millis(1000);
 
-    match rollout_status(mf, kube, &None).await {
+    match rollout_status(t, kube, &None).await {
         Ok(rr) => {
             if rr.ok {
                 return Ok(true);
@@ -435,17 +672,17 @@ pub async fn workload_rollout(mf: &Manifest, kube: &ShipKube) -> Result<bool> {
 
     info!(
         "Waiting {}s for {:?} {} to rollout (not ready yet)",
-        waittime, mf.workload, mf.name
+        waittime, t.workload, t.name
     );
     let mut hash = None;
-    match mf.workload {
+    match t.workload {
         PrimaryWorkload::Deployment => {
             // Attempt to find an owning RS hash to track
             if let Some(rs) = kube.get_rs_from_deploy().await? {
                 if let Some(meta) = rs.metadata {
                     if let Some(labels) = meta.labels {
                         if let Some(h) = labels.get("pod-template-hash") {
-                            debug!("Tracking replicaset {} for {}", h, mf.name);
+                            debug!("Tracking replicaset {} for {}", h, t.name);
                             hash = Some(h.clone());
                         }
                     }
@@ -457,7 +694,7 @@ pub async fn workload_rollout(mf: &Manifest, kube: &ShipKube) -> Result<bool> {
             let sts = kube.get_statefulset().await?;
             let summary = StatefulSummary::try_from(sts)?;
             if let Some(ur) = summary.update_revision {
-                debug!("Tracking statefulset {:?} for {}", ur, mf.name);
+                debug!("Tracking statefulset {:?} for {}", ur, t.name);
                 hash = Some(ur);
             }
         }
@@ -472,18 +709,19 @@ pub async fn workload_rollout(mf: &Manifest, kube: &ShipKube) -> Result<bool> {
     );
     pb.set_draw_delta(1);
     if let Some(h) = &hash {
-        match mf.workload {
+        match t.workload {
             PrimaryWorkload::Deployment => {
-                pb.set_prefix(&format!("{}-{}", mf.name, h));
+                pb.set_prefix(&format!("{}-{}", t.name, h));
             }
             PrimaryWorkload::Statefulset => {
                 pb.set_prefix(h); // statefulset hash already prefixes name
             }
         }
     } else {
-        pb.set_prefix(&mf.name);
+        pb.set_prefix(&t.name);
     }
 
+    let mut last_progress = 0;
     for i in 1..20 {
         trace!("poll iteration {}", i);
         let mut waited = 0;
@@ -493,7 +731,7 @@ pub async fn workload_rollout(mf: &Manifest, kube: &ShipKube) -> Result<bool> {
             trace!("sleep 1s (waited {})", waited);
             Delay::new(one_sec).await;
         }
-        let rr = rollout_status(mf, kube, &hash).await?;
+        let rr = rollout_status(t, kube, &hash).await?;
         debug!("RR: {:?}", rr);
         if let Some(msg) = rr.message {
             pb.set_message(&msg);
@@ -504,6 +742,474 @@ pub async fn workload_rollout(mf: &Manifest, kube: &ShipKube) -> Result<bool> {
             pb.finish_at_current_pos();
             return Ok(true);
         }
+        // surface the likely cause early when no pods became ready since the last poll
+        if rr.progress <= last_progress {
+            match diagnose_rollout(kube, started).await {
+                Ok(Some(d)) => pb.set_message(&d.to_string()),
+                Ok(None) => {}
+                Err(e) => debug!("Failed to diagnose rollout of {}: {}", t.name, e),
+            }
+        }
+        last_progress = rr.progress;
     }
     Ok(false) // timeout
 }
+
+/// How waiting for a single Deployment or Statefulset ended
+enum Tracked {
+    Done,
+    /// Pods did not become ready in time
+    Timeout(String),
+    /// A gate stopped a partitioned rollout between steps
+    Halted(String),
+}
+
+fn pod_ready(pod: &Pod) -> bool {
+    let conds = pod.status.as_ref().and_then(|s| s.conditions.as_ref());
+    conds
+        .map(|cs| cs.iter().any(|c| c.type_ == "Ready" && c.status == "True"))
+        .unwrap_or(false)
+}
+
+/// Check if a statefulset pod runs the given revision and is ready
+async fn pod_updated(kube: &ShipKube, podname: &str, revision: &str) -> bool {
+    // pods briefly disappear while being recreated
+    if let Ok(pod) = kube.get_pod(podname).await {
+        let labels = Meta::meta(&pod).labels.clone().unwrap_or_default();
+        if labels.get("controller-revision-hash").map(String::as_str) == Some(revision) {
+            return pod_ready(&pod);
+        }
+    }
+    false
+}
+
+async fn wait_for_pod(kube: &ShipKube, podname: &str, revision: &str, secs: u32) -> bool {
+    use futures_timer::Delay;
+    info!("Waiting {}s for {} to roll out", secs, podname);
+    for _ in 0..secs {
+        if pod_updated(kube, podname, revision).await {
+            return true;
+        }
+        Delay::new(std::time::Duration::from_millis(1000)).await;
+    }
+    false
+}
+
+/// Ask for confirmation on the terminal
+fn confirm(question: &str) -> bool {
+    use std::io::{self, BufRead, Write};
+    if unsafe { libc::isatty(libc::STDIN_FILENO) } == 0 {
+        warn!("Cannot ask for confirmation without a terminal");
+        return false;
+    }
+    print!("{} [y/N] ", question);
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    answer.trim().eq_ignore_ascii_case("y")
+}
+
+/// Decide whether to update the next ordinal of a partitioned rollout
+///
+/// Returns a reason to halt the rollout if the gate did not pass.
+async fn partition_gate(
+    ru: &RollingUpdate,
+    kube: &ShipKube,
+    pods: &[String],
+    revision: &str,
+    next: u32,
+) -> Option<String> {
+    use futures_timer::Delay;
+    match ru.step_gate() {
+        PartitionGate::Manual => {
+            let question = format!("{} updated. Continue with partition {}?", pods.join(", "), next);
+            if confirm(&question) {
+                None
+            } else {
+                Some(format!("rollout not confirmed before partition {}", next))
+            }
+        }
+        PartitionGate::Auto => {
+            let wait = ru.step_wait();
+            info!("Checking {} stay ready for {}s", pods.join(", "), wait);
+            for _ in 0..wait {
+                for p in pods {
+                    if !pod_updated(kube, p, revision).await {
+                        return Some(format!("{} became unready before partition {}", p, next));
+                    }
+                }
+                Delay::new(std::time::Duration::from_millis(1000)).await;
+            }
+            None
+        }
+    }
+}
+
+/// Wait for the statefulset controller to observe the latest spec
+///
+/// The update revision is only accurate once `observedGeneration` catches up with an apply.
+async fn observed_statefulset(t: &Target, kube: &ShipKube) -> Result<StatefulSet> {
+    use futures_timer::Delay;
+    for _ in 0..t.waittime {
+        let sts = kube.get_statefulset().await?;
+        let generation = Meta::meta(&sts).generation;
+        let observed = sts.status.as_ref().and_then(|s| s.observed_generation);
+        if generation.is_some() && observed >= generation {
+            return Ok(sts);
+        }
+        debug!("Waiting for {} to observe generation {:?}", t.name, generation);
+        Delay::new(std::time::Duration::from_millis(1000)).await;
+    }
+    bail!(
+        "statefulset {} was not observed by its controller within {}s",
+        t.name,
+        t.waittime
+    )
+}
+
+/// Roll out a partitioned Statefulset one ordinal at a time
+///
+/// Kubernetes updates the ordinals at and above the partition straight after an apply.
+/// The partition is then stepped down, waiting for each new pod and passing the gate between steps.
+/// Once every pod is updated, the partition is put back so the live object matches the template.
+/// When a step halts or times out, the partition is left where it stopped and reported,
+/// and the next apply puts it back.
+async fn partitioned_rollout(t: &Target, ru: &RollingUpdate, kube: &ShipKube) -> Result<Tracked> {
+    let sts = observed_statefulset(t, kube).await?;
+    // autoscaled statefulsets can run more than the minimum, so use the live replica count
+    let replicas = sts
+        .spec
+        .as_ref()
+        .and_then(|s| s.replicas)
+        .map_or(t.minimum, |r| r.max(0) as u32);
+    let partition = std::cmp::min(ru.partition.unwrap_or(0), replicas);
+    let revision = match StatefulSummary::try_from(sts)?.update_revision {
+        Some(r) => r,
+        None => bail!("Missing update revision on statefulset {}", t.name),
+    };
+    debug!(
+        "Partitioned rollout of {} at {} using {}",
+        t.name, partition, revision
+    );
+
+    let mut updated = vec![];
+    // statefulsets update ordinals in reverse order
+    for ord in (partition..replicas).rev() {
+        let pod = format!("{}-{}", t.name, ord);
+        if !wait_for_pod(kube, &pod, &revision, t.waittime).await {
+            return Ok(Tracked::Timeout(format!(
+                "timed out waiting {}s for {}",
+                t.waittime, pod
+            )));
+        }
+        updated.push(pod);
+    }
+    let stopped = |reason: String, at: u32| {
+        format!(
+            "{}; partition of {} left at {} with {}/{} pods updated",
+            reason,
+            t.name,
+            at,
+            replicas - at,
+            replicas
+        )
+    };
+    for ord in (0..partition).rev() {
+        if !updated.is_empty() {
+            if let Some(reason) = partition_gate(ru, kube, &updated, &revision, ord).await {
+                return Ok(Tracked::Halted(stopped(reason, ord + 1)));
+            }
+        }
+        info!("Moving partition of {} to {}", t.name, ord);
+        kube.set_statefulset_partition(ord).await?;
+        let pod = format!("{}-{}", t.name, ord);
+        if !wait_for_pod(kube, &pod, &revision, t.waittime).await {
+            let reason = format!("timed out waiting {}s for {}", t.waittime, pod);
+            return Ok(Tracked::Timeout(stopped(reason, ord)));
+        }
+        updated.push(pod);
+    }
+    if partition > 0 {
+        kube.set_statefulset_partition(partition).await?;
+    }
+    Ok(Tracked::Done)
+}
+
+/// Outcome of tracking or validating a single workload
+#[derive(Debug, Clone)]
+pub struct WorkloadResult {
+    /// Kind and name of the workload, e.g. `Deployment/webapp-worker`
+    pub workload: String,
+    /// Failure reason (e.g. `Timeout` or `CronJobSuspended`) when the workload is unhealthy
+    pub reason: Option<String>,
+    /// Explanation of a failure, or extra detail for a healthy workload
+    pub message: Option<String>,
+    /// Classified cause of a rollout that timed out (if available)
+    pub diagnosis: Option<RolloutDiagnosis>,
+    timeout: bool,
+}
+
+impl WorkloadResult {
+    fn ok(workload: String, message: Option<String>) -> Self {
+        WorkloadResult {
+            workload,
+            reason: None,
+            message,
+            diagnosis: None,
+            timeout: false,
+        }
+    }
+
+    fn failed(workload: String, reason: &str, message: String) -> Self {
+        WorkloadResult {
+            workload,
+            reason: Some(reason.into()),
+            message: Some(message),
+            diagnosis: None,
+            timeout: false,
+        }
+    }
+
+    fn timed_out(workload: String, message: String, diagnosis: Option<RolloutDiagnosis>) -> Self {
+        let (reason, message) = match &diagnosis {
+            Some(d) => (d.cause.reason(), format!("{}: {}", message, d)),
+            None => ("Timeout", message),
+        };
+        WorkloadResult {
+            timeout: true,
+            diagnosis,
+            ..WorkloadResult::failed(workload, reason, message)
+        }
+    }
+
+    /// Whether the workload rolled out, or validated, successfully
+    pub fn is_ok(&self) -> bool {
+        self.reason.is_none()
+    }
+
+    /// Whether the workload failed by not rolling out in time
+    pub fn is_timeout(&self) -> bool {
+        self.timeout
+    }
+}
+
+/// Validate a cronjob against the schedule in its manifest
+///
+/// Cronjobs have nothing to roll out, but they can be left suspended,
+/// or not pick up the schedule that was applied.
+fn check_cronjob(schedule: &str, cj: &CronJob, now: &DateTime<Utc>) -> WorkloadResult {
+    let key = format!("CronJob/{}", Meta::name(cj));
+    if let Some(spec) = &cj.spec {
+        if spec.suspend == Some(true) {
+            return WorkloadResult::failed(key, "CronJobSuspended", "cronjob is suspended".into());
+        }
+        if spec.schedule != schedule {
+            let msg = format!("schedule is '{}' but manifest has '{}'", spec.schedule, schedule);
+            return WorkloadResult::failed(key, "ScheduleMismatch", msg);
+        }
+    }
+    let last = cj.status.as_ref().and_then(|s| s.last_schedule_time.as_ref());
+    let msg = match last {
+        Some(t) => format!(
+            "last scheduled {} ago",
+            format_duration(now.signed_duration_since(t.0))
+        ),
+        None => "not scheduled yet".to_string(),
+    };
+    WorkloadResult::ok(key, Some(msg))
+}
+
+/// Track the rollout of every workload in a manifest
+///
+/// Waits for the main workload and each worker deployment in turn, then validates the cronjobs.
+/// Once one workload has timed out, the remaining ones are only checked, not waited for.
+pub async fn manifest_rollout(mf: &Manifest, kube: &ShipKube, since: &DateTime<Utc>) -> Vec<WorkloadResult> {
+    let mut targets = vec![Target::primary(mf)];
+    targets.extend(mf.workers.iter().map(|w| Target::worker(mf, w)));
+
+    let mut results: Vec<WorkloadResult> = vec![];
+    for t in targets {
+        let wkube = kube.scoped(&t.name);
+        let timed_out = || Tracked::Timeout(format!("timed out waiting {}s for rollout", t.waittime));
+        let waited = if results.iter().any(WorkloadResult::is_timeout) {
+            // only check the rest once something has timed out
+            let status = rollout_status(&t, &wkube, &None).await;
+            status.map(|rr| if rr.ok { Tracked::Done } else { timed_out() })
+        } else if let Some(ru) = &t.partitioned {
+            partitioned_rollout(&t, ru, &wkube).await
+        } else {
+            let ok = target_rollout(&t, &wkube, since).await;
+            ok.map(|ok| if ok { Tracked::Done } else { timed_out() })
+        };
+        let res = match waited {
+            Ok(Tracked::Done) => WorkloadResult::ok(t.key(), None),
+            Ok(Tracked::Halted(msg)) => WorkloadResult::failed(t.key(), "PartitionHalted", msg),
+            Ok(Tracked::Timeout(msg)) => {
+                let diagnosis = match diagnose_rollout(&wkube, since).await {
+                    Ok(d) => d,
+                    Err(e) => {
+                        warn!("Unable to diagnose rollout of {}: {}", t.name, e);
+                        None
+                    }
+                };
+                let _ = debug_workload(&t.workload, &wkube).await;
+                WorkloadResult::timed_out(t.key(), msg, diagnosis)
+            }
+            Err(e) => WorkloadResult::failed(t.key(), "RolloutTrackFailure", e.to_string()),
+        };
+        results.push(res);
+    }
+
+    let now = Utc::now();
+    for cj in &mf.cronJobs {
+        let res = match kube.get_cronjob(&cj.container.name).await {
+            Ok(k) => check_cronjob(&cj.schedule, &k, &now),
+            Err(e) => {
+                let key = format!("CronJob/{}", cj.container.name);
+                WorkloadResult::failed(key, "RolloutTrackFailure", e.to_string())
+            }
+        };
+        results.push(res);
+    }
+    for r in &results {
+        debug!("{}: {:?}", mf.name, r);
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_cronjob, pod_ready, RolloutDiagnosis, RolloutFailure, WorkloadResult};
+    use chrono::{TimeZone, Utc};
+    use k8s_openapi::api::{batch::v1beta1::CronJob, core::v1::Pod};
+
+    #[test]
+    fn classify_events() {
+        let imgmsg = "Back-off pulling image \"quay.io/babylonhealth/raftcat:0.1.0\"";
+        assert_eq!(
+            RolloutFailure::from_event("BackOff", imgmsg),
+            Some(RolloutFailure::ImagePull)
+        );
+        assert_eq!(
+            RolloutFailure::from_event("Failed", "Error: ErrImagePull"),
+            Some(RolloutFailure::ImagePull)
+        );
+        assert_eq!(
+            RolloutFailure::from_event("BackOff", "Back-off restarting failed container"),
+            Some(RolloutFailure::CrashLoop)
+        );
+        assert_eq!(
+            RolloutFailure::from_event("FailedScheduling", "0/3 nodes are available: 3 Insufficient cpu."),
+            Some(RolloutFailure::Unschedulable)
+        );
+        assert_eq!(
+            RolloutFailure::from_event("Unhealthy", "Readiness probe failed: HTTP probe failed"),
+            Some(RolloutFailure::ProbeFailure)
+        );
+        assert_eq!(
+            RolloutFailure::from_event("Killing", "Stopping container raftcat"),
+            None
+        );
+        assert_eq!(
+            RolloutFailure::from_container_reason("OOMKilled"),
+            Some(RolloutFailure::OutOfMemory)
+        );
+        assert_eq!(RolloutFailure::from_container_reason("Completed"), None);
+    }
+
+    #[test]
+    fn diagnosis_prefers_root_cause() {
+        let mk = |cause| RolloutDiagnosis {
+            cause,
+            object: "Pod/raftcat-abc".into(),
+            message: "msg".into(),
+        };
+        let diags = vec![
+            mk(RolloutFailure::ProbeFailure),
+            mk(RolloutFailure::OutOfMemory),
+            mk(RolloutFailure::CrashLoop),
+        ];
+        let d = RolloutDiagnosis::most_relevant(diags).unwrap();
+        assert_eq!(d.cause, RolloutFailure::OutOfMemory);
+        assert_eq!(d.to_string(), "OOMKilled on Pod/raftcat-abc: msg");
+        assert!(RolloutDiagnosis::most_relevant(vec![]).is_none());
+    }
+
+    fn cronjob(schedule: &str, suspend: bool, last: Option<&str>) -> CronJob {
+        serde_json::from_value(serde_json::json!({
+            "metadata": { "name": "fake-job" },
+            "spec": {
+                "schedule": schedule,
+                "suspend": suspend,
+                "jobTemplate": {}
+            },
+            "status": { "lastScheduleTime": last }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn cronjob_validation() {
+        let now = Utc.ymd(2020, 4, 20).and_hms(12, 0, 0);
+        let ok = check_cronjob(
+            "0 * * * *",
+            &cronjob("0 * * * *", false, Some("2020-04-20T10:00:00Z")),
+            &now,
+        );
+        assert!(ok.is_ok());
+        assert_eq!(ok.workload, "CronJob/fake-job");
+        assert_eq!(ok.message.unwrap(), "last scheduled 2h ago");
+
+        let fresh = check_cronjob("0 * * * *", &cronjob("0 * * * *", false, None), &now);
+        assert!(fresh.is_ok());
+        assert_eq!(fresh.message.unwrap(), "not scheduled yet");
+
+        let suspended = check_cronjob("0 * * * *", &cronjob("0 * * * *", true, None), &now);
+        assert!(!suspended.is_timeout());
+        assert_eq!(suspended.reason.unwrap(), "CronJobSuspended");
+
+        let stale = check_cronjob("0 1 * * *", &cronjob("0 * * * *", false, None), &now);
+        assert_eq!(stale.reason.unwrap(), "ScheduleMismatch");
+    }
+
+    #[test]
+    fn timeouts_use_diagnosis() {
+        let plain = WorkloadResult::timed_out("Deployment/raftcat".into(), "timed out".into(), None);
+        assert!(plain.is_timeout());
+        assert_eq!(plain.reason.unwrap(), "Timeout");
+
+        let d = RolloutDiagnosis {
+            cause: RolloutFailure::ImagePull,
+            object: "Pod/raftcat-abc".into(),
+            message: "Back-off pulling image".into(),
+        };
+        let diagnosed = WorkloadResult::timed_out("Deployment/raftcat".into(), "timed out".into(), Some(d));
+        assert!(!diagnosed.is_ok());
+        assert_eq!(diagnosed.reason.unwrap(), "ImagePullBackOff");
+        assert_eq!(
+            diagnosed.message.unwrap(),
+            "timed out: ImagePullBackOff on Pod/raftcat-abc: Back-off pulling image"
+        );
+    }
+
+    #[test]
+    fn pod_readiness() {
+        let pod = |ready: &str| -> Pod {
+            serde_json::from_value(serde_json::json!({
+                "metadata": { "name": "fake-storage-2" },
+                "status": {
+                    "conditions": [
+                        { "type": "PodScheduled", "status": "True" },
+                        { "type": "Ready", "status": ready }
+                    ]
+                }
+            }))
+            .unwrap()
+        };
+        assert!(pod_ready(&pod("True")));
+        assert!(!pod_ready(&pod("False")));
+        assert!(!pod_ready(&Pod::default()));
+    }
+}
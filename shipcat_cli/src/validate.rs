@@ -1,28 +1,110 @@
-use super::{Config, Manifest, Region, Result};
-use crate::{error_chain::ChainedError, git};
+use super::{
+    structs::{Dependency, DependencyProtocol, Kong},
+    Config, Error, Manifest, Region, Result,
+};
+use crate::{error_chain::ChainedError, git, graph, kafka, kong, registry};
 use futures::stream::{self, StreamExt};
+use serde_json::json;
+use shipcat_definitions::{Diagnostic, Diagnostics, Severity};
+use shipcat_filebacked::{ManifestCache, SimpleManifest};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    str::FromStr,
+};
 
-async fn verify_manifest(svc: String, conf: &Config, reg: &Region) -> Result<Manifest> {
-    let mf = shipcat_filebacked::load_manifest(&svc, &conf, &reg)
-        .await?
-        .stub(&reg)
-        .await?;
+async fn verify_manifest(mf: Manifest, conf: &Config, reg: &Region) -> Result<Manifest> {
+    let mf = mf.stub(reg).await?;
     mf.verify(&conf, &reg)?;
+    dependencies(&mf, conf, reg).await.into_result()?;
     Ok(mf)
 }
 
+/// API versions routed to by kong apis, e.g. `v2` for `/fake-ask/v2`
+fn served_versions(apis: &[Kong]) -> BTreeSet<String> {
+    apis.iter()
+        .filter_map(|k| k.uris.as_ref())
+        .flat_map(|uris| uris.split(&[',', '/'][..]))
+        .map(str::trim)
+        .filter(|s| s.len() > 1 && s.starts_with('v') && s[1..].chars().all(|c| c.is_ascii_digit()))
+        .map(String::from)
+        .collect()
+}
+
+/// Check a dependency against the provider's manifest in the same region
+fn check_dependency(
+    dep: &Dependency,
+    provider: &SimpleManifest,
+    reg: &Region,
+    path: &str,
+    d: &mut Diagnostics,
+) {
+    if provider.external {
+        d.warn(path, format!("Dependency {} is an external service", dep.name));
+        return;
+    }
+    if !provider.enabled {
+        if provider.base.regions.contains(&reg.name) {
+            d.error(path, format!("Dependency {} is disabled", dep.name));
+        } else {
+            d.error(
+                path,
+                format!("Dependency {} is not enabled in {}", dep.name, reg.name),
+            );
+        }
+        return;
+    }
+    match dep.protocol {
+        DependencyProtocol::Http | DependencyProtocol::Grpc => {}
+        _ => return,
+    }
+    let versions = served_versions(&provider.kong_apis);
+    if !versions.is_empty() && !versions.contains(&dep.api) {
+        d.warn(
+            &format!("{}.api", path),
+            format!(
+                "Dependency {} does not serve api {} through kong (only {})",
+                dep.name,
+                dep.api,
+                versions.into_iter().collect::<Vec<_>>().join(", ")
+            ),
+        );
+    }
+}
+
+/// Validate the dependencies of a manifest against the region
+///
+/// Dependencies must be enabled in the same region, and should not be external.
+/// Versioned kong routes of the provider should serve the depended-upon api version.
+pub async fn dependencies(mf: &Manifest, conf: &Config, reg: &Region) -> Diagnostics {
+    let mut d = Diagnostics::default();
+    for (i, dep) in mf.dependencies.iter().enumerate() {
+        let path = format!("dependencies[{}]", i);
+        // missing services are reported by Dependency::verify
+        if !Path::new(".").join("services").join(&dep.name).is_dir() {
+            continue;
+        }
+        match shipcat_filebacked::load_metadata(&dep.name, conf, reg).await {
+            Ok(provider) => check_dependency(dep, &provider, reg, &path, &mut d),
+            Err(e) => d.check::<()>(&path, Err(e)),
+        }
+    }
+    d
+}
+
 /// Validate all manifests in a service directory for a region
 ///
 /// This is meant to replace `shipcat validate ..all_services`
 /// This does not check secrets.
 pub async fn regional_manifests(conf: &Config, reg: &Region) -> Result<()> {
-    let available = shipcat_filebacked::available(conf, &reg).await?;
+    let (cache, load_errs) = ManifestCache::load_partial(conf, reg).await?;
 
-    let mut buffered = stream::iter(available)
-        .map(move |mf| verify_manifest(mf.base.name, &conf, &reg))
+    let mut buffered = stream::iter(cache)
+        .map(move |mf| verify_manifest(mf, conf, reg))
         .buffer_unordered(16);
 
-    let mut errs = vec![];
+    let mut errs = load_errs.into_iter().map(Error::from).collect::<Vec<_>>();
+    let mut mfs = vec![];
     let mut used_stream_names = vec![];
     let mut used_topic_names = vec![];
     let mut used_user_names = vec![];
@@ -31,14 +113,14 @@ pub async fn regional_manifests(conf: &Config, reg: &Region) -> Result<()> {
             Err(e) => errs.push(e),
             Ok(mf) => {
                 // uniqueness validation
-                for es in mf.eventStreams {
+                for es in &mf.eventStreams {
                     if used_stream_names.contains(&es.name) {
                         bail!("{} cannot reuse eventStream names {}", mf.name, es.name);
                     }
                     used_stream_names.push(es.name.clone());
                 }
-                if let Some(kr) = mf.kafkaResources {
-                    for topic in kr.topics {
+                if let Some(kr) = &mf.kafkaResources {
+                    for topic in &kr.topics {
                         if used_topic_names.contains(&topic.name) {
                             bail!("{}, Topic name already exists: {}", mf.name, &topic.name);
                         }
@@ -51,13 +133,36 @@ pub async fn regional_manifests(conf: &Config, reg: &Region) -> Result<()> {
                         }
                         used_topic_names.push(topic.name.clone());
                     }
-                    for user in kr.users {
+                    for user in &kr.users {
                         if used_user_names.contains(&user.name) {
                             bail!("{}, Kafka User name already exists: {}", mf.name, &user.name);
                         }
                         used_user_names.push(user.name.clone());
                     }
                 }
+                mfs.push(mf);
+            }
+        }
+    }
+
+    for cycle in graph::sync_cycles(&graph::from_manifests(&mfs)) {
+        errs.push(format!("Synchronous dependency cycle between {}", cycle.join(", ")).into());
+    }
+    for (svc, diags) in kafka::diagnose(&mfs) {
+        for w in diags.warnings() {
+            warn!("{}: {}", svc, w.message);
+        }
+    }
+    if reg.kong.is_some() {
+        let services = mfs
+            .iter()
+            .map(|mf| (mf.name.clone(), mf.kongApis.clone()))
+            .collect();
+        for conflict in kong::route_conflicts(&kong::route_table(services, reg)) {
+            if conflict.is_warning() {
+                warn!("Kong route overlap: {}", conflict);
+            } else {
+                errs.push(format!("Kong route conflict: {}", conflict).into());
             }
         }
     }
@@ -105,6 +210,243 @@ pub async fn all_manifests() -> Result<()> {
     Ok(())
 }
 
+/// How to print validation findings
+pub enum ReportFormat {
+    /// Findings grouped by service and file
+    Human,
+    /// A json list of findings
+    Json,
+    /// SARIF 2.1.0 for CI annotations
+    Sarif,
+}
+
+impl FromStr for ReportFormat {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        match input {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            "sarif" => Ok(Self::Sarif),
+            _ => bail!("Report format must be human, json or sarif"),
+        }
+    }
+}
+
+/// A validation finding for a service in a region
+#[derive(Serialize, Clone, Debug)]
+pub struct Finding {
+    pub service: String,
+    pub region: String,
+    #[serde(flatten)]
+    pub diagnostic: Diagnostic,
+}
+
+async fn diagnose_manifest(svc: &str, conf: &Config, reg: &Region, secrets: bool) -> Diagnostics {
+    let loaded = match shipcat_filebacked::load_manifest(svc, conf, reg).await {
+        Ok(mf) if secrets => mf.complete(reg).await,
+        Ok(mf) => mf.stub(reg).await,
+        Err(e) => Err(e),
+    };
+    let mut diags = match loaded {
+        Ok(mf) => {
+            let mut d = mf.diagnose(conf, reg);
+            d.extend(dependencies(&mf, conf, reg).await);
+            d
+        }
+        Err(e) => {
+            let mut d = Diagnostics::default();
+            d.check::<()>("", Err(e));
+            d
+        }
+    };
+    shipcat_filebacked::attribute(svc, reg, &mut diags);
+    diags
+}
+
+/// Collect every validation finding for some services in a region
+///
+/// Load failures are reported as findings against the whole manifest.
+pub async fn findings(services: Vec<String>, conf: &Config, reg: &Region, secrets: bool) -> Vec<Finding> {
+    let mut res = vec![];
+    for svc in services {
+        debug!("validating {} for {}", svc, reg.name);
+        let diags = diagnose_manifest(&svc, conf, reg, secrets).await;
+        res.extend(diags.items.into_iter().map(|diagnostic| Finding {
+            service: svc.clone(),
+            region: reg.name.clone(),
+            diagnostic,
+        }));
+    }
+    res
+}
+
+/// Cross reference the kafka users of some services with the rest of a region
+///
+/// Needs every manifest of the region, so it is skipped when none of the services use kafka.
+/// Manifests that fail to load are left out, as `findings` reports them.
+pub async fn kafka_findings(services: &[String], conf: &Config, reg: &Region) -> Result<Vec<Finding>> {
+    let (cache, _) = ManifestCache::load_partial(conf, reg).await?;
+    let uses_kafka = services
+        .iter()
+        .filter_map(|svc| cache.get(svc))
+        .any(|mf| !mf.eventStreams.is_empty() || mf.kafkaResources.is_some());
+    if !uses_kafka {
+        return Ok(vec![]);
+    }
+    let mfs = cache.into_iter().collect::<Vec<_>>();
+    let mut res = vec![];
+    for (svc, mut d) in kafka::diagnose(&mfs) {
+        if !services.contains(&svc) {
+            continue;
+        }
+        shipcat_filebacked::attribute(&svc, reg, &mut d);
+        res.extend(d.items.into_iter().map(|diagnostic| Finding {
+            service: svc.clone(),
+            region: reg.name.clone(),
+            diagnostic,
+        }));
+    }
+    Ok(res)
+}
+
+/// Check the images of some services against the registry of a region
+///
+/// Missing tags are errors. Unpinned versions, images hosted on other registries and
+/// an `imageSize` that differs from what the registry reports are warnings.
+pub async fn image_findings(services: Vec<String>, conf: &Config, reg: &Region) -> Result<Vec<Finding>> {
+    let registry = match &reg.registry {
+        Some(r) => r,
+        None => bail!("No registry configured for {}", reg.name),
+    };
+    let mut res = vec![];
+    for svc in services {
+        let mf = shipcat_filebacked::load_manifest(&svc, conf, reg).await?;
+        let mut d = Diagnostics::default();
+        match (&mf.image, &mf.version) {
+            (Some(image), Some(_)) if registry::repository(image, &registry.url).is_none() => d.warn(
+                "image",
+                format!("{} is not hosted on {}, not looking it up", image, registry.url),
+            ),
+            (Some(image), Some(version)) => match registry::lookup(registry, image, version).await? {
+                Some(info) => {
+                    if let Some(size) = mf.imageSize {
+                        if size != info.size_mb() {
+                            d.warn(
+                                "imageSize",
+                                format!(
+                                    "imageSize {} differs from {}MB in the registry (derived on apply)",
+                                    size,
+                                    info.size_mb()
+                                ),
+                            );
+                        }
+                    }
+                }
+                None => d.error(
+                    "version",
+                    format!("Tag {} of {} not found in {}", version, image, registry.url),
+                ),
+            },
+            (_, None) => d.warn("version", "No version pinned to check against the registry"),
+            (None, _) => d.error("image", "Image should be set at this point"),
+        }
+        shipcat_filebacked::attribute(&svc, reg, &mut d);
+        res.extend(d.items.into_iter().map(|diagnostic| Finding {
+            service: svc.clone(),
+            region: reg.name.clone(),
+            diagnostic,
+        }));
+    }
+    Ok(res)
+}
+
+fn human_report(findings: &[Finding]) -> String {
+    let mut grouped = BTreeMap::<(&str, &str), BTreeMap<&str, Vec<&Diagnostic>>>::new();
+    for f in findings {
+        let file = f.diagnostic.file.as_deref().unwrap_or("");
+        grouped
+            .entry((&f.service, &f.region))
+            .or_default()
+            .entry(file)
+            .or_default()
+            .push(&f.diagnostic);
+    }
+    let mut out = String::new();
+    for ((svc, region), files) in grouped {
+        out += &format!("{} in {}:\n", svc, region);
+        for (file, diags) in files {
+            let indent = if file.is_empty() {
+                "  "
+            } else {
+                out += &format!("  {}:\n", file);
+                "    "
+            };
+            for d in diags {
+                let path = if d.path.is_empty() {
+                    "".to_string()
+                } else {
+                    format!("{}: ", d.path)
+                };
+                out += &format!("{}{}: {}{}\n", indent, d.severity, path, d.message);
+            }
+        }
+    }
+    out
+}
+
+/// Line of the top level key of a field path in a file (1-indexed)
+fn key_line(file: &str, path: &str) -> usize {
+    let key = path.split(&['.', '['][..]).next().unwrap_or_default();
+    let data = std::fs::read_to_string(file).unwrap_or_default();
+    data.lines()
+        .position(|l| l.starts_with(&format!("{}:", key)))
+        .map_or(1, |i| i + 1)
+}
+
+fn sarif_report(findings: &[Finding]) -> serde_json::Value {
+    let results = findings
+        .iter()
+        .map(|f| {
+            let d = &f.diagnostic;
+            let rule = d.path.split(&['.', '['][..]).next().unwrap_or_default();
+            let path = if d.path.is_empty() {
+                "".to_string()
+            } else {
+                format!("{}: ", d.path)
+            };
+            let mut result = json!({
+                "ruleId": if rule.is_empty() { "manifest".to_string() } else { rule.to_string() },
+                "level": d.severity.to_string(),
+                "message": { "text": format!("{} in {}: {}{}", f.service, f.region, path, d.message) },
+            });
+            if let Some(file) = &d.file {
+                result["locations"] = json!([{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": file },
+                        "region": { "startLine": key_line(file, &d.path) },
+                    }
+                }]);
+            }
+            result
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "version": "2.1.0",
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "shipcat",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/Babylonpartners/shipcat",
+                }
+            },
+            "results": results,
+        }]
+    })
+}
+
 /// Validate the manifest of a service in the services directory
 ///
 /// This will populate the manifest for all supported environments,
@@ -112,22 +454,42 @@ pub async fn all_manifests() -> Result<()> {
 /// Optionally, it will also verify that all secrets are found in the corresponding
 /// vault locations serverside (which require vault credentials).
 pub async fn manifest(services: Vec<String>, conf: &Config, reg: &Region, secrets: bool) -> Result<()> {
+    manifest_report(services, conf, reg, secrets, false, ReportFormat::Human).await
+}
+
+/// Validate the manifests of services and print every finding
+///
+/// Kafka users are cross referenced with the region, and images are optionally checked against its registry.
+/// Fails if any finding is an error, like `manifest`.
+pub async fn manifest_report(
+    services: Vec<String>,
+    conf: &Config,
+    reg: &Region,
+    secrets: bool,
+    images: bool,
+    fmt: ReportFormat,
+) -> Result<()> {
     conf.verify()?; // this should work even with a limited config!
-    for svc in services {
-        debug!("validating {} for {}", svc, reg.name);
-        let mf = if secrets {
-            shipcat_filebacked::load_manifest(&svc, conf, reg)
-                .await?
-                .complete(reg)
-                .await?
-        } else {
-            shipcat_filebacked::load_manifest(&svc, conf, reg)
-                .await?
-                .stub(reg)
-                .await?
-        };
-        mf.verify(conf, reg)?;
-        debug!("validated {} for {}", svc, reg.name);
+    let mut found = findings(services.clone(), conf, reg, secrets).await;
+    found.extend(kafka_findings(&services, conf, reg).await?);
+    if images {
+        found.extend(image_findings(services, conf, reg).await?);
+    }
+    match fmt {
+        ReportFormat::Human => {
+            if !found.is_empty() {
+                eprint!("{}", human_report(&found));
+            }
+        }
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&found)?),
+        ReportFormat::Sarif => println!("{}", serde_json::to_string_pretty(&sarif_report(&found))?),
+    }
+    let errors = found
+        .iter()
+        .filter(|f| f.diagnostic.severity == Severity::Error)
+        .count();
+    if errors > 0 {
+        bail!("Invalid shipcat data: {} validation errors", errors);
     }
     Ok(())
 }
@@ -141,9 +503,8 @@ pub async fn secret_presence_full(conf: &Config, regions: Vec<String>) -> Result
         info!("validating secrets in {}", r);
         let reg = conf.get_region(&r)?; // verifies region or region alias exists
         reg.verify_secrets_exist().await?; // verify secrets for the region
-        for svc in shipcat_filebacked::available(conf, &reg).await? {
-            let mf = shipcat_filebacked::load_manifest(&svc.base.name, conf, &reg).await?;
-            debug!("validating secrets for {} in {}", &svc.base.name, r);
+        for mf in ManifestCache::load(conf, &reg).await? {
+            debug!("validating secrets for {} in {}", mf.name, r);
             mf.verify_secrets_exist(&reg.vault).await?;
         }
     }
@@ -222,10 +583,15 @@ pub fn config(conf: Config) -> Result<()> {
 //
 // Effectively checks:
 // git diff --name-only $(git merge-base origin/master HEAD) | grep ./services/{svc}/*
-fn git_diff_changes() -> Result<Vec<String>> {
-    use regex::Regex;
+pub fn git_diff_changes() -> Result<Vec<String>> {
     let merge_base = git::merge_base()?;
     let diff_output = git::diff_filenames(&merge_base)?;
+    Ok(changed_services(&diff_output))
+}
+
+/// Services touched by a list of changed file names, like `git diff --name-only` prints
+pub fn changed_services(diff_output: &str) -> Vec<String> {
+    use regex::Regex;
     let svc_re = Regex::new(r"^services/(?P<svc>[0-9a-z\-]{1,50})/").unwrap();
     let mut res = vec![];
     for l in diff_output.lines() {
@@ -235,5 +601,69 @@ fn git_diff_changes() -> Result<Vec<String>> {
             }
         }
     }
-    Ok(res)
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{human_report, sarif_report, served_versions, Finding};
+    use shipcat_definitions::{structs::Kong, Diagnostic, Severity};
+
+    fn finding(path: &str, file: Option<&str>, severity: Severity) -> Finding {
+        Finding {
+            service: "fake-ask".into(),
+            region: "dev-uk".into(),
+            diagnostic: Diagnostic {
+                severity,
+                path: path.into(),
+                message: "broken".into(),
+                file: file.map(String::from),
+            },
+        }
+    }
+
+    #[test]
+    fn reports() {
+        let found = vec![
+            finding(
+                "resources",
+                Some("services/fake-ask/manifest.yml"),
+                Severity::Error,
+            ),
+            finding("health", Some("shipcat.conf"), Severity::Warning),
+            finding("", None, Severity::Error),
+        ];
+        assert_eq!(
+            human_report(&found),
+            "fake-ask in dev-uk:
+  error: broken
+  services/fake-ask/manifest.yml:
+    error: resources: broken
+  shipcat.conf:
+    warning: health: broken
+"
+        );
+        let sarif = sarif_report(&found);
+        let results = sarif["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0]["ruleId"], "resources");
+        assert_eq!(results[0]["level"], "error");
+        assert_eq!(results[1]["level"], "warning");
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "services/fake-ask/manifest.yml"
+        );
+        assert!(results[2]["locations"].is_null());
+    }
+
+    #[test]
+    fn kong_versions() {
+        let kong = |uris: &str| Kong {
+            uris: Some(uris.into()),
+            ..Kong::default()
+        };
+        assert!(served_versions(&[kong("/fake-ask")]).is_empty());
+        let versions = served_versions(&[kong("/fake-ask/v1,/fake-ask/v2/"), kong("/v3/version")]);
+        assert_eq!(versions.into_iter().collect::<Vec<_>>(), vec!["v1", "v2", "v3"]);
+    }
 }
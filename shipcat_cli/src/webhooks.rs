@@ -65,10 +65,10 @@ pub async fn apply_event(us: UpgradeState, info: &UpgradeInfo, reg: &Region, con
     // slack notifications:
     let (color, text) = match us {
         UpgradeState::Completed => ("good", format!("applied `{}` in `{}`", info.name, info.region)),
-        UpgradeState::Failed => (
-            "danger",
-            format!("failed to apply `{}` in `{}`", info.name, info.region),
-        ),
+        UpgradeState::Failed => ("danger", match &info.failure {
+            Some(f) => format!("failed to apply `{}` in `{}`: {}", info.name, info.region, f),
+            None => format!("failed to apply `{}` in `{}`", info.name, info.region),
+        }),
         _ => (
             "good",
             format!(
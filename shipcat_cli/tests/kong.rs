@@ -193,3 +193,40 @@ fn assert_upstream_header_transform(plugin: ApiPlugin, service: &str) {
     assert_eq!(&attr.config.add, &expected_headers);
     assert_eq!(&attr.config.replace, &expected_headers);
 }
+
+#[tokio::test]
+async fn kong_route_table() {
+    use shipcat::kong::{route_conflicts, route_table};
+    setup();
+    let (conf, reg) = Config::new(ConfigState::Base, "dev-uk").await.unwrap();
+    let mut services = vec![];
+    for mf in shipcat_filebacked::available(&conf, &reg).await.unwrap() {
+        services.push((mf.base.name, mf.kong_apis));
+    }
+    let routes = route_table(services, &reg);
+    assert_eq!(routes.len(), 2);
+    assert_eq!(routes[0].uri, Some("/ai-auth".into()));
+    assert!(route_conflicts(&routes).is_empty());
+
+    // a second service nested under the same prefix overlaps
+    let mut clash = routes[0].clone();
+    clash.service = "fake-storage".into();
+    clash.api = "fake-storage-auth".into();
+    clash.uri = Some("/ai-auth/v2".into());
+    let conflicts = route_conflicts(&[routes[0].clone(), clash]);
+    assert_eq!(conflicts.len(), 1);
+    assert!(conflicts[0].is_warning());
+    assert_eq!(
+        conflicts[0].to_string(),
+        "fake-ask route /ai-auth overlaps fake-storage-auth route /ai-auth/v2 on fake-ask.dev.something.domain.com,fake.example.com (fake-ask vs fake-storage)"
+    );
+
+    // the same service nesting an api that strips differently is an error
+    let mut nested = routes[0].clone();
+    nested.api = "fake-ask-v2".into();
+    nested.uri = Some("/ai-auth/v2".into());
+    nested.strip_uri = !routes[0].strip_uri;
+    let conflicts = route_conflicts(&[routes[0].clone(), nested]);
+    assert_eq!(conflicts.len(), 1);
+    assert!(!conflicts[0].is_warning());
+}
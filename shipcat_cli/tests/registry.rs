@@ -0,0 +1,60 @@
+use shipcat::registry::lookup;
+use shipcat_definitions::RegistryConfig;
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    thread,
+};
+
+const MANIFEST: &str = r#"{
+  "schemaVersion": 2,
+  "mediaType": "application/vnd.oci.image.manifest.v1+json",
+  "config": { "digest": "sha256:c", "size": 1000 },
+  "layers": [{ "digest": "sha256:l", "size": 2097152 }]
+}"#;
+
+/// A minimal registry serving a single tag of a single repository
+fn local_registry() -> RegistryConfig {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            reader.read_line(&mut request).unwrap();
+            // drain headers
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let res = if request.starts_with("GET /v2/babylonhealth/fake-ask/manifests/1.6.0 ") {
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/vnd.oci.image.manifest.v1+json\r\n\
+                     Docker-Content-Digest: sha256:abc\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    MANIFEST.len(),
+                    MANIFEST
+                )
+            } else {
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+            };
+            stream.write_all(res.as_bytes()).unwrap();
+        }
+    });
+    RegistryConfig {
+        url,
+        pinDigests: true,
+    }
+}
+
+#[tokio::test]
+async fn registry_lookup() {
+    let reg = local_registry();
+    let image = format!("{}/babylonhealth/fake-ask", reg.url.trim_start_matches("http://"));
+
+    let info = lookup(&reg, &image, "1.6.0").await.unwrap().unwrap();
+    assert_eq!(info.digest, "sha256:abc");
+    assert_eq!(info.size_mb(), 3);
+
+    assert!(lookup(&reg, &image, "0.0.1").await.unwrap().is_none());
+}
@@ -0,0 +1,36 @@
+mod common;
+use crate::common::setup;
+
+use shipcat::scaffold::render;
+use shipcat_definitions::{Config, ConfigState};
+use std::path::Path;
+
+#[tokio::test]
+async fn scaffold_worker() {
+    setup();
+    let (conf, reg) = Config::new(ConfigState::Base, "dev-uk").await.unwrap();
+    let regions = vec![reg];
+    let files = render("new-worker", "observability", "worker", &regions, &conf).unwrap();
+    let dir = Path::new(".").join("services").join("new-worker");
+    assert_eq!(files.len(), 2);
+
+    let mf = &files[&dir.join("manifest.yml")];
+    assert!(mf.starts_with("name: new-worker\n"));
+    assert!(mf.contains("regions:\n- dev-uk\n"));
+    // metadata comes from the squad in teams.yml
+    assert!(mf.contains("  team: observability\n"));
+    assert!(mf.contains("  support: CA04UJ8S0\n"));
+    assert!(mf.contains("babylonhealth/new-worker"));
+
+    let ov = &files[&dir.join("dev-uk.yml")];
+    assert_eq!(ov, "# new-worker in dev-uk\nversion: 0.1.0\n");
+
+    // name rules from Manifest::verify
+    assert!(render("-new-worker", "observability", "worker", &regions, &conf).is_err());
+    assert!(render("New_Worker", "observability", "worker", &regions, &conf).is_err());
+    // existing services, squads and scaffolds
+    assert!(render("fake-ask", "observability", "worker", &regions, &conf).is_err());
+    assert!(render("new-worker", "nobody", "worker", &regions, &conf).is_err());
+    assert!(render("new-worker", "observability", "nope", &regions, &conf).is_err());
+    assert!(render("new-worker", "observability", "worker", &[], &conf).is_err());
+}
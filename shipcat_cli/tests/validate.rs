@@ -13,3 +13,95 @@ async fn validate_test() {
     let res2 = validate(vec!["fake-storage".into(), "fake-ask".into()], &conf, &reg, false).await;
     assert!(res2.is_ok())
 }
+
+#[tokio::test]
+async fn validate_findings() {
+    use shipcat::validate::findings;
+    setup();
+    let (conf, reg) = Config::new(ConfigState::Base, "dev-uk").await.unwrap();
+    let found = findings(vec!["fake-ask".into(), "nonexistent".into()], &conf, &reg, false).await;
+    // fake-ask is valid, the missing service is a single error against the whole manifest
+    let errors = found
+        .iter()
+        .filter(|f| f.diagnostic.severity == shipcat_definitions::Severity::Error)
+        .collect::<Vec<_>>();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].service, "nonexistent");
+    assert_eq!(errors[0].diagnostic.path, "");
+    assert!(errors[0].diagnostic.file.is_none());
+}
+
+#[tokio::test]
+async fn validate_dependencies() {
+    use shipcat::validate::dependencies;
+    use shipcat_definitions::structs::Dependency;
+    setup();
+    let (conf, reg) = Config::new(ConfigState::Base, "dev-uk").await.unwrap();
+    let mut mf = shipcat_filebacked::load_manifest("fake-ask", &conf, &reg)
+        .await
+        .unwrap();
+    assert!(dependencies(&mf, &conf, &reg).await.items.is_empty());
+
+    mf.dependencies = ["fake-storage", "external", "out-of-region", "nonexistent"]
+        .iter()
+        .map(|name| Dependency {
+            name: name.to_string(),
+            ..Dependency::default()
+        })
+        .collect();
+    let d = dependencies(&mf, &conf, &reg).await;
+    assert_eq!(d.items.len(), 2);
+    assert_eq!(d.warnings().next().unwrap().path, "dependencies[1]");
+    let err = d.errors().next().unwrap();
+    assert_eq!(err.path, "dependencies[2]");
+    assert!(err.message.contains("not enabled in dev-uk"));
+}
+
+#[tokio::test]
+async fn validate_data_policies() {
+    setup();
+    let (conf, reg) = Config::new(ConfigState::Base, "dev-uk").await.unwrap();
+    let mut mf = shipcat_filebacked::load_manifest("fake-storage", &conf, &reg)
+        .await
+        .unwrap();
+    assert!(!mf.diagnose(&conf, &reg).has_errors());
+
+    // a cipher outside the allow-list of the store classification
+    let store = &mut mf.dataHandling.as_mut().unwrap().stores[0];
+    store.fields[1].cipher = Some("DES".into());
+    let d = mf.diagnose(&conf, &reg);
+    let errors = d.errors().collect::<Vec<_>>();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].path, "dataHandling.stores[0].fields[1].cipher");
+}
+
+#[tokio::test]
+async fn validate_struct_findings() {
+    use shipcat_definitions::structs::{HostAlias, Rbac};
+    setup();
+    let (conf, reg) = Config::new(ConfigState::Base, "dev-uk").await.unwrap();
+    let mut mf = shipcat_filebacked::load_manifest("fake-ask", &conf, &reg)
+        .await
+        .unwrap();
+    assert!(!mf.diagnose(&conf, &reg).has_errors());
+
+    // every problem of a struct is reported, not just the first
+    mf.hostAliases = vec![HostAlias {
+        ip: "300.0.0.1".into(),
+        hostnames: vec!["ok.example.com".into(), "not_ok".into()],
+    }];
+    mf.rbac = vec![Rbac {
+        apiGroups: vec![],
+        resources: vec![],
+        resourceNames: vec![],
+        verbs: vec!["get".into()],
+    }];
+    let d = mf.diagnose(&conf, &reg);
+    let paths = d.errors().map(|e| e.path.as_str()).collect::<Vec<_>>();
+    assert_eq!(paths, vec![
+        "hostAliases[0].ip",
+        "hostAliases[0].hostnames[1]",
+        "rbac[0].apiGroups",
+        "rbac[0].resources",
+    ]);
+}
@@ -1,6 +1,7 @@
 #![allow(non_snake_case)]
 
 use kube_derive::CustomResource;
+use schemars::JsonSchema;
 use semver::Version;
 use std::collections::{BTreeMap, BTreeSet};
 
@@ -9,12 +10,13 @@ use crate::teams;
 
 #[allow(unused_imports)] use super::{Error, Result};
 use crate::{
-    region::{Environment, Region},
+    region::{DiffIgnore, Environment, Region},
     states::ConfigState,
+    structs::security::DataPolicies,
 };
 
 /// Kubernetes cluster information
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct Cluster {
     /// Name of the cluster
@@ -28,7 +30,7 @@ pub struct Cluster {
     pub regions: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct Location {
     /// Location name
@@ -41,14 +43,14 @@ pub struct Location {
     pub local_region: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct GithubParameters {
     /// Organisation name
     pub organisation: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct SlackParameters {
     /// Team name (T...)
@@ -58,7 +60,7 @@ pub struct SlackParameters {
 // ----------------------------------------------------------------------------------
 
 /// Main manifest, serializable from shipcat.conf
-#[derive(CustomResource, Serialize, Deserialize, Debug, Clone)]
+#[derive(CustomResource, Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[kube(
     group = "babylontech.co.uk",
     kind = "ShipcatConfig",
@@ -71,6 +73,7 @@ pub struct Config {
     /// Global defaults for the manifests (used by shipcat_filebacked only)
     #[serde(default)]
     #[cfg(feature = "filesystem")]
+    #[schemars(with = "serde_json::Value")]
     pub defaults: serde_yaml::Value,
 
     /// Cluster definitions
@@ -104,8 +107,19 @@ pub struct Config {
     pub allowedCustomMetadata: BTreeSet<String>,
 
     /// Shipcat version pins
+    #[schemars(with = "BTreeMap<Environment, String>")]
     pub versions: BTreeMap<Environment, Version>,
 
+    /// Changes to ignore when diffing services in any region
+    #[serde(default, skip_serializing_if = "DiffIgnore::is_empty")]
+    pub diffIgnore: DiffIgnore,
+
+    /// Requirements on data stores by information classification
+    ///
+    /// Enforced on `dataHandling` stores when validating manifests.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dataPolicies: DataPolicies,
+
     /// Owners of services, squads, tribes
     ///
     /// Populated from teams.yml
@@ -170,6 +184,15 @@ impl Config {
                 }
                 used_kong_urls.push(kong.config_url.clone());
             }
+            if let Some(registry) = &r.registry {
+                registry.verify(&r.name)?;
+            }
+            for p in r.dataPolicies.values() {
+                p.verify()?;
+            }
+        }
+        for p in self.dataPolicies.values() {
+            p.verify()?;
         }
         Ok(())
     }
@@ -325,6 +348,25 @@ impl Config {
         )
     }
 
+    /// JSON Schema for `shipcat.conf`
+    pub fn schema() -> schemars::schema::RootSchema {
+        schemars::schema_for!(Config)
+    }
+
+    /// Diff ignore rules for a region, including the config level ones
+    pub fn diff_ignore(&self, region: &Region) -> DiffIgnore {
+        self.diffIgnore.clone().merge(&region.diffIgnore)
+    }
+
+    /// Data policies for a region
+    ///
+    /// Region level policies replace config level ones for the same classification.
+    pub fn data_policies(&self, region: &Region) -> DataPolicies {
+        let mut policies = self.dataPolicies.clone();
+        policies.extend(region.dataPolicies.clone());
+        policies
+    }
+
     /// Region exposer (needed in a few special cases, raftcat, crd reconcile)
     pub fn get_regions(&self) -> Vec<Region> {
         self.regions.clone()
@@ -401,7 +443,12 @@ impl Config {
     ///
     /// Pass this a region request via argument or a current context
     pub async fn new(state: ConfigState, context: &str) -> Result<(Config, Region)> {
-        let mut conf = Self::read().await?;
+        Self::read().await?.for_context(state, context).await
+    }
+
+    /// Resolve a context in a config read from `shipcat.conf` and filter it like `new`
+    pub async fn for_context(self, state: ConfigState, context: &str) -> Result<(Config, Region)> {
+        let mut conf = self;
         let region = if let Some(r) = conf.resolve_context(context.to_string()) {
             r
         } else {
@@ -438,6 +485,15 @@ impl Config {
         Ok(res)
     }
 
+    /// Parse a config from the contents of `shipcat.conf` and `teams.yml`
+    ///
+    /// For configs that are not in pwd, like ones at another git revision.
+    pub fn parse(conf: &str, teams: &str) -> Result<Config> {
+        let mut res: Config = serde_yaml::from_str(conf)?;
+        res.owners = serde_yaml::from_str(teams)?;
+        Ok(res)
+    }
+
     /// Read a config in pwd and leave placeholders
     pub async fn read() -> Result<Config> {
         let pwd = Path::new(".");
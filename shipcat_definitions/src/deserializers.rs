@@ -1,7 +1,8 @@
+use schemars::JsonSchema;
 use serde::de::{value::SeqAccessDeserializer, Deserialize, Deserializer, Error, SeqAccess, Visitor};
 use std::{fmt, marker::PhantomData};
 
-#[derive(Deserialize, Clone, Default)]
+#[derive(Deserialize, Clone, Default, JsonSchema)]
 pub struct CommaSeparatedString(#[serde(deserialize_with = "comma_separated_string")] Vec<String>);
 
 impl Into<Vec<String>> for CommaSeparatedString {
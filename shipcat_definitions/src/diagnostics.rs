@@ -0,0 +1,132 @@
+use super::Result;
+use std::fmt;
+
+/// How serious a validation finding is
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Fails validation
+    Error,
+    /// Reported, but does not fail validation
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A single validation finding
+#[derive(Serialize, Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Path of the manifest field the finding is about
+    ///
+    /// E.g. `resources` or `dependencies[1]`. Empty when it's about the manifest as a whole.
+    pub path: String,
+    pub message: String,
+    /// File that contributed the field (if known)
+    ///
+    /// Filled in by the manifest loader, relative to the manifests repository.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+/// Accumulated validation findings
+///
+/// Verification functions push into this rather than bailing on the first problem,
+/// so every problem can be reported at once.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Diagnostics {
+    pub items: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    fn push(&mut self, severity: Severity, path: &str, message: String) {
+        self.items.push(Diagnostic {
+            severity,
+            path: path.to_string(),
+            message,
+            file: None,
+        });
+    }
+
+    /// Record a failing finding
+    pub fn error<S: Into<String>>(&mut self, path: &str, message: S) {
+        self.push(Severity::Error, path, message.into())
+    }
+
+    /// Record a non-failing finding
+    pub fn warn<S: Into<String>>(&mut self, path: &str, message: S) {
+        self.push(Severity::Warning, path, message.into())
+    }
+
+    /// Record the error of a bailing verify function (if any)
+    pub fn check<T>(&mut self, path: &str, res: Result<T>) {
+        if let Err(e) = res {
+            let msg = e.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(": ");
+            self.error(path, msg)
+        }
+    }
+
+    /// Append the findings from a nested verification
+    pub fn extend(&mut self, other: Diagnostics) {
+        self.items.extend(other.items)
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.items.iter().filter(|d| d.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.items.iter().filter(|d| d.severity == Severity::Warning)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// Convert to the old bailing interface
+    ///
+    /// Warnings are logged, and all errors are joined into one.
+    pub fn into_result(self) -> Result<()> {
+        for w in self.warnings() {
+            warn!("{}", w.message);
+        }
+        let errs = self.errors().map(|d| d.message.clone()).collect::<Vec<_>>();
+        if !errs.is_empty() {
+            bail!("{}", errs.join("\n"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostics, Severity};
+
+    #[test]
+    fn accumulate() {
+        let mut d = Diagnostics::default();
+        d.check::<()>("resources", Ok(()));
+        assert!(d.items.is_empty());
+        assert!(d.clone().into_result().is_ok());
+
+        d.warn("health", "no health check");
+        assert!(!d.has_errors());
+        assert!(d.clone().into_result().is_ok());
+
+        let failed: super::Result<()> = Err("bad resources".into());
+        d.check("resources", failed);
+        d.error("name", "bad name");
+        assert_eq!(d.errors().count(), 2);
+        assert_eq!(d.items[1].severity, Severity::Error);
+        assert_eq!(d.items[1].path, "resources");
+        let err = d.into_result().unwrap_err();
+        assert_eq!(err.to_string(), "bad resources\nbad name");
+    }
+}
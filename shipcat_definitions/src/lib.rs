@@ -77,7 +77,9 @@ error_chain! {
 
 /// Config with regional data
 pub mod region;
-pub use crate::region::{Environment, KongConfig, ReconciliationMode, Region, VaultConfig, VersionScheme};
+pub use crate::region::{
+    DiffIgnore, Environment, KongConfig, ReconciliationMode, Region, RegistryConfig, VaultConfig, VersionScheme,
+};
 /// Master config with cross-region data
 pub mod config;
 pub use crate::config::{Cluster, Config, ConfigFallback, ShipcatConfig};
@@ -109,6 +111,10 @@ pub use crate::states::{ConfigState, PrimaryWorkload};
 /// Computational helpers
 pub mod math;
 
+/// Accumulated validation findings
+pub mod diagnostics;
+pub use crate::diagnostics::{Diagnostic, Diagnostics, Severity};
+
 /// A renderer of `tera` templates (jinja style)
 ///
 /// Used for small app configs that are inlined in the completed manifests.
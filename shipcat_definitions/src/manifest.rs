@@ -1,6 +1,7 @@
 use crate::vault::Vault;
 use kube_derive::CustomResource;
 use regex::Regex;
+use schemars::JsonSchema;
 use std::collections::{BTreeMap, BTreeSet};
 
 use super::Result;
@@ -8,7 +9,7 @@ use crate::{
     config::Config,
     region::{Region, VaultConfig},
     states::{ManifestState, PrimaryWorkload},
-    ManifestStatus,
+    Diagnostics, ManifestStatus,
 };
 
 // All structs come from the structs directory
@@ -25,7 +26,7 @@ use super::structs::{
 };
 
 /// Main manifest, serializable from manifest.yml or the shipcat CRD.
-#[derive(CustomResource, Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(CustomResource, Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 #[kube(
     group = "babylontech.co.uk",
     kind = "ShipcatManifest",
@@ -63,6 +64,7 @@ pub struct Manifest {
     /// Whether the service should be public
     ///
     /// This is a special flag not exposed to the charts at the moment.
+    /// Defaults to `gate.public` when a gate is configured, see `shipcat migrate gate-public`.
     ///
     /// ```yaml
     /// publiclyAccessible: true
@@ -493,6 +495,16 @@ pub struct Manifest {
     ///   maxUnavailable: 0%
     ///   maxSurge: 50%
     /// ```
+    ///
+    /// A `Statefulset` instead takes a partition, which `shipcat apply` steps down one ordinal at a time,
+    /// pausing between ordinals for a confirmation (`Manual`) or until the new pods stay ready (`Auto`):
+    ///
+    /// ```yaml
+    /// rollingUpdate:
+    ///   partition: 3
+    ///   stepGate: Auto
+    ///   stepWait: 60
+    /// ```
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub rollingUpdate: Option<RollingUpdate>,
 
@@ -850,6 +862,19 @@ pub struct Manifest {
     )]
     pub uid: Option<String>,
 
+    /// Digest of the image version, resolved on apply
+    ///
+    /// Only set in regions whose registry pins digests. Charts should prefer
+    /// `image@imageDigest` over `image:version` when it is set.
+    ///
+    /// Exposed from shipcat, but not overrideable.
+    #[serde(default)]
+    #[cfg_attr(
+        feature = "filesystem",
+        serde(skip_deserializing, skip_serializing_if = "Option::is_none")
+    )]
+    pub imageDigest: Option<String>,
+
     /// Raw secrets from environment variables.
     ///
     /// The `env` map fills in secrets in this via the `vault` client.
@@ -893,6 +918,13 @@ pub struct Manifest {
 }
 
 impl Manifest {
+    /// JSON Schema for the completed manifest
+    ///
+    /// Carries the field documentation that the manifest sources do not.
+    pub fn schema() -> schemars::schema::RootSchema {
+        schemars::schema_for!(Manifest)
+    }
+
     /// Set the version field
     pub fn version(mut self, version: String) -> Self {
         self.version = Some(version);
@@ -933,134 +965,165 @@ impl Manifest {
         Ok(())
     }
 
-    /// Verify assumptions about manifest
-    ///
-    /// Assumes the manifest has been populated with `implicits`
-    pub fn verify(&self, conf: &Config, region: &Region) -> Result<()> {
-        self.verify_region()?;
+    /// Problems with a service name, empty if it is valid
+    pub fn name_errors(name: &str) -> Vec<&'static str> {
+        let mut errs = vec![];
         // limit to 50 characters, alphanumeric, dashes for sanity.
         // 63 is kube dns limit (13 char suffix buffer)
         let re = Regex::new(r"^[0-9a-z\-]{1,50}$").unwrap();
-        if !re.is_match(&self.name) {
-            bail!("Please use a short, lower case service names with dashes");
+        if !re.is_match(name) {
+            errs.push("Please use a short, lower case service names with dashes");
         }
-        if self.name.ends_with('-') || self.name.starts_with('-') {
-            bail!("Please use dashes to separate words only");
+        if name.ends_with('-') || name.starts_with('-') {
+            errs.push("Please use dashes to separate words only");
+        }
+        errs
+    }
+
+    /// Verify assumptions about manifest
+    ///
+    /// Assumes the manifest has been populated with `implicits`
+    pub fn verify(&self, conf: &Config, region: &Region) -> Result<()> {
+        self.diagnose(conf, region).into_result()
+    }
+
+    /// Verify assumptions about manifest, recording every problem found
+    ///
+    /// Same checks as `verify`, but does not stop at the first failure.
+    /// Findings are keyed by the path of the offending manifest field.
+    pub fn diagnose(&self, conf: &Config, region: &Region) -> Diagnostics {
+        let mut d = Diagnostics::default();
+        d.check("regions", self.verify_region());
+        for e in Manifest::name_errors(&self.name) {
+            d.error("name", e);
         }
 
-        self.verify_destination_rules(region)?;
+        d.check("destinationRules", self.verify_destination_rules(region));
 
         // TODO: remove?
         if let Some(ref dh) = self.dataHandling {
-            dh.verify()?
+            d.check("dataHandling", dh.verify());
+            dh.diagnose(&conf.data_policies(region), "dataHandling", &mut d);
         }
 
         if let Some(ref md) = self.metadata {
-            md.verify(&conf.owners, &conf.allowedCustomMetadata)?;
+            md.diagnose(&conf.owners, &conf.allowedCustomMetadata, &mut d);
         } else {
-            bail!("Missing metadata for {}", self.name);
+            d.error("metadata", format!("Missing metadata for {}", self.name));
         }
 
+        // kube-external services skip most validation
         if self.external {
-            warn!("Ignoring most validation for kube-external service {}", self.name);
-            return Ok(());
+            return d;
         }
 
         if let Some(v) = &self.version {
-            region.versioningScheme.verify(v)?;
+            d.check("version", region.versioningScheme.verify(v));
         }
 
         // TODO [DIP-499]: Separate gate/kong params + adjust the checks
         if let Some(g) = &self.gate {
             if self.kongApis.is_empty() {
-                bail!("Can't have a `gate` configuration without a `kong` one");
+                d.error("gate", "Can't have a `gate` configuration without a `kong` one");
             }
             if g.public != self.publiclyAccessible {
-                bail!("[Migration plan] `publiclyAccessible` and `gate.public` must be equal");
+                d.error(
+                    "gate.public",
+                    "[Migration plan] `publiclyAccessible` and `gate.public` must be equal",
+                );
             }
         }
 
         // run the `Verify` trait on all imported structs
         // mandatory structs first
         if let Some(ref r) = self.resources {
-            r.verify()?;
+            r.diagnose("resources", &mut d);
         } else {
-            bail!("Resources is mandatory");
+            d.error("resources", "Resources is mandatory");
         }
 
         // optional/vectorised entries
-        for d in &self.dependencies {
-            d.verify()?;
+        for (i, dep) in self.dependencies.iter().enumerate() {
+            dep.diagnose(&format!("dependencies[{}]", i), &mut d);
         }
 
-        for ha in &self.hostAliases {
-            ha.verify()?;
+        for (i, ha) in self.hostAliases.iter().enumerate() {
+            ha.diagnose(&format!("hostAliases[{}]", i), &mut d);
         }
-        for tl in &self.tolerations {
-            tl.verify()?;
+        for (i, tl) in self.tolerations.iter().enumerate() {
+            tl.diagnose(&format!("tolerations[{}]", i), &mut d);
         }
-        for r in &self.rbac {
-            r.verify()?;
+        for (i, r) in self.rbac.iter().enumerate() {
+            r.diagnose(&format!("rbac[{}]", i), &mut d);
         }
-        for pv in &self.persistentVolumes {
-            pv.verify()?;
+        for (i, pv) in self.persistentVolumes.iter().enumerate() {
+            pv.diagnose(&format!("persistentVolumes[{}]", i), &mut d);
         }
         if let Some(ref cmap) = self.configs {
-            cmap.verify()?;
+            cmap.diagnose("configs", &mut d);
         }
         for k in self.labels.keys() {
             if !conf.allowedLabels.contains(k) {
-                bail!("Service: {} using label {} not defined in config", self.name, k)
+                d.error(
+                    &format!("labels.{}", k),
+                    format!("Service: {} using label {} not defined in config", self.name, k),
+                );
             }
         }
-        for es in &self.eventStreams {
-            es.verify()?;
+        for (i, es) in self.eventStreams.iter().enumerate() {
+            es.diagnose(&format!("eventStreams[{}]", i), &mut d);
         }
         if let Some(kr) = &self.kafkaResources {
-            kr.verify()?;
+            kr.diagnose("kafkaResources", &mut d);
         }
-        for pa in &self.prometheusAlerts {
-            pa.verify(&self.name)?;
+        for (i, cj) in self.cronJobs.iter().enumerate() {
+            cj.diagnose(&format!("cronJobs[{}]", i), &mut d);
+        }
+        for (i, pa) in self.prometheusAlerts.iter().enumerate() {
+            pa.diagnose(&self.name, &format!("prometheusAlerts[{}]", i), &mut d);
         }
         // misc minor properties
         if self.replicaCount.unwrap() == 0 {
-            bail!("Need replicaCount to be at least 1");
+            d.error("replicaCount", "Need replicaCount to be at least 1");
         }
         if let Some(ref ru) = &self.rollingUpdate {
-            ru.verify(self.replicaCount.unwrap())?;
+            d.check(
+                "rollingUpdate",
+                ru.verify(self.replicaCount.unwrap(), &self.workload),
+            );
         }
 
-        self.env.verify()?;
+        self.env.diagnose("env", &mut d);
 
         // internal errors - implicits set these!
         if self.image.is_none() {
-            bail!("Image should be set at this point")
+            d.error("image", "Image should be set at this point")
         }
         if self.imageSize.is_none() {
-            bail!("imageSize must be set at this point");
+            d.error("imageSize", "imageSize must be set at this point");
         }
         if self.chart.is_none() {
-            bail!("chart must be set at this point");
+            d.error("chart", "chart must be set at this point");
         }
         if self.namespace == "" {
-            bail!("namespace must be set at this point");
+            d.error("namespace", "namespace must be set at this point");
         }
         if self.regions.is_empty() {
-            bail!("No regions specified for {}", self.name);
+            d.error("regions", format!("No regions specified for {}", self.name));
         }
         if self.environment == "" {
-            bail!("Service {} ended up with an empty environment", self.name);
-        }
-        if self.namespace == "" {
-            bail!("Service {} ended up with an empty namespace", self.name);
+            d.error(
+                "environment",
+                format!("Service {} ended up with an empty environment", self.name),
+            );
         }
 
         // health check
         if self.health.is_none() && self.readinessProbe.is_none() {
-            warn!("{} does not set a health check", self.name)
+            d.warn("health", format!("{} does not set a health check", self.name))
         }
 
-        Ok(())
+        d
     }
 
     fn get_vault_path(&self, vc: &VaultConfig) -> String {
@@ -1156,26 +1219,26 @@ impl Manifest {
         secrets
     }
 
-    pub async fn verify_secrets_exist(&self, vc: &VaultConfig) -> Result<()> {
-        use std::collections::HashSet;
-        // what are we requesting
+    /// Env vars and secret files that must exist in vault
+    pub fn vault_keys(&self) -> BTreeSet<String> {
         // TODO: Use envvars directly
-        let keys = self
+        let env = self
             .env
             .plain
-            .clone()
-            .into_iter()
-            .filter(|(_, v)| v == "IN_VAULT")
-            .map(|(k, _)| k)
-            .collect::<HashSet<_>>();
+            .iter()
+            .filter(|(_, v)| *v == "IN_VAULT")
+            .map(|(k, _)| k.clone());
         let files = self
             .secretFiles
-            .clone()
-            .into_iter()
-            .filter(|(_, v)| v == "IN_VAULT")
-            .map(|(k, _)| k)
-            .collect::<HashSet<_>>();
-        let expected = keys.union(&files).cloned().collect::<HashSet<_>>();
+            .iter()
+            .filter(|(_, v)| *v == "IN_VAULT")
+            .map(|(k, _)| k.clone());
+        env.chain(files).collect()
+    }
+
+    pub async fn verify_secrets_exist(&self, vc: &VaultConfig) -> Result<()> {
+        // what are we requesting
+        let expected = self.vault_keys();
         if expected.is_empty() {
             return Ok(()); // no point trying to cross reference
         }
@@ -1186,7 +1249,7 @@ impl Manifest {
 
         // list secrets; fail immediately if folder is empty
         let found = match v.list(&secpth).await {
-            Ok(lst) => lst.into_iter().collect::<HashSet<_>>(),
+            Ok(lst) => lst.into_iter().collect::<BTreeSet<_>>(),
             Err(e) => bail!(
                 "Missing secret folder {} expected to contain {:?}: {}",
                 secpth,
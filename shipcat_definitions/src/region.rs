@@ -1,4 +1,5 @@
 use crate::structs::kong::Kong;
+use schemars::JsonSchema;
 use std::{collections::BTreeMap, env};
 
 use regex::Regex;
@@ -10,13 +11,13 @@ use uuid::Uuid;
 
 #[allow(unused_imports)] use super::{BaseManifest, ConfigState, Result, Vault};
 
-use super::structs::Authorization;
+use super::structs::{security::DataPolicies, Authorization};
 
 /// Versioning Scheme used in region
 ///
 /// This is valdiated strictly using `shipcat validate` when versions are found in manifests.
 /// Otherwise, it's validated on upgrade time (via `shipcat apply`) when it's passed.
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub enum VersionScheme {
     /// Version must be valid semver (no leading v)
     ///
@@ -58,7 +59,7 @@ impl VersionScheme {
 }
 
 /// Vault configuration for a region
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(test, derive(Default))]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct VaultConfig {
@@ -114,7 +115,7 @@ impl VaultConfig {
 //}
 
 /// Kafka configuration for a region
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct KafkaConfig {
     /// Broker urls in "hostname:port" format.
@@ -140,7 +141,7 @@ pub struct KafkaConfig {
 }
 
 /// Webhook types that shipcat might trigger after actions
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[serde(tag = "name", deny_unknown_fields, rename_all = "snake_case")]
 pub enum Webhook {
     /// Audit webhook details
@@ -148,7 +149,7 @@ pub enum Webhook {
 }
 
 /// Where / how to send audited events
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct AuditWebhook {
     /// Endpoint
@@ -158,7 +159,7 @@ pub struct AuditWebhook {
 }
 
 /// Configure how CRs will be deployed on a region
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct CRSettings {
     #[serde(rename = "config")]
@@ -168,7 +169,7 @@ pub struct CRSettings {
 // ----------------------------------------------------------------------------------
 
 /// Kong configuration for a region
-#[derive(Serialize, Deserialize, Clone, Debug, Default)] // TODO: better Default impl
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)] // TODO: better Default impl
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct KongConfig {
     /// Base URL to use (e.g. uk.dev.babylontech.co.uk)
@@ -188,7 +189,7 @@ pub struct KongConfig {
 }
 
 /// StatusCake configuration for a region
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct StatuscakeConfig {
     /// Contact Group that will be used if tests go down
@@ -199,8 +200,46 @@ pub struct StatuscakeConfig {
     pub extra_tags: Option<String>,
 }
 
+/// Container registry for a region
+///
+/// Any registry implementing the OCI Distribution API.
+/// Only images hosted on this registry (or without a registry host) are looked up.
+///
+/// Authentication is limited to a static bearer token read from `REGISTRY_TOKEN`.
+/// The token challenge flow (`WWW-Authenticate` against a token service) is not supported,
+/// so registries that require it need a pre-issued token.
+///
+/// ```yaml
+/// registry:
+///   url: https://quay.io
+///   pinDigests: true
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+#[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
+pub struct RegistryConfig {
+    /// Base url of the registry (without the `/v2` suffix)
+    pub url: String,
+    /// Resolve version tags to immutable digests on apply (and when diffing against the cluster)
+    ///
+    /// The digest is injected as `imageDigest` and recorded in the shipcatmanifest status.
+    #[serde(default)]
+    pub pinDigests: bool,
+}
+
+impl RegistryConfig {
+    pub fn verify(&self, region: &str) -> Result<()> {
+        if Url::parse(&self.url).is_err() {
+            bail!("Registry url for {} must be a valid url", region);
+        }
+        if self.url.ends_with('/') {
+            bail!("Registry url for {} must not end with a slash", region);
+        }
+        Ok(())
+    }
+}
+
 /// Logz.io configuration for a region
-#[derive(Serialize, Deserialize, Clone, Debug, Default)] // TODO: better Default impl
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)] // TODO: better Default impl
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct LogzIoConfig {
     /// Base URL to use (e.g. https://app-eu.logz.io/#/dashboard/kibana/dashboard)
@@ -210,7 +249,7 @@ pub struct LogzIoConfig {
 }
 
 /// Grafana details for a region
-#[derive(Serialize, Deserialize, Clone, Debug, Default)] // TODO: better Default impl
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)] // TODO: better Default impl
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct GrafanaConfig {
     /// Base URL to use (e.g. https://dev-grafana.ops.babylontech.co.uk)
@@ -220,20 +259,20 @@ pub struct GrafanaConfig {
 }
 
 /// Sentry details for a region
-#[derive(Serialize, Deserialize, Clone, Debug, Default)] // TODO: better Default impl
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)] // TODO: better Default impl
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct SentryConfig {
     /// Base URL to use (e.g. https://dev-uk-sentry.ops.babylontech.co.uk)
     pub url: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct KongAnonymousConsumers {
     pub anonymous: BTreeMap<String, String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct KongOauthConsumer {
     pub oauth_client_id: String,
@@ -241,14 +280,14 @@ pub struct KongOauthConsumer {
     pub username: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct KongJwtConsumer {
     pub kid: String,
     pub public_key: String,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct KongTcpLogConfig {
     pub enabled: bool,
@@ -264,14 +303,14 @@ impl KongConfig {
 
 /// Defaults for services in this region
 // TODO: This should be ManifestDefaults from shipcat_filebacked
-#[derive(Deserialize, Clone, Debug, Default)]
+#[derive(Deserialize, Clone, Debug, Default, JsonSchema)]
 #[serde(default)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct DefaultConfig {
     pub kong: DefaultKongConfig,
 }
 
-#[derive(Deserialize, Clone, Debug, Default)]
+#[derive(Deserialize, Clone, Debug, Default, JsonSchema)]
 #[serde(default)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct DefaultKongConfig {
@@ -396,8 +435,54 @@ mod test_webhooks {
 
 // ----------------------------------------------------------------------------------
 
+/// Rules for changes that should not count as a diff
+///
+/// Applied to `kubectl diff` output before deciding whether to upgrade,
+/// and before showing diffs to users or slack.
+/// Can be set on the config level and on the region level; both sets of rules apply.
+///
+/// ```yaml
+/// diffIgnore:
+///   kinds: [HorizontalPodAutoscaler]
+///   paths: ["spec.template.metadata.annotations.checksum/*"]
+///   labels: [app.kubernetes.io/version]
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
+pub struct DiffIgnore {
+    /// Kubernetes kinds whose changes are ignored entirely
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub kinds: Vec<String>,
+    /// Dotted paths into kubernetes objects whose changes are ignored
+    ///
+    /// Paths are matched in full from the top of the object, with list entries skipped.
+    /// A `*` matches any characters, so `spec.template.metadata.annotations.checksum/*`
+    /// ignores all checksum annotations on the pod template.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    /// Label keys whose changes are ignored wherever labels are set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+}
+
+impl DiffIgnore {
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty() && self.paths.is_empty() && self.labels.is_empty()
+    }
+
+    /// Combine with another set of rules
+    pub fn merge(mut self, other: &DiffIgnore) -> Self {
+        self.kinds.extend(other.kinds.iter().cloned());
+        self.paths.extend(other.paths.iter().cloned());
+        self.labels.extend(other.labels.iter().cloned());
+        self
+    }
+}
+
+// ----------------------------------------------------------------------------------
+
 /// Environments are well defined strings
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Environment {
     /// Production environment
@@ -432,7 +517,7 @@ impl ToString for Environment {
 // ----------------------------------------------------------------------------------
 
 /// Environments are well defined strings
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub enum ReconciliationMode {
     /// Shipcat owned, CRD based decision
     ///
@@ -453,7 +538,7 @@ impl Default for ReconciliationMode {
 ///
 /// Either it's a pure kubernetes context with a namespace and a cluster,
 /// or it's an abstract concept with many associated real kubernetes contexts.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(test, derive(Default))]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct Region {
@@ -492,6 +577,9 @@ pub struct Region {
     /// Statuscake configuration for the region
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub statuscake: Option<StatuscakeConfig>,
+    /// Container registry for the region
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<RegistryConfig>,
     /// List of Whitelisted IPs
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub ip_whitelist: Vec<String>,
@@ -516,10 +604,14 @@ pub struct Region {
     pub customResources: Option<CRSettings>,
 
     /// Old default values for services
+    ///
+    /// Rejected when loading manifests, `shipcat migrate region-defaults` moves it into `defaultsV2`.
     // TODO: Remove after everything has been migrated to `defaultsV2`
     #[serde(skip_serializing, default)]
     pub defaults: Option<DefaultConfig>,
     /// Old default environment variables to inject
+    ///
+    /// Rejected when loading manifests, `shipcat migrate region-defaults` moves it into `defaultsV2`.
     // TODO: Remove after everything has been migrated to `defaultsV2`
     #[serde(default, skip_serializing)]
     pub env: Option<BTreeMap<String, String>>,
@@ -527,11 +619,26 @@ pub struct Region {
     // TODO: Rename to `defaults` after removing legacy field
     #[serde(skip_serializing, default)]
     #[cfg(feature = "filesystem")]
+    #[schemars(with = "Option<serde_json::Value>")]
     pub defaultsV2: Option<serde_yaml::Value>,
 
     /// The regular expression used to verify destination rules' regions
     #[serde(default, skip_serializing_if = "Option::is_none", with = "serde_regex")]
+    // skip_serializing keeps schemars from rendering the (always empty) default via serde_regex
+    #[schemars(with = "Option<String>", skip_serializing)]
     pub destinationRuleHostRegex: Option<Regex>,
+
+    /// Changes to ignore when diffing services in this region
+    ///
+    /// These are combined with the config level `diffIgnore`.
+    #[serde(default, skip_serializing_if = "DiffIgnore::is_empty")]
+    pub diffIgnore: DiffIgnore,
+
+    /// Requirements on data stores by information classification in this region
+    ///
+    /// These replace the config level `dataPolicies` for the same classification.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dataPolicies: DataPolicies,
 }
 
 impl Region {
@@ -566,6 +673,20 @@ impl Region {
         )
     }
 
+    // Get the Vault URL for a single secret of a service in this region
+    pub fn vault_secret_url(&self, app: &str, key: &str) -> String {
+        let vault_url = self.vault.url.clone();
+        let path = "/ui/vault/secrets/secret/show/";
+        format!(
+            "{vault_url}/{path}/{env}/{app}/{key}",
+            vault_url = vault_url.trim_matches('/'),
+            path = path.trim_matches('/'),
+            env = &self.name,
+            app = &app,
+            key = &key
+        )
+    }
+
     pub fn grafana_url(&self, app: &str) -> Option<String> {
         self.grafana.clone().map(|gf| {
             format!("{grafana_url}/d/{dashboard_id}/kubernetes-services?var-cluster={cluster}&var-namespace={namespace}&var-deployment={app}",
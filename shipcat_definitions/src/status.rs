@@ -1,5 +1,7 @@
 use super::Result;
 use chrono::{SecondsFormat, Utc};
+use schemars::JsonSchema;
+use std::collections::BTreeMap;
 
 pub fn make_date() -> String {
     // Format == `1996-12-19T16:39:57-08:00`, but we hardcode Utc herein.
@@ -9,7 +11,7 @@ pub fn make_date() -> String {
 /// Status object for shipcatmanifests crd
 ///
 /// All fields optional, but we try to ensure all fields exist.
-#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ManifestStatus {
     /// Detailed individual conditions, emitted as they happen during apply
@@ -18,12 +20,23 @@ pub struct ManifestStatus {
     /// A more easily readable summary of why the conditions are what they are
     #[serde(default)]
     pub summary: Option<ConditionSummary>,
+    /// Rollout state of each workload, keyed by `Kind/name`
+    ///
+    /// Covers the primary workload, every worker deployment, and every cronjob.
+    /// A map rather than a list so that merge patches can update individual entries.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub workloads: BTreeMap<String, Condition>,
+    /// Digest the version tag resolved to at the last apply
+    ///
+    /// Only set in regions whose registry pins digests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub imageDigest: Option<String>,
     /* TODO: vault secret hash
      * MAYBE: kong status?
      * MAYBE: canary status? */
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Conditions {
     /// Generated
@@ -50,12 +63,13 @@ pub struct Conditions {
     /// If rollout.status is false, this might contain information about:
     /// - deployment(s) failing to roll out in time
     /// - network errors tracking the rollout
+    /// When kubernetes events explain a timeout, reason is the classified cause (e.g. `OOMKilled`).
     /// Best effort information given in message, but this won't replace DeploymentConditions
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub rolledout: Option<Condition>,
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ConditionSummary {
     /// Date string (RFC3339) of when we generated the template successfully
@@ -115,7 +129,7 @@ pub struct ConditionSummary {
 /// See https://github.com/kubernetes/kubernetes/issues/7856#issuecomment-323196033
 /// and https://github.com/clux/kube-rs/issues/43
 /// For the reasoning.
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
 pub struct Condition {
     /// Whether or not in a good state
     ///
@@ -229,7 +243,7 @@ mod tests {
 }
 
 
-#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
 pub struct Applier {
     /// Human readable text describing what applied
     pub name: String,
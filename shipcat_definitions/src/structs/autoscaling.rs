@@ -4,9 +4,10 @@
 
 use super::Result;
 use k8s_openapi::api::autoscaling::v2beta2::MetricSpec;
+use schemars::JsonSchema;
 
 /// Configuration parameters for HorizontalPodAutoScaler
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct AutoScaling {
     pub minReplicas: u32,
     pub maxReplicas: u32,
@@ -21,6 +22,7 @@ pub struct AutoScaling {
     /// If not set, the default metric will be set to 80% average CPU utilization.
     ///
     /// The maximum replica count across all metrics will be used.
+    #[schemars(with = "Vec<serde_json::Value>")]
     pub metrics: Vec<MetricSpec>,
 }
 
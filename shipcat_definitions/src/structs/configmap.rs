@@ -1,4 +1,5 @@
-use super::Result;
+use crate::Diagnostics;
+use schemars::JsonSchema;
 
 /// ConfigMap
 ///
@@ -6,7 +7,7 @@ use super::Result;
 /// Deals with automatic mounting into the pods.
 ///
 /// Only one of these is supported.
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct ConfigMap {
     /// Container-local directory path where configs are available
@@ -18,7 +19,7 @@ pub struct ConfigMap {
 /// ConfigMapped File
 ///
 /// Files that are mounted under the parent `mount` path.
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct ConfigMappedFile {
     /// Name of file to template (from service repo paths)
@@ -34,24 +35,34 @@ pub struct ConfigMappedFile {
 
 
 impl ConfigMap {
-    pub fn verify(&self) -> Result<()> {
+    /// Record every problem with the config map under `path`
+    pub fn diagnose(&self, path: &str, d: &mut Diagnostics) {
+        let mount_path = format!("{}.mount", path);
         // mount paths can't be empty string
         if self.mount == "" || self.mount.starts_with('~') {
-            bail!("Invalid mountpath '{}'", self.mount)
+            d.error(&mount_path, format!("Invalid mountpath '{}'", self.mount));
         }
         // and must end in a slash to have a standard
         if !self.mount.ends_with('/') {
-            bail!("Mount path '{}' must end with a slash", self.mount);
+            d.error(
+                &mount_path,
+                format!("Mount path '{}' must end with a slash", self.mount),
+            );
         }
-        for f in &self.files {
+        for (i, f) in self.files.iter().enumerate() {
             if !f.name.ends_with(".j2") {
-                bail!("Only supporting templated config files atm")
+                d.error(
+                    &format!("{}.files[{}].name", path, i),
+                    "Only supporting templated config files atm",
+                );
             }
             if f.dest == "" {
-                bail!("Empty mount destination for {}", f.name);
+                d.error(
+                    &format!("{}.files[{}].dest", path, i),
+                    format!("Empty mount destination for {}", f.name),
+                );
             }
         }
         // TODO: verify file exists? done later anyway
-        Ok(())
     }
 }
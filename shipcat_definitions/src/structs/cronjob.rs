@@ -1,7 +1,10 @@
-use super::Container;
-use std::collections::BTreeMap;
+use super::{Container, Result};
+use crate::Diagnostics;
+use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike, Utc};
+use schemars::JsonSchema;
+use std::collections::{BTreeMap, BTreeSet};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct JobVolumeClaim {
     /// The cron job name
@@ -10,7 +13,7 @@ pub struct JobVolumeClaim {
 }
 
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 pub struct CronJob {
     /// Common properties for all types of container
     #[serde(flatten)]
@@ -45,3 +48,261 @@ pub struct CronJob {
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub podAnnotations: BTreeMap<String, String>,
 }
+
+impl CronJob {
+    /// Record every problem with the cron job under `path`
+    ///
+    /// Invalid schedules are errors, timeouts longer than the shortest interval are warnings.
+    pub fn diagnose(&self, path: &str, d: &mut Diagnostics) {
+        let schedule = match CronSchedule::parse(&self.schedule) {
+            Ok(s) => s,
+            Err(e) => {
+                return d.error(
+                    &format!("{}.schedule", path),
+                    format!(
+                        "{} has an invalid schedule '{}': {}",
+                        self.container.name, self.schedule, e
+                    ),
+                )
+            }
+        };
+        if let (Some(timeout), Some(interval)) = (self.timeout, schedule.min_interval()) {
+            if i64::from(timeout) > interval.num_seconds() {
+                d.warn(
+                    &format!("{}.timeout", path),
+                    format!(
+                        "{} has a timeout of {}s, longer than its {}s interval",
+                        self.container.name,
+                        timeout,
+                        interval.num_seconds()
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// A parsed cron schedule in the 5 field syntax used by kubernetes
+///
+/// Supports lists, ranges, steps, month/weekday names, `?`, and the `@daily` style macros.
+/// Runs are computed in UTC like the kube controller manager.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    minutes: BTreeSet<u32>,
+    hours: BTreeSet<u32>,
+    days: BTreeSet<u32>,
+    months: BTreeSet<u32>,
+    weekdays: BTreeSet<u32>,
+    /// Whether day of month / week were restricted (affects how they combine)
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+fn parse_value(s: &str, names: &[&str], offset: u32) -> Result<u32> {
+    let lower = s.to_lowercase();
+    if let Some(i) = names.iter().position(|n| *n == lower) {
+        return Ok(i as u32 + offset);
+    }
+    Ok(s.parse::<u32>()?)
+}
+
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<BTreeSet<u32>> {
+    let mut res = BTreeSet::new();
+    for part in field.split(',') {
+        let (range, step) = match part.find('/') {
+            Some(i) => (&part[..i], part[i + 1..].parse::<u32>()?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            bail!("step of 0 in '{}'", part);
+        }
+        let (lo, hi) = if range == "*" || range == "?" {
+            (min, max)
+        } else if let Some(i) = range.find('-') {
+            (
+                parse_value(&range[..i], names, min)?,
+                parse_value(&range[i + 1..], names, min)?,
+            )
+        } else {
+            let v = parse_value(range, names, min)?;
+            // a single value with a step means "from value to the end"
+            (v, if part.contains('/') { max } else { v })
+        };
+        if lo < min || hi > max || lo > hi {
+            bail!("'{}' is outside {}-{}", part, min, max);
+        }
+        res.extend((lo..=hi).step_by(step as usize));
+    }
+    Ok(res)
+}
+
+impl CronSchedule {
+    pub fn parse(schedule: &str) -> Result<Self> {
+        let expanded = match schedule.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            s => s,
+        };
+        let fields = expanded.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            bail!("expected 5 fields, found {}", fields.len());
+        }
+        let restricted = |f: &str| f != "*" && f != "?";
+        let mut weekdays = parse_field(fields[4], 0, 7, &WEEKDAYS)?;
+        if weekdays.remove(&7) {
+            weekdays.insert(0); // 7 is also sunday
+        }
+        Ok(CronSchedule {
+            minutes: parse_field(fields[0], 0, 59, &[])?,
+            hours: parse_field(fields[1], 0, 23, &[])?,
+            days: parse_field(fields[2], 1, 31, &[])?,
+            months: parse_field(fields[3], 1, 12, &MONTHS)?,
+            weekdays,
+            days_restricted: restricted(fields[2]),
+            weekdays_restricted: restricted(fields[4]),
+        })
+    }
+
+    fn day_matches(&self, t: &DateTime<Utc>) -> bool {
+        let dom = self.days.contains(&t.day());
+        let dow = self.weekdays.contains(&t.weekday().num_days_from_sunday());
+        // standard cron: either matches when both are restricted
+        if self.days_restricted && self.weekdays_restricted {
+            dom || dow
+        } else {
+            dom && dow
+        }
+    }
+
+    /// First run strictly after `after`
+    ///
+    /// Returns None if the schedule never fires within five years (e.g. `0 0 31 2 *`).
+    pub fn next_after(&self, after: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = *after - Duration::seconds(i64::from(after.second())) + Duration::minutes(1);
+        let mut t = start.with_nanosecond(0)?;
+        let limit = start + Duration::days(5 * 366);
+        while t < limit {
+            if !self.months.contains(&t.month()) {
+                let (y, m) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = Utc.ymd(y, m, 1).and_hms(0, 0, 0);
+            } else if !self.day_matches(&t) {
+                t = t.date().succ().and_hms(0, 0, 0);
+            } else if !self.hours.contains(&t.hour()) {
+                t = t.date().and_hms(t.hour(), 0, 0) + Duration::hours(1);
+            } else if !self.minutes.contains(&t.minute()) {
+                t = t + Duration::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+
+    /// The next `n` runs after `after`
+    pub fn upcoming(&self, after: &DateTime<Utc>, n: usize) -> Vec<DateTime<Utc>> {
+        let mut res = Vec::with_capacity(n);
+        let mut t = *after;
+        while res.len() < n {
+            match self.next_after(&t) {
+                Some(next) => {
+                    res.push(next);
+                    t = next;
+                }
+                None => break,
+            }
+        }
+        res
+    }
+
+    /// Shortest gap between consecutive runs
+    ///
+    /// Sampled over the first 1000 runs from a fixed date, so the result is stable.
+    pub fn min_interval(&self) -> Option<Duration> {
+        let start = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+        let runs = self.upcoming(&start, 1000);
+        runs.windows(2).map(|w| w[1] - w[0]).min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CronJob, CronSchedule};
+    use crate::{Diagnostics, Severity};
+    use chrono::{Duration, TimeZone, Utc};
+
+    #[test]
+    fn parse_schedules() {
+        assert!(CronSchedule::parse("*/15 * * * *").is_ok());
+        assert!(CronSchedule::parse("0 9-17/2 * jan-mar mon-fri").is_ok());
+        assert!(CronSchedule::parse("30 2 1,15 * ?").is_ok());
+        assert!(CronSchedule::parse("@daily").is_ok());
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+        assert!(CronSchedule::parse("0 0 * * funday").is_err());
+        assert!(CronSchedule::parse("5-1 * * * *").is_err());
+    }
+
+    #[test]
+    fn next_runs() {
+        let start = Utc.ymd(2020, 1, 1).and_hms(10, 7, 30); // a wednesday
+        let every15 = CronSchedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(every15.upcoming(&start, 2), vec![
+            Utc.ymd(2020, 1, 1).and_hms(10, 15, 0),
+            Utc.ymd(2020, 1, 1).and_hms(10, 30, 0),
+        ]);
+        let weekdays = CronSchedule::parse("0 9 * * mon-fri").unwrap();
+        assert_eq!(weekdays.upcoming(&start, 3), vec![
+            Utc.ymd(2020, 1, 2).and_hms(9, 0, 0),
+            Utc.ymd(2020, 1, 3).and_hms(9, 0, 0),
+            Utc.ymd(2020, 1, 6).and_hms(9, 0, 0),
+        ]);
+        // day of month OR day of week when both are restricted
+        let either = CronSchedule::parse("0 0 13 * 5").unwrap();
+        assert_eq!(either.upcoming(&start, 2), vec![
+            Utc.ymd(2020, 1, 3).and_hms(0, 0, 0),
+            Utc.ymd(2020, 1, 10).and_hms(0, 0, 0),
+        ]);
+        let leap = CronSchedule::parse("0 0 29 feb *").unwrap();
+        assert_eq!(
+            leap.next_after(&start),
+            Some(Utc.ymd(2020, 2, 29).and_hms(0, 0, 0))
+        );
+        assert_eq!(
+            CronSchedule::parse("0 0 31 2 *").unwrap().next_after(&start),
+            None
+        );
+        assert_eq!(every15.min_interval(), Some(Duration::minutes(15)));
+    }
+
+    #[test]
+    fn diagnose_cronjobs() {
+        let mut cj = CronJob::default();
+        cj.container.name = "nightly".into();
+        cj.schedule = "0 * * * *".into();
+        cj.timeout = Some(7200);
+        let mut d = Diagnostics::default();
+        cj.diagnose("cronJobs[0]", &mut d);
+        assert_eq!(d.items.len(), 1);
+        assert_eq!(d.items[0].severity, Severity::Warning);
+        assert_eq!(d.items[0].path, "cronJobs[0].timeout");
+
+        cj.schedule = "0 * * *".into();
+        let mut d = Diagnostics::default();
+        cj.diagnose("cronJobs[0]", &mut d);
+        assert!(d.has_errors());
+        assert_eq!(d.items[0].path, "cronJobs[0].schedule");
+    }
+}
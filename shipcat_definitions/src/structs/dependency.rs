@@ -1,10 +1,11 @@
-use super::Result;
+use crate::Diagnostics;
+use schemars::JsonSchema;
 use std::path::Path;
 
 /// Supported dependency protocols
 ///
 /// Forces lowercase values of this enum to be used
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum DependencyProtocol {
     /// HTTP REST dependency
@@ -25,7 +26,7 @@ impl Default for DependencyProtocol {
 }
 
 /// Dependency of a service
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct Dependency {
     /// Name of service relied upon (used to goto dependent manifest)
@@ -48,21 +49,32 @@ fn default_api_version() -> String {
 
 
 impl Dependency {
-    pub fn verify(&self) -> Result<()> {
+    /// Record every problem with the dependency under `path`
+    pub fn diagnose(&self, path: &str, d: &mut Diagnostics) {
         // self.name must exist in services/
         let dpth = Path::new(".").join("services").join(self.name.clone());
         if !dpth.is_dir() {
-            bail!("Service {} does not exist in services/", self.name);
+            d.error(
+                &format!("{}.name", path),
+                format!("Service {} does not exist in services/", self.name),
+            );
         }
         if self.api != "" {
             let vstr = self.api.chars().skip_while(|ch| *ch == 'v').collect::<String>();
-            let ver: usize = vstr.parse()?;
-            trace!(
-                "Parsed api version of dependency {} as {}",
-                self.name.clone(),
-                ver
-            );
+            match vstr.parse::<usize>() {
+                Ok(ver) => trace!(
+                    "Parsed api version of dependency {} as {}",
+                    self.name.clone(),
+                    ver
+                ),
+                Err(e) => d.error(
+                    &format!("{}.api", path),
+                    format!(
+                        "Invalid api version {} for dependency {}: {}",
+                        self.api, self.name, e
+                    ),
+                ),
+            }
         }
-        Ok(())
     }
 }
@@ -1,10 +1,11 @@
 use super::Result;
 use regex::Regex;
+use schemars::JsonSchema;
 
 /// DestinationRule
 ///
 /// An abstraction that captures the information needed to make routing decisions.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct DestinationRule {
     /// The identifier the incoming request must possess to be considered for forwarding
     pub identifier: String,
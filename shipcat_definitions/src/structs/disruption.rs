@@ -7,10 +7,11 @@
 /// This work is left here in case it becomes useful.
 /// Users may wish to look at rollingupdate.rs instead, which has a useful alternative.
 
+use schemars::JsonSchema;
 use super::{Result};
 
 // Untagged enum to get around the weird validation
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[serde(untagged)]
 pub enum AvailabilityPolicy {
     Percentage(String),
@@ -46,7 +47,7 @@ impl AvailabilityPolicy {
 /// Users need to set exactly one of these to pass validation.
 /// The values are "how many replicas" when integer values are used,
 /// and "what percentage of total replicas" when a % is added to the string.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct DisruptionBudget {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub minAvailable: Option<AvailabilityPolicy>,
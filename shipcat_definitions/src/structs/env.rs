@@ -1,4 +1,6 @@
 use super::Result;
+use crate::Diagnostics;
+use schemars::JsonSchema;
 use std::collections::{BTreeMap, BTreeSet};
 
 /// Environment variables to inject
@@ -29,7 +31,7 @@ use std::collections::{BTreeMap, BTreeSet};
 /// region, and replace them internally.
 ///
 /// The `as_secret` destinction only serves to put `AUTH_SECRET` into `Manifest::secrets`.
-#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug, JsonSchema)]
 #[serde(default)]
 pub struct EnvVars {
     /// Plain text (non-secret) environment variables
@@ -65,12 +67,21 @@ impl EnvVars {
     }
 
     pub fn verify(&self) -> Result<()> {
+        let mut d = Diagnostics::default();
+        self.diagnose("env", &mut d);
+        d.into_result()
+    }
+
+    /// Record every problem with the env vars under `path`
+    pub fn diagnose(&self, path: &str, d: &mut Diagnostics) {
         for k in self.plain.keys() {
             if k != &k.to_uppercase() {
-                bail!("Env vars need to be uppercase, found: {}", k);
+                d.error(
+                    &format!("{}.{}", path, k),
+                    format!("Env vars need to be uppercase, found: {}", k),
+                );
             }
         }
-        Ok(())
     }
 
     // Remove variables with a value "IN_VAULT", mark them as a secret and return them.
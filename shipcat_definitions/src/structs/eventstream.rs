@@ -1,14 +1,15 @@
-use super::Result;
+use crate::Diagnostics;
+use schemars::JsonSchema;
 use std::collections::BTreeMap;
 
-#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+#[derive(Default, Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct EventDefinition {
     pub key: String,
     pub value: String,
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct EventStream {
     pub name: String,
@@ -26,13 +27,19 @@ pub struct EventStream {
 }
 
 impl EventStream {
-    pub fn verify(&self) -> Result<()> {
+    /// Record every problem with the event stream under `path`
+    pub fn diagnose(&self, path: &str, d: &mut Diagnostics) {
         if self.event_definitions.is_empty() {
-            bail!("Event definitions must not be empty when EventStreams is specified");
+            d.error(
+                &format!("{}.eventDefinitions", path),
+                "Event definitions must not be empty when EventStreams is specified",
+            );
         }
         if self.name.is_empty() {
-            bail!("EventStream name must not be empty when EventStreams is specified");
+            d.error(
+                &format!("{}.name", path),
+                "EventStream name must not be empty when EventStreams is specified",
+            );
         }
-        Ok(())
     }
 }
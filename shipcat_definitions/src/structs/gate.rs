@@ -1,10 +1,11 @@
+use schemars::JsonSchema;
 use std::ops::Not;
 
 /// Gate service configuration
 ///
 /// Gate is a babylon-specific, filtering entry-point for kong, as such, requires kong.
 /// Configuration for gate is expected to be picked up outside of shipcat for services using kong.
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct Gate {
     /// Let external traffic in or not
@@ -1,9 +1,10 @@
-use super::Result;
+use crate::Diagnostics;
 use regex::Regex;
+use schemars::JsonSchema;
 
 // HostAlias support for all pods regardless of network configuration.
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
 pub struct HostAlias {
     /// ip address string
     pub ip: String,
@@ -12,23 +13,31 @@ pub struct HostAlias {
 }
 
 impl HostAlias {
-    /// Verify syntax
-    pub fn verify(&self) -> Result<()> {
+    /// Record every syntax problem with the host alias under `path`
+    pub fn diagnose(&self, path: &str, d: &mut Diagnostics) {
         // Commonly accepted hostname regex from https://stackoverflow.com/questions/106179/regular-expression-to-match-dns-hostname-or-ip-address
         let ip_re = Regex::new(r"^(([0-9]|[1-9][0-9]|1[0-9]{2}|2[0-4][0-9]|25[0-5])\.){3}([0-9]|[1-9][0-9]|1[0-9]{2}|2[0-4][0-9]|25[0-5])$").unwrap();
         if self.ip == "" || !ip_re.is_match(&self.ip) {
-            bail!("The ip address for the host alias is incorrect");
+            d.error(
+                &format!("{}.ip", path),
+                "The ip address for the host alias is incorrect",
+            );
         }
         if self.hostnames.is_empty() {
-            bail!("At least one hostname must be specified for the host alias");
+            d.error(
+                &format!("{}.hostnames", path),
+                "At least one hostname must be specified for the host alias",
+            );
         }
-        for hostname in &self.hostnames {
-            // Commonly accepted ip address regex from https://stackoverflow.com/questions/106179/regular-expression-to-match-dns-hostname-or-ip-address
-            let host_re = Regex::new(r"^(([a-zA-Z0-9]|[a-zA-Z0-9][a-zA-Z0-9\-]*[a-zA-Z0-9])\.)*([A-Za-z0-9]|[A-Za-z0-9][A-Za-z0-9\-]*[A-Za-z0-9])$").unwrap();
+        // Commonly accepted ip address regex from https://stackoverflow.com/questions/106179/regular-expression-to-match-dns-hostname-or-ip-address
+        let host_re = Regex::new(r"^(([a-zA-Z0-9]|[a-zA-Z0-9][a-zA-Z0-9\-]*[a-zA-Z0-9])\.)*([A-Za-z0-9]|[A-Za-z0-9][A-Za-z0-9\-]*[A-Za-z0-9])$").unwrap();
+        for (i, hostname) in self.hostnames.iter().enumerate() {
             if !host_re.is_match(&hostname) {
-                bail!("The hostname {} is incorrect for {}", hostname, self.ip);
+                d.error(
+                    &format!("{}.hostnames[{}]", path, i),
+                    format!("The hostname {} is incorrect for {}", hostname, self.ip),
+                );
             }
         }
-        Ok(())
     }
 }
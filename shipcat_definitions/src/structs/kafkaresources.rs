@@ -1,8 +1,10 @@
 use super::Result;
+use crate::Diagnostics;
 use regex::Regex;
+use schemars::JsonSchema;
 use std::collections::BTreeMap;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct KafkaTopics {
     pub name: String,
 
@@ -19,7 +21,7 @@ pub struct KafkaTopics {
 /// Resource Types relating to a Kafka ACL to be applied onto a resource,
 /// values derived from the Strimzi Kafka User Custom Resource Definition
 /// [Strimzi Kafka User CRD ](https://github.com/strimzi/strimzi-kafka-operator/blob/master/install/user-operator/04-Crd-kafkauser.yaml)
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum KafkaUserResourceType {
     Topic,
@@ -31,7 +33,7 @@ pub enum KafkaUserResourceType {
 /// Operations relating to a Kafka ACL to be applied onto a resource,
 /// values derived from the Strimzi Kafka User Custom Resource Definition
 /// [Strimzi Kafka User CRD ](https://github.com/strimzi/strimzi-kafka-operator/blob/master/install/user-operator/04-Crd-kafkauser.yaml)
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub enum KafkaUserOperation {
     Read,
@@ -47,14 +49,14 @@ pub enum KafkaUserOperation {
     All,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum KafkaUserPatternType {
     Literal,
     Prefix,
 }
 
-#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+#[derive(Default, Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[serde(deny_unknown_fields, rename_all = "camelCase")]
 pub struct AclDefinition {
     pub resource_name: String,
@@ -71,7 +73,7 @@ fn default_host() -> String {
 }
 
 
-#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+#[derive(Default, Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct KafkaUsers {
     pub name: String,
@@ -79,7 +81,7 @@ pub struct KafkaUsers {
 }
 
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct KafkaResources {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -98,56 +100,36 @@ impl KafkaResources {
             .is_match(value)
     }
 
-    fn is_VALID_PARTITIONS(value: &i32) -> bool {
-        let partition_range = 1..10000;
-        partition_range.contains(value)
-    }
-
-    fn is_VALID_REPLICAS(value: &i32) -> bool {
-        let replica_range = 1..32767;
-        replica_range.contains(value)
-    }
-
     pub fn verify(&self) -> Result<()> {
-        let mut failed_topics = vec![];
-        let mut failed_partitions = vec![];
-        let mut failed_replicas = vec![];
-
-        let mut failed_users = vec![];
+        let mut d = Diagnostics::default();
+        self.diagnose("kafkaResources", &mut d);
+        d.into_result()
+    }
 
-        for topic in &self.topics {
+    /// Record every invalid topic and user name under `path`
+    pub fn diagnose(&self, path: &str, d: &mut Diagnostics) {
+        for (i, topic) in self.topics.iter().enumerate() {
             if !KafkaResources::is_VALID_K8S_NAME(&topic.name) {
-                failed_topics.push(&topic.name);
-            }
-            if !KafkaResources::is_VALID_PARTITIONS(&topic.partitions) {
-                failed_partitions.push(&topic.partitions);
-            }
-            if !KafkaResources::is_VALID_REPLICAS(&topic.replicas) {
-                failed_replicas.push(&topic.replicas);
+                d.error(
+                    &format!("{}.topics[{}].name", path, i),
+                    format!(
+                        "invalid topic name {}, must match expression \"^[0-9a-z\\-\\.]{{1,63}}$\"",
+                        topic.name
+                    ),
+                );
             }
         }
-
-        for user in &self.users {
+        for (i, user) in self.users.iter().enumerate() {
             if !KafkaResources::is_VALID_K8S_NAME(&user.name) {
-                failed_users.push(&user.name);
+                d.error(
+                    &format!("{}.users[{}].name", path, i),
+                    format!(
+                        "invalid user name {}, must match expression \"^[0-9a-z\\-\\.]{{1,63}}$\"",
+                        user.name
+                    ),
+                );
             }
         }
-
-        if !failed_topics.is_empty() {
-            bail!(
-                "invalid topic name(s), must match expression \"^[0-9a-z\\-\\.]{{1,63}}$\": {:?}",
-                failed_topics
-            );
-        }
-
-        if !failed_users.is_empty() {
-            bail!(
-                "invalid user name(s), must match expression \"^[0-9a-z\\-\\.]{{1,63}}$\": {:?}",
-                failed_users
-            );
-        }
-
-        Ok(())
     }
 }
 
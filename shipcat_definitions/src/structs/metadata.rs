@@ -1,17 +1,18 @@
 use crate::teams::Owners;
 use regex::Regex;
+use schemars::JsonSchema;
 use std::{
     collections::{BTreeMap, BTreeSet},
     ops::{Deref, DerefMut},
 };
 
 use super::Result;
-use crate::config::SlackParameters;
+use crate::{config::SlackParameters, Diagnostics};
 
 /// Legacy contact data
 ///
 /// This property is being phased out in favour of .maintainer
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct Contact {
     /// Free text name
     pub name: String,
@@ -49,7 +50,7 @@ impl Contact {
 }
 
 /// Slack channel verifier
-#[derive(Serialize, Deserialize, PartialEq, Clone, Default, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Default, Debug, JsonSchema)]
 pub struct SlackChannel(String);
 impl SlackChannel {
     pub fn new(chan: &str) -> Self {
@@ -90,7 +91,7 @@ impl DerefMut for SlackChannel {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Language {
     Rust,
@@ -122,7 +123,7 @@ pub enum Language {
 /// context:
 ///   name: consultations
 /// ```
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Context {
     /// name of parent context
@@ -143,7 +144,7 @@ impl Context {
 }
 
 /// Metadata for a service
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[cfg_attr(test, derive(Default))]
 pub struct Metadata {
     /// Git repository
@@ -230,48 +231,72 @@ impl Metadata {
 
 impl Metadata {
     pub fn verify(&self, owners: &Owners, allowedCustomMetadata: &BTreeSet<String>) -> Result<()> {
+        let mut d = Diagnostics::default();
+        self.diagnose(owners, allowedCustomMetadata, &mut d);
+        d.into_result()
+    }
+
+    /// Record every problem with the metadata
+    pub fn diagnose(&self, owners: &Owners, allowedCustomMetadata: &BTreeSet<String>, d: &mut Diagnostics) {
         if !owners.squads.contains_key(&self.team) {
-            bail!("Team name {} does not match a squad in teams.yml", self.team);
+            d.error(
+                "metadata.team",
+                format!("Team name {} does not match a squad in teams.yml", self.team),
+            );
         }
-        for cc in &self.contacts {
-            cc.verify()?;
+        for (i, cc) in self.contacts.iter().enumerate() {
+            d.check(&format!("metadata.contacts[{}]", i), cc.verify());
         }
         if let Some(context) = &self.context {
-            context.verify()?;
+            d.check("metadata.context", context.verify());
         }
         for m in &self.maintainers {
             if !owners.people.contains_key(m) {
-                bail!("Person {} does not match a person in teams.yml", m)
+                d.error(
+                    "metadata.maintainers",
+                    format!("Person {} does not match a person in teams.yml", m),
+                );
             }
         }
         let re = Regex::new(r"[a-z0-9\-\.\{\}]").unwrap();
         if !re.is_match(&self.gitTagTemplate) {
-            bail!("gitTagTemplate {} is of invalid format", self.gitTagTemplate);
+            d.error(
+                "metadata.gitTagTemplate",
+                format!("gitTagTemplate {} is of invalid format", self.gitTagTemplate),
+            );
         }
         let sanityre = Regex::new(r"\{\{.?version.?\}\}").unwrap();
         if !sanityre.is_match(&self.gitTagTemplate) {
-            bail!(
-                "gitTagTemplate {} does not dereference {{ version }}",
-                self.gitTagTemplate
+            d.error(
+                "metadata.gitTagTemplate",
+                format!(
+                    "gitTagTemplate {} does not dereference {{ version }}",
+                    self.gitTagTemplate
+                ),
             );
         }
         if let Some(channel) = &self.support {
-            channel.verify()?;
+            d.check("metadata.support", channel.verify());
         }
         if let Some(channel) = &self.notifications {
-            channel.verify()?;
+            d.check("metadata.notifications", channel.verify());
         }
         if let Some(runbook) = &self.runbook {
             if !runbook.ends_with(".md") && !runbook.ends_with(".rt") && !runbook.ends_with(".org") {
-                bail!("Runbook must be a file in the service repo with a valid extension (.md / .rt / .org)");
+                d.error(
+                    "metadata.runbook",
+                    "Runbook must be a file in the service repo with a valid extension (.md / .rt / .org)",
+                );
             }
         }
         for k in self.custom.keys() {
             if !allowedCustomMetadata.contains(k) {
-                bail!("{} is not an allowed metadata property", k);
+                d.error(
+                    &format!("metadata.custom.{}", k),
+                    format!("{} is not an allowed metadata property", k),
+                );
             }
         }
-        Ok(())
     }
 }
 
@@ -54,7 +54,7 @@ pub use self::env::EnvVars;
 // translations - these are typically inlined in templates as yaml
 /// Kubernetes resource structs
 pub mod resources;
-pub use self::resources::{parse_memory, ResourceRequirements};
+pub use self::resources::{parse_cpu, parse_memory, ResourceRequirements};
 /// Kubernetes volumes
 pub mod volume;
 pub use self::volume::{Volume, VolumeMount};
@@ -66,7 +66,7 @@ mod probes;
 pub use self::probes::Probe;
 /// Kubernetes rolling-update settings
 pub mod rollingupdate;
-pub use self::rollingupdate::RollingUpdate;
+pub use self::rollingupdate::{PartitionGate, RollingUpdate};
 /// Kubernetes horizontal pod autoscaler
 pub mod autoscaling;
 /// Kubernetes container lifecycle events
@@ -89,7 +89,7 @@ pub use self::vault::VaultOpts;
 
 /// Cron Jobs
 pub mod cronjob;
-pub use self::cronjob::{CronJob, JobVolumeClaim};
+pub use self::cronjob::{CronJob, CronSchedule, JobVolumeClaim};
 
 // Kubernetes Containers
 pub mod container;
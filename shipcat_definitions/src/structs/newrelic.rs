@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use std::collections::BTreeMap;
 
 use super::metadata::SlackChannel;
@@ -16,7 +17,7 @@ use super::metadata::SlackChannel;
 ///   incidentPreference: PER_POLICY
 ///   slack: C12ABYZ78
 /// ```
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Newrelic {
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
@@ -25,7 +26,7 @@ pub struct Newrelic {
     pub slack: SlackChannel,
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct NewrelicAlert {
     pub name: String,
@@ -36,7 +37,7 @@ pub struct NewrelicAlert {
 /// NewRelic AlertPolicy attribute that we configure once per Application (service@region) monitored
 ///
 /// Details available at [this link](https://docs.newrelic.com/docs/alerts/new-relic-alerts/configuring-alert-policies/specify-when-new-relic-creates-incidents#preference-options)
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum NewrelicIncidentPreference {
     /// Only one incident will be open at a time for the entire policy. This is the default.
@@ -1,9 +1,11 @@
-use super::{resources::parse_memory, Result};
+use super::resources::parse_memory;
+use crate::Diagnostics;
+use schemars::JsonSchema;
 
 /// K8s Access modes for PVCs
 ///
 /// See [K8s access mode docs](https://kubernetes.io/docs/concepts/storage/persistent-volumes/#access-modes).
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub enum VolumeAccessMode {
     ReadWriteOnce,
     ReadOnlyMany,
@@ -19,7 +21,7 @@ impl Default for VolumeAccessMode {
 /// A kubernetes Persistent Volume Claim
 ///
 /// See [K8s persistent volume docs](https://kubernetes.io/docs/concepts/storage/persistent-volumes/)-.
-#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug, JsonSchema)]
 pub struct PersistentVolume {
     pub name: String,
     pub mountPath: String,
@@ -29,18 +31,29 @@ pub struct PersistentVolume {
 }
 
 impl PersistentVolume {
-    pub fn verify(&self) -> Result<()> {
-        let size = parse_memory(&self.size)?;
-        // sanity number; 16TB via https://docs.aws.amazon.com/AWSEC2/latest/UserGuide/ebs-volume-types.html
-        if size > 16.0 * 1024.0 * 1024.0 * 1024.0 * 1024.0 {
-            bail!("Persistent Volume request more than 16 TB")
+    /// Record every problem with the volume claim under `path`
+    pub fn diagnose(&self, path: &str, d: &mut Diagnostics) {
+        let size_path = format!("{}.size", path);
+        match parse_memory(&self.size) {
+            // sanity number; 16TB via https://docs.aws.amazon.com/AWSEC2/latest/UserGuide/ebs-volume-types.html
+            Ok(size) if size > 16.0 * 1024.0 * 1024.0 * 1024.0 * 1024.0 => {
+                d.error(&size_path, "Persistent Volume request more than 16 TB")
+            }
+            Ok(_) => {}
+            Err(e) => d.check::<()>(&size_path, Err(e)),
         }
+        let mount_path = format!("{}.mountPath", path);
         if !self.mountPath.starts_with('/') {
-            bail!("Mount path '{}' must start with a slash", self.mountPath);
+            d.error(
+                &mount_path,
+                format!("Mount path '{}' must start with a slash", self.mountPath),
+            );
         }
         if self.mountPath.ends_with('/') {
-            bail!("Mount path '{}' must not end with a slash", self.mountPath);
+            d.error(
+                &mount_path,
+                format!("Mount path '{}' must not end with a slash", self.mountPath),
+            );
         }
-        Ok(())
     }
 }
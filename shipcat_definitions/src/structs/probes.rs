@@ -1,7 +1,8 @@
 use super::Result;
+use schemars::JsonSchema;
 
 
-#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct HttpGet {
     /// Uri path to GET (i.e. / or /health)
@@ -17,7 +18,7 @@ fn http_get_default_port() -> String {
     "http".into()
 }
 
-#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct HttpHeader {
     pub name: String,
@@ -25,7 +26,7 @@ pub struct HttpHeader {
 }
 
 
-#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct Exec {
     /// Command to execute in the container
@@ -33,14 +34,14 @@ pub struct Exec {
 }
 
 
-#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct TcpSocket {
     pub port: String,
 }
 
 /// Liveness or readiness Probe
-#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct Probe {
     /// Http Get probe
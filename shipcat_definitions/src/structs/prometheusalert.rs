@@ -1,12 +1,15 @@
 use super::Result;
+use crate::Diagnostics;
 use inflector::cases::pascalcase::is_pascal_case;
+use prometheus_parser::{Expression, LabelOp, Selector};
 use regex::Regex;
+use schemars::JsonSchema;
 
 /// Data describing one Prometheus alert.
 ///
 /// This roughly corresponds to a Rule object in the Prometheus Operator API spec:
 /// https://github.com/coreos/prometheus-operator/blob/master/Documentation/api.md#rule
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct PrometheusAlert {
     /// Name of the alert
     ///
@@ -45,7 +48,7 @@ pub struct PrometheusAlert {
 ///
 /// Represents the set of alert severities we allow in our Prometheus alerts.
 #[serde(rename_all = "lowercase")]
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub enum PrometheusAlertSeverity {
     /// Warning severity
     ///
@@ -58,31 +61,189 @@ pub enum PrometheusAlertSeverity {
     Error,
 }
 
+/// Labels an expression can match on to select the series of a service
+const SCOPE_LABELS: &[&str] = &["app", "service", "job", "pod", "container", "deployment"];
+
+/// Collect every selector in a PromQL expression
+fn selectors<'a>(expr: &'a Expression, acc: &mut Vec<&'a Selector>) {
+    match expr {
+        Expression::Selector(s) => acc.push(s),
+        Expression::Group(g) => selectors(&g.expression, acc),
+        Expression::Function(f) => {
+            for a in &f.args {
+                selectors(a, acc)
+            }
+        }
+        Expression::Operator(o) => {
+            selectors(&o.lhs, acc);
+            selectors(&o.rhs, acc);
+        }
+        Expression::Float(_) | Expression::String(_) => {}
+    }
+}
+
+/// Whether a selector only matches series belonging to the service
+///
+/// Prometheus anchors label regexes, so they have to match the whole service name.
+fn scoped_to(sel: &Selector, svc: &str) -> bool {
+    sel.labels.iter().any(|l| {
+        SCOPE_LABELS.contains(&l.key.as_str())
+            && match l.op {
+                LabelOp::Equal => l.value == svc,
+                LabelOp::RegexEqual => {
+                    matches!(Regex::new(&format!("^(?:{})$", l.value)), Ok(re) if re.is_match(svc))
+                }
+                _ => false,
+            }
+    })
+}
+
 impl PrometheusAlert {
     pub fn verify(&self, svc: &str) -> Result<()> {
+        let mut d = Diagnostics::default();
+        self.diagnose(svc, "", &mut d);
+        d.into_result()
+    }
+
+    /// Record problems with the alert
+    ///
+    /// Expressions whose selectors do not match on a label of the service are warned about,
+    /// as they would fire for (or be silenced by) other services.
+    pub fn diagnose(&self, svc: &str, path: &str, d: &mut Diagnostics) {
         if !is_pascal_case(&self.name) {
-            bail!("Prometheus alert for {} needs a non-empty PascalCaseName", svc);
+            d.error(
+                &format!("{}.name", path),
+                format!("Prometheus alert for {} needs a non-empty PascalCaseName", svc),
+            );
         }
         if self.summary.is_empty() {
-            bail!(
-                "Prometheus alert for {} needs a summary of the problem it identifies",
-                svc
+            d.error(
+                &format!("{}.summary", path),
+                format!(
+                    "Prometheus alert for {} needs a summary of the problem it identifies",
+                    svc
+                ),
             );
         }
         if self.description.is_empty() {
-            bail!(
-                "Prometheus alert for {} needs a description of the problem it identifies",
-                svc
+            d.error(
+                &format!("{}.description", path),
+                format!(
+                    "Prometheus alert for {} needs a description of the problem it identifies",
+                    svc
+                ),
             );
         }
-        if !Regex::new(r"^\d+[mh]$").unwrap().is_match(&self.min_duration) {
-            bail!("Prometheus alert has invalid min_duration value (needs to be like '15m' or '1h')");
+        // Prometheus duration format, e.g. 30s, 15m, 1h30m
+        if !Regex::new(r"^(\d+(ms|s|m|h|d|w|y))+$")
+            .unwrap()
+            .is_match(&self.min_duration)
+        {
+            d.error(
+                &format!("{}.min_duration", path),
+                format!(
+                    "Prometheus alert {} has invalid min_duration '{}' (needs to be like '15m' or '1h')",
+                    self.name, self.min_duration
+                ),
+            );
         }
         // PromQL expression sanity (NB: syntax only, operator verifies properly)
-        if let Err(e) = prometheus_parser::parse_expr(&self.expr) {
-            bail!("Prometheus alert expression for {} invalid: {:?}", svc, e);
+        let expr_path = format!("{}.expr", path);
+        match prometheus_parser::parse_expr(&self.expr) {
+            Err(e) => d.error(
+                &expr_path,
+                format!("Prometheus alert expression for {} invalid: {}", svc, e),
+            ),
+            Ok(expr) => {
+                let mut sels = vec![];
+                selectors(&expr, &mut sels);
+                for sel in sels.into_iter().filter(|s| !scoped_to(s, svc)) {
+                    d.warn(
+                        &expr_path,
+                        format!(
+                            "Prometheus alert {} selects '{}' without matching {} on any of the labels {}",
+                            self.name,
+                            sel.metric.as_deref().unwrap_or("{..}"),
+                            svc,
+                            SCOPE_LABELS.join(", ")
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PrometheusAlert, PrometheusAlertSeverity};
+    use crate::Diagnostics;
+
+    fn alert(expr: &str, min_duration: &str) -> PrometheusAlert {
+        PrometheusAlert {
+            name: "FakeAskErrors".into(),
+            summary: "fake-ask is erroring".into(),
+            description: "More than one error per second".into(),
+            expr: expr.into(),
+            min_duration: min_duration.into(),
+            severity: PrometheusAlertSeverity::Warning,
         }
+    }
+
+    fn diagnose(pa: &PrometheusAlert) -> Diagnostics {
+        let mut d = Diagnostics::default();
+        pa.diagnose("fake-ask", "prometheusAlerts[0]", &mut d);
+        d
+    }
+
+    #[test]
+    fn scoped_expressions() {
+        let ok = alert(
+            r#"sum(rate(http_errors_total{app="fake-ask"}[5m])) / sum(rate(http_requests_total{app=~"fake-ask|fake-ask-worker"}[5m])) > 0.1"#,
+            "1h30m",
+        );
+        assert!(diagnose(&ok).items.is_empty());
+        assert!(ok.verify("fake-ask").is_ok());
 
-        Ok(())
+        let unscoped = alert(
+            r#"rate(http_errors_total{app="fake-ask"}[5m]) > on() (up{job!="fake-ask"})"#,
+            "15m",
+        );
+        let d = diagnose(&unscoped);
+        assert!(!d.has_errors());
+        assert_eq!(d.warnings().count(), 1);
+        assert_eq!(d.items[0].path, "prometheusAlerts[0].expr");
+        assert!(d.items[0].message.contains("'up'"));
+
+        // other services containing the name are not the service
+        for other in &[
+            r#"up{job="fake-ask-v2"}"#,
+            r#"up{app="not-fake-ask"}"#,
+            r#"up{app=~"fake-ask-.*"}"#,
+        ] {
+            let d = diagnose(&alert(&format!("{} == 0", other), "5m"));
+            assert_eq!(d.warnings().count(), 1, "{} is unscoped", other);
+        }
+        assert!(diagnose(&alert(r#"up{app=~"fake-.*"} == 0"#, "5m"))
+            .items
+            .is_empty());
+    }
+
+    #[test]
+    fn invalid_alerts() {
+        let d = diagnose(&alert(r#"rate(http_errors_total{app="fake-ask"}[5m] > 1"#, "5m"));
+        assert_eq!(d.errors().count(), 1);
+        assert_eq!(d.items[0].path, "prometheusAlerts[0].expr");
+
+        for bad in &["5", "5min", "m5", "1.5h", ""] {
+            let d = diagnose(&alert(r#"up{app="fake-ask"} == 0"#, bad));
+            assert_eq!(d.errors().count(), 1, "{} is invalid", bad);
+            assert_eq!(d.items[0].path, "prometheusAlerts[0].min_duration");
+        }
+        for good in &["30s", "15m", "1h", "2d", "1h30m", "500ms"] {
+            assert!(diagnose(&alert(r#"up{app="fake-ask"} == 0"#, good))
+                .items
+                .is_empty());
+        }
     }
 }
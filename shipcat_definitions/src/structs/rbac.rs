@@ -1,4 +1,5 @@
-use super::Result;
+use crate::Diagnostics;
+use schemars::JsonSchema;
 
 /// RBAC (Role-Based Access Control) PolicyRule
 ///
@@ -8,7 +9,7 @@ use super::Result;
 /// This is a port of [k8s PolicyRule](https://kubernetes.io/docs/reference/generated/kubernetes-api/v1.15/#policyrule-v1beta1-rbac-authorization-k8s-io)
 /// We skip `nonResourceURLs` since it is only relevant for ClusterRoles
 /// We also disallow empty resources to shoehorn in "all" access.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct Rbac {
     /// API groups containing resources
@@ -23,17 +24,25 @@ pub struct Rbac {
 }
 
 impl Rbac {
-    pub fn verify(&self) -> Result<()> {
+    /// Record every problem with the rule under `path`
+    pub fn diagnose(&self, path: &str, d: &mut Diagnostics) {
         if self.apiGroups.is_empty() {
-            bail!("RBAC needs to have at least one item in apiGroups");
+            d.error(
+                &format!("{}.apiGroups", path),
+                "RBAC needs to have at least one item in apiGroups",
+            );
         }
         if self.resources.is_empty() {
-            bail!("RBAC needs to have at least one item in resources");
+            d.error(
+                &format!("{}.resources", path),
+                "RBAC needs to have at least one item in resources",
+            );
         }
         if self.verbs.is_empty() {
-            bail!("RBAC needs to have at least one item in verbs");
+            d.error(
+                &format!("{}.verbs", path),
+                "RBAC needs to have at least one item in verbs",
+            );
         }
-
-        Ok(())
     }
 }
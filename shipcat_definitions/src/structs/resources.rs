@@ -1,4 +1,6 @@
 use super::Result;
+use crate::Diagnostics;
+use schemars::JsonSchema;
 use std::ops::{Add, AddAssign, Mul};
 
 // Kubernetes resouce structs
@@ -10,7 +12,7 @@ use std::ops::{Add, AddAssign, Mul};
 // implemented to be a bit more useful, as well as some to convert between them.
 
 /// Kubernetes resource requests or limit
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct Resources<T> {
     /// CPU request string
@@ -23,7 +25,7 @@ pub struct Resources<T> {
 /// Kubernetes resources
 ///
 /// This can be inlined straight into a container spec at the moment
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct ResourceRequirements<T> {
     /// Resource requests for k8s
@@ -114,32 +116,41 @@ impl ResourceRequirements<f64> {
 impl ResourceRequirements<String> {
     // TODO: look at config for limits?
     pub fn verify(&self) -> Result<()> {
+        let mut d = Diagnostics::default();
+        self.diagnose("resources", &mut d);
+        d.into_result()
+    }
+
+    /// Record every problem with the resources under `path`
+    pub fn diagnose(&self, path: &str, d: &mut Diagnostics) {
         // (We can unwrap all the values as we assume implicit called!)
-        let n = self.normalised()?;
+        let n = match self.normalised() {
+            Ok(n) => n,
+            Err(e) => return d.check::<()>(path, Err(e)),
+        };
         let req = &n.requests;
         let lim = &n.limits;
 
         // 1.1 limits >= requests
         if req.cpu > lim.cpu {
-            bail!("Requested more CPU than what was limited");
+            d.error(path, "Requested more CPU than what was limited");
         }
         if req.memory > lim.memory {
-            bail!("Requested more memory than what was limited");
+            d.error(path, "Requested more memory than what was limited");
         }
         // 1.2 sanity numbers (based on c5.9xlarge)
         if req.cpu > 36.0 {
-            bail!("Requested more than 36 cores");
+            d.error(path, "Requested more than 36 cores");
         }
         if req.memory > 72.0 * 1024.0 * 1024.0 * 1024.0 {
-            bail!("Requested more than 72 GB of memory");
+            d.error(path, "Requested more than 72 GB of memory");
         }
         if lim.cpu > 36.0 {
-            bail!("CPU limit set to more than 36 cores");
+            d.error(path, "CPU limit set to more than 36 cores");
         }
         if lim.memory > 72.0 * 1024.0 * 1024.0 * 1024.0 {
-            bail!("Memory limit set to more than 72 GB of memory");
+            d.error(path, "Memory limit set to more than 72 GB of memory");
         }
-        Ok(())
     }
 }
 
@@ -189,7 +200,8 @@ pub fn parse_memory(s: &str) -> Result<f64> {
 
 // Parse normal k8s cpu resource values into floats
 // We don't allow power of two variants here
-fn parse_cpu(s: &str) -> Result<f64> {
+// NB: nano and micro cores are only used by the metrics api
+pub fn parse_cpu(s: &str) -> Result<f64> {
     let digits = s
         .chars()
         .take_while(|ch| ch.is_digit(10) || *ch == '.')
@@ -203,6 +215,10 @@ fn parse_cpu(s: &str) -> Result<f64> {
     trace!("Parsed {} ({})", digits, unit);
     if unit == "m" {
         res /= 1000.0;
+    } else if unit == "u" {
+        res /= 1000.0 * 1000.0;
+    } else if unit == "n" {
+        res /= 1000.0 * 1000.0 * 1000.0;
     } else if unit == "k" {
         res *= 1000.0;
     } else if unit != "" {
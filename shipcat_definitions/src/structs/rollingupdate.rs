@@ -1,7 +1,9 @@
 use super::Result;
+use crate::PrimaryWorkload;
+use schemars::JsonSchema;
 
 // Untagged enum to get around the weird validation
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[serde(untagged)]
 pub enum AvailabilityPolicy {
     Percentage(String),
@@ -61,8 +63,25 @@ impl AvailabilityPolicy {
     }
 }
 
+/// How a partitioned Statefulset rollout proceeds between ordinals
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub enum PartitionGate {
+    /// Ask for confirmation on the terminal before updating the next ordinal
+    Manual,
+    /// Proceed once the updated pods have stayed ready for `stepWait` seconds
+    Auto,
+}
+
+impl Default for PartitionGate {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
 /// Configuration parameters for Deployment.spec.strategy.rollingUpdate
-#[derive(Serialize, Deserialize, Debug, Clone)]
+///
+/// or for Statefulset.spec.updateStrategy.rollingUpdate when using `partition`.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct RollingUpdate {
     /// How many replicas or percentage of replicas that can be down during rolling-update
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -70,6 +89,19 @@ pub struct RollingUpdate {
     /// Maximum number of pods that can be created over replicaCount
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub maxSurge: Option<AvailabilityPolicy>,
+
+    /// Statefulset ordinal to partition a rolling update at
+    ///
+    /// Only pods with an ordinal >= partition are updated by kubernetes,
+    /// and `shipcat apply` steps the partition down one ordinal at a time from there.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partition: Option<u32>,
+    /// How to proceed between partition steps (defaults to Auto)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stepGate: Option<PartitionGate>,
+    /// Seconds updated pods must stay ready before an Auto gate proceeds (defaults to 30)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stepWait: Option<u32>,
 }
 
 /// Implement Default that matches kubernetes
@@ -81,13 +113,22 @@ impl Default for RollingUpdate {
         RollingUpdate {
             maxUnavailable: Some(AvailabilityPolicy::Percentage(25.to_string())),
             maxSurge: Some(AvailabilityPolicy::Percentage(25.to_string())),
+            partition: None,
+            stepGate: None,
+            stepWait: None,
         }
     }
 }
 
 
 impl RollingUpdate {
-    pub fn verify(&self, replicas: u32) -> Result<()> {
+    pub fn verify(&self, replicas: u32, workload: &PrimaryWorkload) -> Result<()> {
+        if self.partition.is_some() || self.stepGate.is_some() || self.stepWait.is_some() {
+            if let PrimaryWorkload::Statefulset = workload {
+                return self.verify_partition(replicas);
+            }
+            bail!("rollingUpdate.partition can only be used with a Statefulset workload");
+        }
         if self.maxUnavailable.is_none() && self.maxSurge.is_none() {
             bail!("Need to set one of maxUnavailable or maxSurge in rollingUpdate");
         }
@@ -99,6 +140,33 @@ impl RollingUpdate {
         }
         Ok(())
     }
+
+    fn verify_partition(&self, replicas: u32) -> Result<()> {
+        if self.maxUnavailable.is_some() || self.maxSurge.is_some() {
+            bail!("partition cannot be combined with maxUnavailable or maxSurge in rollingUpdate");
+        }
+        match self.partition {
+            None => bail!("Need to set partition in rollingUpdate for a Statefulset"),
+            Some(p) if p > replicas => {
+                bail!("Cannot have partition set higher than replicaCount {}", replicas)
+            }
+            Some(_) => {}
+        }
+        if self.stepWait.is_some() && self.stepGate == Some(PartitionGate::Manual) {
+            bail!("stepWait only applies to an Auto stepGate");
+        }
+        Ok(())
+    }
+
+    /// The gate to use between partition steps
+    pub fn step_gate(&self) -> PartitionGate {
+        self.stepGate.clone().unwrap_or_default()
+    }
+
+    /// Seconds to wait for pods to stay ready between Auto partition steps
+    pub fn step_wait(&self) -> u32 {
+        self.stepWait.unwrap_or(30)
+    }
 }
 
 
@@ -167,7 +235,8 @@ impl RollingUpdate {
 
 #[cfg(test)]
 mod tests {
-    use super::{AvailabilityPolicy, RollingUpdate};
+    use super::{AvailabilityPolicy, PartitionGate, RollingUpdate};
+    use crate::PrimaryWorkload;
 
     #[test]
     fn rollout_iteration_no_overflow() {
@@ -192,6 +261,7 @@ mod tests {
         let rusurge = RollingUpdate {
             maxUnavailable: Some(AvailabilityPolicy::Percentage("25%".to_string())),
             maxSurge: Some(AvailabilityPolicy::Percentage("50%".to_string())),
+            ..RollingUpdate::default()
         };
         assert_eq!(rusurge.rollout_iterations(8), 2); // 2 dn 6  up, 6  dn 2 up
         assert_eq!(rusurge.rollout_iterations(16), 2); // 4 dn 12 up, 12 dn 4 up
@@ -200,6 +270,7 @@ mod tests {
         let rusurge = RollingUpdate {
             maxUnavailable: Some(AvailabilityPolicy::Percentage("75%".to_string())),
             maxSurge: Some(AvailabilityPolicy::Percentage("25%".to_string())),
+            ..RollingUpdate::default()
         };
         assert_eq!(rusurge.rollout_iterations(8), 1); // 6 dn 8 up (then 2 down ungated)
 
@@ -207,7 +278,60 @@ mod tests {
         let rusurge = RollingUpdate {
             maxUnavailable: Some(AvailabilityPolicy::Percentage("25%".to_string())),
             maxSurge: Some(AvailabilityPolicy::Percentage("0%".to_string())),
+            ..RollingUpdate::default()
         };
         assert_eq!(rusurge.rollout_iterations(8), 4); // 2 dn 2 up (x4)
     }
+
+    #[test]
+    fn partition_verify() {
+        let sts = PrimaryWorkload::Statefulset;
+        let partitioned = RollingUpdate {
+            maxUnavailable: None,
+            maxSurge: None,
+            partition: Some(2),
+            stepGate: Some(PartitionGate::Manual),
+            stepWait: None,
+        };
+        assert!(partitioned.verify(3, &sts).is_ok());
+        assert!(partitioned.verify(1, &sts).is_err()); // partition > replicas
+        assert!(partitioned.verify(3, &PrimaryWorkload::Deployment).is_err());
+        assert_eq!(partitioned.step_gate(), PartitionGate::Manual);
+
+        let auto = RollingUpdate {
+            stepGate: None,
+            stepWait: Some(60),
+            ..partitioned.clone()
+        };
+        assert!(auto.verify(3, &sts).is_ok());
+        assert_eq!(auto.step_gate(), PartitionGate::Auto);
+        assert_eq!(auto.step_wait(), 60);
+
+        // statefulsets without a partition keep the plain rolling update rules
+        let surging = RollingUpdate {
+            maxUnavailable: None,
+            maxSurge: Some(AvailabilityPolicy::Percentage("50%".to_string())),
+            ..RollingUpdate::default()
+        };
+        assert!(surging.verify(3, &sts).is_ok());
+        assert!(surging.verify(3, &PrimaryWorkload::Deployment).is_ok());
+        let unavailable = RollingUpdate {
+            maxUnavailable: Some(AvailabilityPolicy::Unsigned(1)),
+            maxSurge: None,
+            ..RollingUpdate::default()
+        };
+        assert!(unavailable.verify(3, &sts).is_ok());
+
+        // but cannot be mixed with a partition
+        let mixed = RollingUpdate {
+            partition: Some(1),
+            ..surging
+        };
+        assert!(mixed.verify(3, &sts).is_err());
+        let ungated = RollingUpdate {
+            partition: None,
+            ..auto
+        };
+        assert!(ungated.verify(3, &sts).is_err());
+    }
 }
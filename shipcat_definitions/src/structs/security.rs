@@ -1,11 +1,13 @@
 use super::Result;
+use crate::Diagnostics;
 use regex::Regex;
-use std::path::Path;
+use schemars::JsonSchema;
+use std::{collections::BTreeMap, fmt, path::Path};
 
 /// What sensitive data is managed and how
 ///
 /// See https://engineering.ops.babylontech.co.uk/docs/principles-security/
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct DataHandling {
     /// Where and how data is stored
@@ -25,7 +27,7 @@ impl DataHandling {
 }
 
 /// Possible levels of information classification of the data stored in the data store.
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum InformationClassification {
     StrictlyConfidential,
@@ -35,6 +37,84 @@ pub enum InformationClassification {
     Public,
 }
 
+impl fmt::Display for InformationClassification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            InformationClassification::StrictlyConfidential => "strictlyConfidential",
+            InformationClassification::ConfidentialPatientData => "confidentialPatientData",
+            InformationClassification::CommercialConfidential => "commercialConfidential",
+            InformationClassification::ProtectedInternal => "protectedInternal",
+            InformationClassification::Public => "public",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Requirements on data stores of an information classification
+///
+/// Set in `shipcat.conf` under `dataPolicies`, keyed by classification.
+///
+/// ```yaml
+/// dataPolicies:
+///   confidentialPatientData:
+///     encrypted: true
+///     ciphers: [AES256]
+///     keyRotator: true
+///     maxRetentionPeriod: 8y
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
+pub struct DataPolicy {
+    /// Data must be encrypted at the storage side
+    #[serde(default)]
+    pub encrypted: bool,
+    /// Ciphers allowed for encryption (any when empty)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ciphers: Vec<String>,
+    /// A key rotator must be set
+    #[serde(default)]
+    pub keyRotator: bool,
+    /// Longest retention period allowed
+    ///
+    /// When set, a retention period is mandatory. Same format as `retentionPeriod`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maxRetentionPeriod: Option<String>,
+}
+
+/// Data policies keyed by the classification they apply to
+pub type DataPolicies = BTreeMap<InformationClassification, DataPolicy>;
+
+/// Parse a retention period into days
+///
+/// A number followed by a humantime style unit: `d`/`days`, `w`/`weeks`, `M`/`months` (30 days)
+/// or `y`/`years` (365 days). A lowercase `m` is minutes in humantime, so it is rejected.
+pub fn retention_days(period: &str) -> Result<u64> {
+    let period = period.trim();
+    let (num, unit) = period.split_at(period.find(|c: char| !c.is_ascii_digit()).unwrap_or(period.len()));
+    let n: u64 = match num.parse() {
+        Ok(n) => n,
+        Err(_) => bail!("Retention period {} must start with a number", period),
+    };
+    let days = match unit.trim() {
+        "d" | "day" | "days" => 1,
+        "w" | "week" | "weeks" => 7,
+        "M" | "month" | "months" => 30,
+        "y" | "year" | "years" => 365,
+        "m" => bail!("Retention period {} is in minutes, use M for months", period),
+        _ => bail!("Retention period {} needs a unit of d, w, M or y", period),
+    };
+    Ok(n * days)
+}
+
+impl DataPolicy {
+    pub fn verify(&self) -> Result<()> {
+        if let Some(max) = &self.maxRetentionPeriod {
+            retention_days(max)?;
+        }
+        Ok(())
+    }
+}
+
 impl Default for InformationClassification {
     fn default() -> Self {
         InformationClassification::ConfidentialPatientData
@@ -42,7 +122,7 @@ impl Default for InformationClassification {
 }
 
 /// Data storage information and encryption information
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct DataStore {
     /// Storage type (one of "MySQL", "DynamoDB", "S3", "File", "Kafka")
@@ -62,7 +142,9 @@ pub struct DataStore {
     // Data is encryption strategies TODO: does this live in here?
     /// Key rotator if used TODO: format?
     pub keyRotator: Option<String>,
-    /// Retention period if any TODO: format? humantime?
+    /// Retention period if any
+    ///
+    /// A number with a unit of `d`, `w`, `M` (months) or `y`, like `30d` or `6M`.
     pub retentionPeriod: Option<String>,
 
     /// The information classification of the data stored in the data store.
@@ -101,7 +183,7 @@ impl DataStore {
 }
 
 /// Data storage information and encryption information
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct DataField {
     /// Canonical name of the data field
@@ -118,12 +200,12 @@ pub struct DataField {
     // Data is encryption strategies TODO: does this live in here?
     // Key rotator if used
     pub keyRotator: Option<String>,
-    // Retention period if any
+    // Retention period if any, same format as `DataStore::retentionPeriod`
     pub retentionPeriod: Option<String>,
 }
 
 /// Data storage information and encryption information
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct DataProcess {
     /// Canonical field name
@@ -132,7 +214,117 @@ pub struct DataProcess {
     pub source: String,
 }
 
+/// The encryption parameters of a store or one of its fields
+struct Protection<'a> {
+    path: String,
+    encrypted: Option<bool>,
+    cipher: &'a Option<String>,
+    keyRotator: &'a Option<String>,
+    retentionPeriod: &'a Option<String>,
+}
+
+impl DataStore {
+    /// Encryption parameters that apply to the stored data
+    ///
+    /// Fields have inherited the store values via `implicits`, so they are checked when present.
+    fn protections(&self, path: &str) -> Vec<Protection<'_>> {
+        if self.fields.is_empty() {
+            return vec![Protection {
+                path: path.to_string(),
+                encrypted: self.encrypted,
+                cipher: &self.cipher,
+                keyRotator: &self.keyRotator,
+                retentionPeriod: &self.retentionPeriod,
+            }];
+        }
+        self.fields
+            .iter()
+            .enumerate()
+            .map(|(i, f)| Protection {
+                path: format!("{}.fields[{}]", path, i),
+                encrypted: f.encrypted,
+                cipher: &f.cipher,
+                keyRotator: &f.keyRotator,
+                retentionPeriod: &f.retentionPeriod,
+            })
+            .collect()
+    }
+
+    /// Record violations of the policy for the store's classification
+    pub fn diagnose(&self, policies: &DataPolicies, path: &str, d: &mut Diagnostics) {
+        let class = match &self.informationClassification {
+            Some(c) => c,
+            None => return,
+        };
+        let policy = match policies.get(class) {
+            Some(p) => p,
+            None => return,
+        };
+        let max_days = policy
+            .maxRetentionPeriod
+            .as_ref()
+            .and_then(|m| retention_days(m).ok());
+        for p in self.protections(path) {
+            if policy.encrypted && p.encrypted != Some(true) {
+                d.error(
+                    &format!("{}.encrypted", p.path),
+                    format!("{} data in {} must be encrypted", class, self.backend),
+                );
+            }
+            if !policy.ciphers.is_empty() {
+                match p.cipher {
+                    Some(c) if policy.ciphers.contains(c) => {}
+                    Some(c) => d.error(
+                        &format!("{}.cipher", p.path),
+                        format!(
+                            "Cipher {} is not allowed for {} data (allowed: {})",
+                            c,
+                            class,
+                            policy.ciphers.join(", ")
+                        ),
+                    ),
+                    None => d.error(
+                        &format!("{}.cipher", p.path),
+                        format!("{} data in {} needs a cipher", class, self.backend),
+                    ),
+                }
+            }
+            if policy.keyRotator && p.keyRotator.is_none() {
+                d.error(
+                    &format!("{}.keyRotator", p.path),
+                    format!("{} data in {} needs a keyRotator", class, self.backend),
+                );
+            }
+            if let (Some(max), Some(max_days)) = (&policy.maxRetentionPeriod, max_days) {
+                let rpath = format!("{}.retentionPeriod", p.path);
+                match p.retentionPeriod.as_ref().map(|r| retention_days(r)) {
+                    None => d.error(
+                        &rpath,
+                        format!("{} data in {} needs a retentionPeriod", class, self.backend),
+                    ),
+                    Some(Err(e)) => d.error(&rpath, e.to_string()),
+                    Some(Ok(days)) if days > max_days => d.error(
+                        &rpath,
+                        format!(
+                            "{} data in {} can be retained for at most {}",
+                            class, self.backend, max
+                        ),
+                    ),
+                    Some(Ok(_)) => {}
+                }
+            }
+        }
+    }
+}
+
 impl DataHandling {
+    /// Record violations of the data policies
+    pub fn diagnose(&self, policies: &DataPolicies, path: &str, d: &mut Diagnostics) {
+        for (i, s) in self.stores.iter().enumerate() {
+            s.diagnose(policies, &format!("{}.stores[{}]", path, i), d);
+        }
+    }
+
     pub fn verify(&self) -> Result<()> {
         // field names must be PascalCase
         let re = Regex::new(r"^[A-Z][[:alpha:]\d]+$").unwrap();
@@ -155,3 +347,68 @@ impl DataHandling {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{retention_days, DataHandling, DataPolicies, DataPolicy, InformationClassification};
+    use crate::Diagnostics;
+
+    #[test]
+    fn retention_periods() {
+        assert_eq!(retention_days("30d").unwrap(), 30);
+        assert_eq!(retention_days("2w").unwrap(), 14);
+        assert_eq!(retention_days("6M").unwrap(), 180);
+        assert_eq!(retention_days("6 months").unwrap(), 180);
+        assert_eq!(retention_days("8y").unwrap(), 2920);
+        assert!(retention_days("6m").is_err());
+        assert!(retention_days("forever").is_err());
+        assert!(retention_days("10").is_err());
+        assert!(retention_days("1h").is_err());
+    }
+
+    #[test]
+    fn enforce_policies() {
+        let mut policies = DataPolicies::new();
+        policies.insert(InformationClassification::ConfidentialPatientData, DataPolicy {
+            encrypted: true,
+            ciphers: vec!["AES256".into()],
+            keyRotator: true,
+            maxRetentionPeriod: Some("1y".into()),
+        });
+        let mut dh: DataHandling = serde_yaml::from_str(
+            r#"
+stores:
+- backend: S3
+  informationClassification: confidentialPatientData
+  encrypted: true
+  cipher: AES256
+  keyRotator: 2w
+  retentionPeriod: 6M
+  fields:
+  - name: DateOfBirth
+  - name: ChatHistory
+    encrypted: false
+    cipher: DES
+    retentionPeriod: 2y
+- backend: MySQL
+  informationClassification: protectedInternal
+- backend: Postgres
+  informationClassification: confidentialPatientData
+"#,
+        )
+        .unwrap();
+        dh.implicits();
+        let mut d = Diagnostics::default();
+        dh.diagnose(&policies, "dataHandling", &mut d);
+        let paths = d.errors().map(|e| e.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, vec![
+            "dataHandling.stores[0].fields[1].encrypted",
+            "dataHandling.stores[0].fields[1].cipher",
+            "dataHandling.stores[0].fields[1].retentionPeriod",
+            "dataHandling.stores[2].encrypted",
+            "dataHandling.stores[2].cipher",
+            "dataHandling.stores[2].keyRotator",
+            "dataHandling.stores[2].retentionPeriod",
+        ]);
+    }
+}
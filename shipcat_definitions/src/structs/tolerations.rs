@@ -1,14 +1,15 @@
-use super::Result;
+use crate::Diagnostics;
+use schemars::JsonSchema;
 
 /// Operator for a toleraton
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub enum Operator {
     Exists,
     Equal,
 }
 
 /// Effect of a toleration
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub enum Effect {
     NoSchedule,
     NoExecute,
@@ -21,7 +22,7 @@ impl Default for Effect {
 }
 
 /// Kubernetes Tolerations parameters for a service
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct Tolerations {
     /// What key does the toleration apply to?
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -41,20 +42,24 @@ pub struct Tolerations {
 
 
 impl Tolerations {
-    pub fn verify(&self) -> Result<()> {
+    /// Record every problem with the toleration under `path`
+    pub fn diagnose(&self, path: &str, d: &mut Diagnostics) {
         match self.operator {
-            Operator::Exists => assert!(
-                self.value.is_none(),
-                "must set tolerations.value when operator is Exists"
+            Operator::Exists if self.value.is_some() => d.error(
+                &format!("{}.value", path),
+                "cannot set tolerations.value when operator is Exists",
             ),
-            Operator::Equal => assert!(
-                self.value.is_some(),
-                "cannot set tolerations.value when operator is Equal"
+            Operator::Equal if self.value.is_none() => d.error(
+                &format!("{}.value", path),
+                "must set tolerations.value when operator is Equal",
             ),
+            _ => {}
         }
         if self.effect != Effect::NoExecute && self.tolerationSeconds.is_some() {
-            bail!("cannot set tolerations.tolerationSeconds unless effect is NoExecute");
+            d.error(
+                &format!("{}.tolerationSeconds", path),
+                "cannot set tolerations.tolerationSeconds unless effect is NoExecute",
+            );
         }
-        Ok(())
     }
 }
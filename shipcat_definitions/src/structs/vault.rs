@@ -1,4 +1,6 @@
-#[derive(Serialize, Deserialize, Debug, Clone)]
+use schemars::JsonSchema;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[cfg_attr(feature = "filesystem", serde(deny_unknown_fields))]
 pub struct VaultOpts {
     /// If Vault name differs from service name
@@ -1,11 +1,12 @@
 use super::{autoscaling::AutoScaling, Container};
+use schemars::JsonSchema;
 use std::collections::BTreeMap;
 
 /// Worker for a service
 ///
 /// Essentially a side-car like object that can scale resources separately to the main pods.
 /// Useful for services that have one single side service that polls or does some work.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct Worker {
     /// Replication limits
     pub replicaCount: u32,
@@ -1,10 +1,11 @@
 use merge::Merge;
+use schemars::JsonSchema;
 
 use shipcat_definitions::structs::Authorization;
 
 use super::{util::Build, Result};
 
-#[derive(Deserialize, Default, Merge, Clone)]
+#[derive(Serialize, Deserialize, Default, Merge, Clone, JsonSchema)]
 pub struct AuthorizationSource {
     pub allowed_audiences: Option<Vec<String>>,
     pub allow_anonymous: Option<bool>,
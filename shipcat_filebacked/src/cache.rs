@@ -0,0 +1,128 @@
+use std::{
+    collections::{btree_map, BTreeMap},
+    sync::Arc,
+};
+
+use shipcat_definitions::{Config, Error, Manifest, Region, Result, ResultExt};
+
+use crate::{manifest::ManifestSource, tree::Tree};
+
+/// Every available manifest in a region, loaded once
+///
+/// Services are read and merged concurrently on the blocking thread pool,
+/// sharing one copy of the config and the region defaults.
+/// Region-wide commands should load this once instead of calling `load_manifest` per service.
+pub struct ManifestCache {
+    manifests: BTreeMap<String, Manifest>,
+}
+
+impl ManifestCache {
+    /// Load all services that are enabled and not external in a region
+    pub async fn load(conf: &Config, reg: &Region) -> Result<Self> {
+        let (cache, errs) = Self::load_partial(conf, reg).await?;
+        // return the first error, but log the rest so one run shows every broken service
+        let mut errs = errs.into_iter();
+        if let Some(first) = errs.next() {
+            for e in errs {
+                error!("{}", e);
+                debug!("{:?}", e);
+            }
+            return Err(first);
+        }
+        Ok(cache)
+    }
+
+    /// Load all services like `load`, but keep going past services that fail to load
+    ///
+    /// Returns the services that loaded along with the errors of the rest, in name order,
+    /// so that validation can report every broken service together.
+    pub async fn load_partial(conf: &Config, reg: &Region) -> Result<(Self, Vec<Error>)> {
+        let defaults = ManifestSource::default_layers(conf, reg)?;
+        let shared = Arc::new((conf.clone(), reg.clone()));
+        let handles = ManifestSource::all_names(&Tree::Worktree)?
+            .into_iter()
+            .map(|svc| {
+                let shared = shared.clone();
+                let defaults = defaults.clone();
+                tokio::task::spawn_blocking(move || ManifestSource::load_available(&svc, &shared, defaults))
+            })
+            .collect::<Vec<_>>();
+
+        // join in name order so errors are reported deterministically
+        let mut manifests = BTreeMap::new();
+        let mut errs = vec![];
+        for h in handles {
+            match h.await.chain_err(|| "Manifest loading task failed")? {
+                Ok(Some(mf)) => {
+                    manifests.insert(mf.name.clone(), mf);
+                }
+                Ok(None) => {}
+                Err(e) => errs.push(e),
+            }
+        }
+        debug!("Loaded {} manifests for {}", manifests.len(), reg.name);
+        Ok((ManifestCache { manifests }, errs))
+    }
+
+    /// The manifest of an available service
+    pub fn get(&self, service: &str) -> Option<&Manifest> {
+        self.manifests.get(service)
+    }
+
+    /// All available manifests sorted by name
+    pub fn manifests(&self) -> impl Iterator<Item = &Manifest> {
+        self.manifests.values()
+    }
+
+    /// Names of all available services
+    pub fn names(&self) -> Vec<String> {
+        self.manifests.keys().cloned().collect()
+    }
+}
+
+impl IntoIterator for ManifestCache {
+    type IntoIter = std::iter::Map<btree_map::IntoIter<String, Manifest>, fn((String, Manifest)) -> Manifest>;
+    type Item = Manifest;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.manifests.into_iter().map(|(_, mf)| mf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ManifestCache;
+    use shipcat_definitions::Config;
+    use std::{env, fs, path::Path};
+
+    fn setup() {
+        let pwd = env::current_dir().unwrap();
+        let pth = fs::canonicalize(Path::new(&pwd).join("..").join("tests")).unwrap();
+        std::env::set_current_dir(pth).unwrap();
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn load_region() {
+        setup();
+
+        let conf = Config::read().await.unwrap();
+        let region = conf.get_region("dev-uk").unwrap();
+
+        let cache = ManifestCache::load(&conf, &region).await.unwrap();
+        let available = crate::available(&conf, &region).await.unwrap();
+        let names = available.iter().map(|m| m.base.name.clone()).collect::<Vec<_>>();
+        assert_eq!(cache.names(), names);
+
+        let cached = cache.get("fake-ask").unwrap();
+        let loaded = crate::load_manifest("fake-ask", &conf, &region).await.unwrap();
+        assert_eq!(
+            serde_yaml::to_string(cached).unwrap(),
+            serde_yaml::to_string(&loaded).unwrap()
+        );
+        assert!(cache.get("external").is_none());
+
+        let (partial, errs) = ManifestCache::load_partial(&conf, &region).await.unwrap();
+        assert!(errs.is_empty());
+        assert_eq!(partial.names(), cache.names());
+    }
+}
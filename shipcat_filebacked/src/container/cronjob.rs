@@ -1,4 +1,5 @@
 use merge::Merge;
+use schemars::JsonSchema;
 
 use shipcat_definitions::{
     structs::{CronJob, JobVolumeClaim},
@@ -10,7 +11,7 @@ use std::collections::BTreeMap;
 
 use super::source::{ContainerBuildParams, ContainerSource};
 
-#[derive(Deserialize, Merge, Clone, Default)]
+#[derive(Serialize, Deserialize, Merge, Clone, Default, JsonSchema)]
 #[serde(default, rename_all = "camelCase")]
 pub struct CronJobSource {
     pub schedule: Option<String>,
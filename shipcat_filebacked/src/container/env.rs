@@ -1,11 +1,12 @@
 use merge::Merge;
+use schemars::JsonSchema;
 use std::collections::BTreeMap;
 
 use shipcat_definitions::{structs::EnvVars, Result};
 
 use crate::util::{Build, RelaxedString};
 
-#[derive(Deserialize, Clone, Default, Debug, PartialEq, Merge)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Merge, JsonSchema)]
 pub struct EnvVarsSource(BTreeMap<String, RelaxedString>);
 
 impl Build<EnvVars, ()> for EnvVarsSource {
@@ -1,10 +1,11 @@
 use regex::Regex;
+use schemars::JsonSchema;
 
 use shipcat_definitions::Result;
 
 use crate::util::Build;
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct ImageNameSource(String);
 
 impl Build<String, ()> for ImageNameSource {
@@ -26,7 +27,7 @@ impl Build<String, ()> for ImageNameSource {
     }
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct ImageTagSource(String);
 
 impl Build<String, ()> for ImageTagSource {
@@ -1,9 +1,10 @@
+use schemars::JsonSchema;
 use shipcat_definitions::{structs::Container, Result};
 
 use super::source::{ContainerBuildParams, ContainerSource};
 use crate::util::Build;
 
-#[derive(Deserialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default, JsonSchema)]
 pub struct SidecarSource(ContainerSource);
 
 impl Build<Container, ContainerBuildParams> for SidecarSource {
@@ -0,0 +1,498 @@
+use serde_yaml::Value;
+use shipcat_definitions::Result;
+use std::collections::BTreeMap;
+
+/// Canonical order of top level keys in manifest and override files
+///
+/// Follows the field order of `Manifest`, with source only keys next to what they affect.
+/// Unknown keys are kept after these, in their original order.
+const KEY_ORDER: &[&str] = &[
+    "name",
+    "extends",
+    "publiclyAccessible",
+    "external",
+    "kompassPlugin",
+    "disabled",
+    "regions",
+    "metadata",
+    "chart",
+    "imagePrefix",
+    "image",
+    "imageSize",
+    "version",
+    "command",
+    "securityContext",
+    "dataHandling",
+    "resources",
+    "replicaCount",
+    "env",
+    "secretFiles",
+    "configs",
+    "vault",
+    "httpPort",
+    "ports",
+    "externalPort",
+    "health",
+    "dependencies",
+    "destinationRules",
+    "workers",
+    "sidecars",
+    "readinessProbe",
+    "livenessProbe",
+    "lifecycle",
+    "rollingUpdate",
+    "autoScaling",
+    "tolerations",
+    "hostAliases",
+    "initContainers",
+    "volumes",
+    "volumeMounts",
+    "persistentVolumes",
+    "cronJobs",
+    "serviceAnnotations",
+    "podAnnotations",
+    "labels",
+    "kongApis",
+    "kong",
+    "gate",
+    "kafka",
+    "sourceRanges",
+    "rbac",
+    "eventStreams",
+    "kafkaResources",
+    "newrelic",
+    "sentry",
+    "upgradeNotifications",
+    "workload",
+    "prometheusAlerts",
+];
+
+/// Top level maps whose values are `RelaxedString`s
+const RELAXED_MAPS: &[&str] = &["env", "labels", "podAnnotations"];
+
+/// Top level lists of containers, whose items have an `env` map of `RelaxedString`s
+const CONTAINER_LISTS: &[&str] = &["sidecars", "workers", "initContainers", "cronJobs"];
+
+/// A top level key with its value and the comments directly above it
+pub(crate) struct Block {
+    pub(crate) key: String,
+    pub(crate) lines: Vec<String>,
+}
+
+/// Top level key of a line, if it starts one
+fn top_level_key(line: &str) -> Option<String> {
+    let first = line.chars().next()?;
+    if first.is_whitespace() || "#-".contains(first) || line.starts_with("---") {
+        return None;
+    }
+    let end = line.find(':')?;
+    Some(line[..end].trim_matches(|c| c == '"' || c == '\'').to_string())
+}
+
+/// Byte offset of a trailing comment in a value, outside of quotes
+fn comment_start(value: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in value.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if (c == '"' || c == '\'') && prev.is_whitespace() => quote = Some(c),
+            None if c == '#' && prev.is_whitespace() => return Some(i),
+            None => {}
+        }
+        prev = c;
+    }
+    None
+}
+
+/// Canonical spelling of a scalar that is read as a string
+///
+/// Plain when that reads back as the same string, double quoted otherwise,
+/// so numbers and booleans that are used as strings are quoted.
+/// Returns None for anything that is not a single line scalar.
+fn relaxed_scalar(raw: &str) -> Option<String> {
+    if raw.is_empty() || raw.starts_with(|c| "|>&*!{[".contains(c)) {
+        return None;
+    }
+    let s = match serde_yaml::from_str::<Value>(&format!("k: {}", raw)).ok()?["k"].clone() {
+        Value::String(s) => s,
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => return None,
+    };
+    let plain = !s.is_empty()
+        && s.trim() == s
+        && !s.contains('\n')
+        && comment_start(&s).is_none()
+        && !s.starts_with(&['"', '\''][..])
+        && serde_yaml::from_str::<Value>(&format!("k: {}", s))
+            .ok()
+            .map_or(false, |v| v["k"] == Value::String(s.clone()));
+    if plain {
+        Some(s)
+    } else {
+        Some(double_quoted(&s))
+    }
+}
+
+fn double_quoted(s: &str) -> String {
+    let mut res = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+/// Normalise the quoting of a `key: value` line whose value is a `RelaxedString`
+fn normalise_relaxed(line: &str) -> String {
+    let indent = line.len() - line.trim_start().len();
+    let sep = match line.find(": ") {
+        Some(i) if !line.trim_start().starts_with('#') => i,
+        _ => return line.to_string(),
+    };
+    let rest = &line[sep + 2..];
+    let (value, comment) = match comment_start(rest) {
+        Some(i) => (rest[..i].trim(), Some(&rest[i..])),
+        None => (rest.trim(), None),
+    };
+    let canonical = match relaxed_scalar(value) {
+        Some(v) => v,
+        None => return line.to_string(),
+    };
+    let mut res = format!("{}{}: {}", &line[..indent], line[indent..sep].trim(), canonical);
+    if let Some(c) = comment {
+        res = format!("{} {}", res, c);
+    }
+    res
+}
+
+/// Normalise quoting in the `env` maps of the items of a container list
+fn normalise_container_envs(lines: &mut [String]) {
+    // column of the current `env` key, and the indent of its entries
+    let mut env: Option<(usize, Option<usize>)> = None;
+    for line in lines {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - trimmed.len();
+        if let Some((column, entries)) = &mut env {
+            if indent > *column {
+                if entries.is_none() {
+                    *entries = Some(indent);
+                }
+                if *entries == Some(indent) {
+                    *line = normalise_relaxed(line);
+                }
+                continue;
+            }
+            env = None;
+        }
+        let item = trimmed.trim_start_matches("- ");
+        if let Some(rest) = item.strip_prefix("env:") {
+            if rest.trim().is_empty() || rest.trim_start().starts_with('#') {
+                env = Some((line.len() - item.len(), None));
+            }
+        }
+    }
+}
+
+/// Normalise quoting in the value lines of a block
+fn normalise_block(block: &mut Block) {
+    let relaxed_map = RELAXED_MAPS.contains(&block.key.as_str());
+    let resources = block.key == "resources";
+    let key_line = block
+        .lines
+        .iter()
+        .position(|l| top_level_key(l).is_some())
+        .unwrap_or(0);
+    if CONTAINER_LISTS.contains(&block.key.as_str()) {
+        normalise_container_envs(&mut block.lines[key_line + 1..]);
+        return;
+    }
+    let child_indent = block.lines[key_line + 1..]
+        .iter()
+        .find(|l| !l.trim().is_empty() && !l.trim_start().starts_with('#'))
+        .map(|l| l.len() - l.trim_start().len());
+    for line in &mut block.lines[key_line + 1..] {
+        let indent = line.len() - line.trim_start().len();
+        let trimmed = line.trim_start();
+        let normalise = if relaxed_map {
+            Some(indent) == child_indent
+        } else if resources {
+            // resources.{requests,limits}.{cpu,memory}
+            Some(indent) != child_indent && (trimmed.starts_with("cpu:") || trimmed.starts_with("memory:"))
+        } else {
+            false
+        };
+        if normalise {
+            *line = normalise_relaxed(line);
+        }
+    }
+}
+
+/// Split a file into its preamble, top level blocks and trailing comments
+pub(crate) fn split(data: &str) -> (Vec<String>, Vec<Block>, Vec<String>) {
+    let mut preamble = vec![];
+    let mut blocks: Vec<Block> = vec![];
+    // comment lines at column 0 that are not yet known to belong to the next key
+    let mut pending: Vec<String> = vec![];
+    for line in data.lines().map(|l| l.trim_end().to_string()) {
+        if let Some(key) = top_level_key(&line) {
+            let mut lines = std::mem::take(&mut pending);
+            // a blank line separates comments about the previous block from this one
+            if let Some(i) = lines.iter().rposition(|l| l.is_empty()) {
+                let earlier = lines.drain(..=i).collect::<Vec<_>>();
+                match blocks.last_mut() {
+                    Some(b) => b.lines.extend(earlier),
+                    None => preamble.extend(earlier),
+                }
+            }
+            lines.push(line);
+            blocks.push(Block { key, lines });
+        } else if line.is_empty() || line.starts_with('#') {
+            pending.push(line);
+        } else {
+            let target = match blocks.last_mut() {
+                Some(b) => &mut b.lines,
+                None => &mut preamble,
+            };
+            target.append(&mut pending);
+            target.push(line);
+        }
+    }
+    (preamble, blocks, pending)
+}
+
+/// Push lines dropping blank lines at the ends and collapsing runs of them
+fn push_lines(out: &mut Vec<String>, lines: Vec<String>) {
+    let start = out.len();
+    for l in lines {
+        if l.is_empty() && out.len() > start && out.last().map_or(false, |p| p.is_empty()) {
+            continue;
+        }
+        if l.is_empty() && out.len() == start {
+            continue;
+        }
+        out.push(l);
+    }
+    while out.len() > start && out.last().map_or(false, |l| l.is_empty()) {
+        out.pop();
+    }
+}
+
+/// Every scalar of a document by path, as strings so that requoting does not count as a change
+pub(crate) fn scalars(path: String, v: &Value, out: &mut BTreeMap<String, String>) {
+    match v {
+        Value::Mapping(m) => {
+            for (k, v) in m {
+                let key = serde_yaml::to_string(k).unwrap_or_default();
+                scalars(
+                    format!("{}.{}", path, key.trim_start_matches("---").trim()),
+                    v,
+                    out,
+                );
+            }
+        }
+        Value::Sequence(s) => {
+            for (i, v) in s.iter().enumerate() {
+                scalars(format!("{}[{}]", path, i), v, out);
+            }
+        }
+        Value::String(s) => {
+            out.insert(path, s.clone());
+        }
+        Value::Number(n) => {
+            out.insert(path, n.to_string());
+        }
+        Value::Bool(b) => {
+            out.insert(path, b.to_string());
+        }
+        Value::Null => {
+            out.insert(path, "~".into());
+        }
+    }
+}
+
+/// Format a manifest or override file into canonical form
+///
+/// Top level keys are sorted into `Manifest` field order, carrying the comments directly above them
+/// and a single blank line if they were separated from the key before them.
+/// `RelaxedString` values in `env` (also of sidecars, workers, init containers and cron jobs),
+/// `labels`, `podAnnotations` and `resources` get canonical quoting.
+/// Everything else is left as written.
+pub fn format_source(filename: &str, data: &str) -> Result<String> {
+    if let Err(e) = crate::check_source(filename, data) {
+        bail!("{} is not a valid {}: {}", filename, kind(filename), e);
+    }
+    let (preamble, mut blocks, trailing) = split(data);
+    for b in &mut blocks {
+        normalise_block(b);
+    }
+    // blank lines between blocks end up at the end of the block before
+    let mut blank = matches!(preamble.last(), Some(l) if l.is_empty());
+    let mut blocks = blocks
+        .into_iter()
+        .map(|b| {
+            let separated = blank;
+            blank = matches!(b.lines.last(), Some(l) if l.is_empty());
+            (separated, b)
+        })
+        .collect::<Vec<_>>();
+    let rank = |key: &str| {
+        KEY_ORDER
+            .iter()
+            .position(|k| *k == key)
+            .unwrap_or(KEY_ORDER.len())
+    };
+    blocks.sort_by_key(|(_, b)| rank(&b.key));
+
+    let mut out = vec![];
+    push_lines(&mut out, preamble);
+    for (separated, b) in blocks {
+        if separated && !out.is_empty() {
+            out.push(String::new());
+        }
+        push_lines(&mut out, b.lines);
+    }
+    if trailing.iter().any(|l| !l.is_empty()) {
+        out.push(String::new());
+        push_lines(&mut out, trailing);
+    }
+    let mut res = out.join("\n");
+    res.push('\n');
+
+    let (mut before, mut after) = (BTreeMap::new(), BTreeMap::new());
+    scalars(String::new(), &serde_yaml::from_str(data)?, &mut before);
+    scalars(String::new(), &serde_yaml::from_str(&res)?, &mut after);
+    if before != after {
+        bail!(
+            "Formatting {} would change its meaning, please report this",
+            filename
+        );
+    }
+    Ok(res)
+}
+
+fn kind(filename: &str) -> &'static str {
+    if filename == "manifest.yml" {
+        "manifest"
+    } else {
+        "override file"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_source, relaxed_scalar, KEY_ORDER};
+
+    #[test]
+    fn key_order_is_complete() {
+        let schema = serde_json::to_value(crate::manifest_schema()).unwrap();
+        for key in schema["properties"].as_object().unwrap().keys() {
+            assert!(
+                KEY_ORDER.contains(&key.as_str()),
+                "{} has no canonical position",
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn relaxed_quoting() {
+        assert_eq!(relaxed_scalar("true").unwrap(), "\"true\"");
+        assert_eq!(relaxed_scalar("2").unwrap(), "\"2\"");
+        assert_eq!(relaxed_scalar("'250m'").unwrap(), "250m");
+        assert_eq!(relaxed_scalar("\"IN_VAULT\"").unwrap(), "IN_VAULT");
+        assert_eq!(
+            relaxed_scalar("\"-Xms256m -Xmx2048m\"").unwrap(),
+            "-Xms256m -Xmx2048m"
+        );
+        assert_eq!(
+            relaxed_scalar("\"{{ base_urls.services }}/x\"").unwrap(),
+            "\"{{ base_urls.services }}/x\""
+        );
+        assert_eq!(relaxed_scalar("\"a # b\"").unwrap(), "\"a # b\"");
+        assert_eq!(relaxed_scalar("\"\"").unwrap(), "\"\"");
+        assert!(relaxed_scalar("|").is_none());
+        assert!(relaxed_scalar("{a: b}").is_none());
+    }
+
+    #[test]
+    fn format_manifest() {
+        let data = r#"---
+# fake-ask manifest
+
+env:
+  DEBUG: true
+  NAME: 'fake' # the name
+  URL: "{{ base_urls.services }}/x"
+# who to page
+metadata:
+  team: observability
+  repo: 'https://github.com/babylonhealth/shipcat'
+
+resources:
+  requests:
+    cpu: 1
+    memory: "1Gi"
+sidecars:
+- name: redis
+  env:
+    PORT: 6379
+    MODE: "cluster" # quoted
+  image: 'redis'
+- env:
+    DEBUG: 'false'
+name: fake-ask
+# trailing
+"#;
+        let formatted = format_source("manifest.yml", data).unwrap();
+        assert_eq!(
+            formatted,
+            r#"---
+# fake-ask manifest
+name: fake-ask
+# who to page
+metadata:
+  team: observability
+  repo: 'https://github.com/babylonhealth/shipcat'
+
+resources:
+  requests:
+    cpu: "1"
+    memory: 1Gi
+
+env:
+  DEBUG: "true"
+  NAME: fake # the name
+  URL: "{{ base_urls.services }}/x"
+sidecars:
+- name: redis
+  env:
+    PORT: "6379"
+    MODE: cluster # quoted
+  image: 'redis'
+- env:
+    DEBUG: "false"
+
+# trailing
+"#
+        );
+        // formatting is idempotent
+        assert_eq!(format_source("manifest.yml", &formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn format_invalid() {
+        assert!(format_source("dev-uk.yml", "replicaCount: many\n").is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use merge::Merge;
+use schemars::JsonSchema;
 use std::collections::BTreeMap;
 
 use shipcat_definitions::{
@@ -11,7 +12,7 @@ use super::{
     util::{Build, Enabled, EnabledMap},
 };
 
-#[derive(Deserialize, Default, Merge, Clone)]
+#[derive(Serialize, Deserialize, Default, Merge, Clone, JsonSchema)]
 #[serde(default)]
 pub struct KongApisSource {
     /// Default values to merge into every API
@@ -25,7 +26,7 @@ pub struct KongApisBuildParams {
     pub service: String,
     pub region: Region,
     pub kong: KongConfig,
-    // TODO: Remove Manifest.kong
+    // TODO: Remove Manifest.kong after everything has been migrated with `shipcat migrate kong-apis`
     pub single_api: Enabled<KongSource>,
 }
 
@@ -93,7 +94,7 @@ impl KongApisSource {
     }
 }
 
-#[derive(Deserialize, Default, Merge, Clone)]
+#[derive(Serialize, Deserialize, Default, Merge, Clone, JsonSchema)]
 #[serde(default, deny_unknown_fields)]
 pub struct KongSource {
     pub upstream_url: Option<String>,
@@ -107,6 +108,7 @@ pub struct KongSource {
     pub internal: Option<bool>,
     #[serde(rename = "camelCase")]
     pub publicly_accessible: Option<bool>,
+    // Legacy auth - not migrated, `jwt` has no `authorization` equivalent
     pub auth: Option<Authentication>,
     pub babylon_auth_header: Option<BabylonAuthHeader>,
     pub authorization: Enabled<AuthorizationSource>,
@@ -223,7 +225,7 @@ impl KongSource {
     }
 }
 
-#[derive(Deserialize, Default, Merge, Clone)]
+#[derive(Serialize, Deserialize, Default, Merge, Clone, JsonSchema)]
 #[serde(default, deny_unknown_fields)]
 pub struct KongRateLimitSource {
     pub per_second: Option<u32>,
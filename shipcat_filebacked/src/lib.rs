@@ -13,24 +13,78 @@ mod simple;
 pub use crate::simple::SimpleManifest;
 mod kong;
 
+mod cache;
+pub use crate::cache::ManifestCache;
+mod format;
+pub use crate::format::format_source;
 mod load;
+pub use crate::load::Provenance;
+mod migrate;
+pub use crate::migrate::{migration, Migration, MigrationTarget, MIGRATIONS};
+mod tree;
+pub use crate::tree::Tree;
 mod util;
 
-use manifest::ManifestSource;
-use shipcat_definitions::{BaseManifest, Config, Manifest, Region, Result};
+use manifest::{ManifestOverrides, ManifestSource};
+use schemars::{schema::RootSchema, schema_for};
+use shipcat_definitions::{BaseManifest, Config, Diagnostics, Manifest, Region, Result};
 
 pub async fn load_manifest(service: &str, conf: &Config, reg: &Region) -> Result<Manifest> {
-    ManifestSource::load_manifest(service, conf, reg).await
+    ManifestSource::load_manifest(service, conf, reg, &Tree::Worktree).await
+}
+
+/// Load a manifest as it was in a git revision, without touching the working tree
+///
+/// `conf` should be loaded from the same revision via `Tree::config`.
+pub async fn load_manifest_at(tree: &Tree, service: &str, conf: &Config, reg: &Region) -> Result<Manifest> {
+    ManifestSource::load_manifest(service, conf, reg, tree).await
 }
 
 pub async fn load_metadata(service: &str, conf: &Config, reg: &Region) -> Result<SimpleManifest> {
-    ManifestSource::load_metadata(service, conf, reg).await
+    ManifestSource::load_metadata(service, conf, reg, &Tree::Worktree).await
 }
 
 pub async fn all(conf: &Config) -> Result<Vec<BaseManifest>> {
-    ManifestSource::all(conf).await
+    ManifestSource::all(conf, &Tree::Worktree).await
 }
 
 pub async fn available(conf: &Config, reg: &Region) -> Result<Vec<SimpleManifest>> {
-    ManifestSource::available(conf, reg).await
+    ManifestSource::available(conf, reg, &Tree::Worktree).await
+}
+
+/// Services available in a region as of a git revision
+pub async fn available_at(tree: &Tree, conf: &Config, reg: &Region) -> Result<Vec<SimpleManifest>> {
+    ManifestSource::available(conf, reg, tree).await
+}
+
+/// Explain which source set each value of the merged manifest sources
+pub async fn explain(service: &str, conf: &Config, reg: &Region) -> Result<Vec<Provenance>> {
+    ManifestSource::explain(service, conf, reg).await
+}
+
+/// Attribute diagnostics to the service file that contributed their field
+pub fn attribute(service: &str, reg: &Region, diags: &mut Diagnostics) {
+    ManifestSource::attribute(service, reg, diags)
+}
+
+/// Syntax check the contents of a single manifest or override file
+///
+/// `manifest.yml` is parsed as a full manifest, any other file as overrides.
+/// Errors carry the yaml location when serde knows it.
+pub fn check_source(filename: &str, data: &str) -> std::result::Result<(), serde_yaml::Error> {
+    if filename == "manifest.yml" {
+        serde_yaml::from_str::<ManifestSource>(data).map(|_| ())
+    } else {
+        serde_yaml::from_str::<ManifestOverrides>(data).map(|_| ())
+    }
+}
+
+/// JSON Schema for `services/{svc}/manifest.yml`
+pub fn manifest_schema() -> RootSchema {
+    schema_for!(ManifestSource)
+}
+
+/// JSON Schema for environment and region override files like `services/{svc}/dev-uk.yml`
+pub fn overrides_schema() -> RootSchema {
+    schema_for!(ManifestOverrides)
 }
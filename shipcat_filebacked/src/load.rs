@@ -1,94 +1,208 @@
-use std::path::{Path, PathBuf};
-
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+};
+
+use super::{BaseManifest, SimpleManifest};
+use crate::{
+    manifest::{FragmentSource, ManifestDefaults, ManifestOverrides, ManifestSource},
+    tree::Tree,
+};
 use merge::Merge;
 use serde::de::DeserializeOwned;
-use shipcat_definitions::{Config, ErrorKind, Manifest, Region, Result, ResultExt};
-use walkdir::WalkDir;
-
-use super::{authorization::AuthorizationSource, util::Enabled, BaseManifest, SimpleManifest};
-use crate::manifest::{ManifestDefaults, ManifestOverrides, ManifestSource};
+use shipcat_definitions::{Config, Diagnostics, ErrorKind, Manifest, Region, Result, ResultExt};
 
 impl ManifestSource {
-    pub async fn load_manifest(service: &str, conf: &Config, reg: &Region) -> Result<Manifest> {
+    pub async fn load_manifest(service: &str, conf: &Config, reg: &Region, tree: &Tree) -> Result<Manifest> {
         let reg_name = reg.name.clone();
         let service_name = service.to_string();
 
-        let merged = ManifestSource::load_merged(service, conf, reg)
+        let merged = ManifestSource::load_merged(service, conf, reg, tree)
             .await
             .chain_err(|| ErrorKind::FailedToBuildManifest(service_name.clone(), reg_name.clone()))?;
         merged
-            .build(&(conf.clone(), reg.clone()))
-            .await
+            .build(&(conf.clone(), reg.clone()), tree)
             .chain_err(|| ErrorKind::FailedToBuildManifest(service_name.clone(), reg_name.clone()))
     }
 
-    pub async fn load_metadata(service: &str, conf: &Config, reg: &Region) -> Result<SimpleManifest> {
-        let manifest = ManifestSource::load_merged(service, conf, reg).await?;
+    pub async fn load_metadata(
+        service: &str,
+        conf: &Config,
+        reg: &Region,
+        tree: &Tree,
+    ) -> Result<SimpleManifest> {
+        let manifest = ManifestSource::load_merged(service, conf, reg, tree).await?;
         manifest.build_simple(&conf, &reg)
     }
 
-    async fn load_merged(service: &str, conf: &Config, reg: &Region) -> Result<Self> {
+    /// Load a service for a region cache, or None if it is not available in the region
+    ///
+    /// Takes the default layers of the region, so they are only computed once per region,
+    /// and borrows the config and region shared by the whole cache.
+    pub(crate) fn load_available(
+        service: &str,
+        params: &(Config, Region),
+        defaults: Vec<Layer>,
+    ) -> Result<Option<Manifest>> {
+        let (conf, reg) = params;
+        let tree = Tree::Worktree;
+        let merged = Self::merge_layers(service, defaults, reg, &tree)
+            .chain_err(|| ErrorKind::InvalidManifest(service.to_string()))?;
+        let simple = merged
+            .build_simple(conf, reg)
+            .chain_err(|| ErrorKind::InvalidManifest(service.to_string()))?;
+        if !simple.enabled || simple.external {
+            return Ok(None);
+        }
+        let manifest = merged
+            .build(params, &tree)
+            .chain_err(|| ErrorKind::FailedToBuildManifest(service.to_string(), reg.name.clone()))?;
+        Ok(Some(manifest))
+    }
+
+    async fn load_merged(service: &str, conf: &Config, reg: &Region, tree: &Tree) -> Result<Self> {
+        Self::merge_layers(service, Self::default_layers(conf, reg)?, reg, tree)
+    }
+
+    fn merge_layers(service: &str, defaults: Vec<Layer>, reg: &Region, tree: &Tree) -> Result<Self> {
+        let (mut manifest, layers) = Self::load_layers(service, defaults, reg, tree)?;
+        manifest.overrides = layers
+            .into_iter()
+            .fold(ManifestOverrides::default(), |merged, l| {
+                merged.merge(l.overrides)
+            });
+        Ok(manifest)
+    }
+
+    /// The layers every service in a region starts from
+    pub(crate) fn default_layers(conf: &Config, reg: &Region) -> Result<Vec<Layer>> {
+        Ok(vec![
+            Layer::defaults("builtin", ManifestDefaults::builtin()),
+            Layer::defaults("shipcat.conf", ManifestDefaults::from_global(conf)?),
+            Layer::defaults(
+                &format!("shipcat.conf#{}", reg.name),
+                ManifestDefaults::from_region(reg)?,
+            ),
+        ])
+    }
+
+    /// The sources of the manifest of a service in a region, in merge order
+    ///
+    /// Returns `manifest.yml` without its overrides, which are in the layer of the same name.
+    fn load_layers(
+        service: &str,
+        defaults: Vec<Layer>,
+        reg: &Region,
+        tree: &Tree,
+    ) -> Result<(Self, Vec<Layer>)> {
         let dir = Self::services_dir().join(service);
 
-        if !dir.exists() {
+        if !tree.is_dir(&dir) {
             bail!("Service folder {} does not exist", dir.display())
         }
 
-        let builtin_defaults = ManifestDefaults::builtin();
-        let global_defaults = ManifestDefaults::from_global(conf)?;
-        let regional_defaults = ManifestDefaults::from_region(reg)?;
-        let defaults = builtin_defaults.merge(global_defaults.merge(regional_defaults));
+        let mut layers = defaults;
 
         let source_path = Self::services_dir().join(service).join("manifest.yml");
         debug!("Loading service manifest from {:?}", source_path);
-        let source: ManifestSource = read_from(&source_path).await?;
-        let mut manifest = defaults.merge_source(source);
-
-        let env_path = dir.join(format!("{}.yml", reg.environment.to_string()));
-        if env_path.is_file() {
-            debug!("Loading service overrides from {:?}", env_path);
-            let env: ManifestOverrides = read_from(&env_path).await?;
-            manifest = manifest.merge_overrides(env);
+        let mut source: ManifestSource = read_from(tree, &source_path)?;
+
+        for name in fragment_order(tree, &source.extends)? {
+            let fragment_path = fragment_path(&name)?;
+            debug!("Loading manifest fragment from {:?}", fragment_path);
+            let fragment: FragmentSource = read_from(tree, &fragment_path)?;
+            layers.push(Layer {
+                name: format!("{}.yml", name),
+                overrides: fragment.overrides,
+            });
         }
 
-        let region_path = dir.join(format!("{}.yml", reg.name));
-        if region_path.is_file() {
-            debug!("Loading service overrides from {:?}", region_path);
-            let region: ManifestOverrides = read_from(&region_path).await?;
-            manifest = manifest.merge_overrides(region);
+        layers.push(Layer {
+            name: format!("services/{}/manifest.yml", service),
+            overrides: std::mem::take(&mut source.overrides),
+        });
+
+        for file in Self::override_files(reg) {
+            let path = dir.join(&file);
+            if tree.is_file(&path) {
+                debug!("Loading service overrides from {:?}", path);
+                layers.push(Layer {
+                    name: format!("services/{}/{}", service, file),
+                    overrides: read_from(tree, &path)?,
+                });
+            }
         }
 
-        Ok(manifest)
+        Ok((source, layers))
     }
 
-    fn all_names() -> Vec<String> {
-        let mut res: Vec<_> = WalkDir::new(&ManifestSource::services_dir())
-            .min_depth(1)
-            .max_depth(1)
+    /// Explain where every value of the merged sources of a manifest came from
+    ///
+    /// Replays the merge one layer at a time, recording which layer changed each value.
+    /// Lists are replaced as a whole when merging, so they are explained as a whole.
+    pub async fn explain(service: &str, conf: &Config, reg: &Region) -> Result<Vec<Provenance>> {
+        let (_, layers) = Self::load_layers(service, Self::default_layers(conf, reg)?, reg, &Tree::Worktree)?;
+        let mut merged = ManifestOverrides::default();
+        let mut current = BTreeMap::new();
+        let mut history = BTreeMap::<String, Vec<(String, serde_yaml::Value)>>::new();
+        for l in layers {
+            merged = merged.merge(l.overrides);
+            let mut next = BTreeMap::new();
+            leaves("", serde_yaml::to_value(&merged)?, &mut next);
+            for (path, value) in &next {
+                if current.get(path) != Some(value) {
+                    history
+                        .entry(path.clone())
+                        .or_default()
+                        .push((l.name.clone(), value.clone()));
+                }
+            }
+            current = next;
+        }
+        Ok(current
             .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_dir())
-            .map(|e| {
-                let mut cmps = e.path().components();
-                cmps.next(); // .
-                cmps.next(); // services
-                let svccomp = cmps.next().unwrap();
-                let svcname = svccomp.as_os_str().to_str().unwrap();
-                svcname.to_string()
+            .map(|(path, value)| {
+                let mut changes = history.remove(&path).unwrap_or_default();
+                let layer = changes.pop().map(|(l, _)| l).unwrap_or_default();
+                Provenance {
+                    path,
+                    value,
+                    layer,
+                    overrode: changes,
+                }
             })
-            .collect();
-        res.sort();
-        res
+            .collect())
+    }
+
+    /// Override files of a service in a region, in merge order
+    ///
+    /// `<environment>.yml`, then `<location>.yml` for each location the region serves
+    /// (in the order the region lists them), then `<region>.yml`.
+    fn override_files(reg: &Region) -> Vec<String> {
+        let mut names = vec![reg.environment.to_string()];
+        names.extend(reg.locations.iter().cloned());
+        names.push(reg.name.clone());
+        let mut files = vec![];
+        for n in names {
+            let f = format!("{}.yml", n);
+            if !files.contains(&f) {
+                files.push(f);
+            }
+        }
+        files
     }
 
-    pub async fn all(conf: &Config) -> Result<Vec<BaseManifest>> {
+    pub(crate) fn all_names(tree: &Tree) -> Result<Vec<String>> {
+        tree.dirs(&Self::services_dir())
+    }
+
+    pub async fn all(conf: &Config, tree: &Tree) -> Result<Vec<BaseManifest>> {
         let mut all = vec![];
-        for service in Self::all_names() {
+        for service in Self::all_names(tree)? {
             let source_path = Self::services_dir().join(&service).join("manifest.yml");
             debug!("Loading service manifest from {:?}", source_path);
-            let source: ManifestSource = read_from(&source_path)
-                .await
-                .chain_err(|| ErrorKind::InvalidManifest(service.clone()))?;
+            let source: ManifestSource =
+                read_from(tree, &source_path).chain_err(|| ErrorKind::InvalidManifest(service.clone()))?;
             let manifest = source
                 .build_base(conf)
                 .chain_err(|| ErrorKind::InvalidManifest(service.clone()))?;
@@ -97,10 +211,10 @@ impl ManifestSource {
         Ok(all)
     }
 
-    pub async fn available(conf: &Config, reg: &Region) -> Result<Vec<SimpleManifest>> {
+    pub async fn available(conf: &Config, reg: &Region, tree: &Tree) -> Result<Vec<SimpleManifest>> {
         let mut available = vec![];
-        for service in Self::all_names() {
-            let manifest = Self::load_metadata(&service, conf, reg)
+        for service in Self::all_names(tree)? {
+            let manifest = Self::load_metadata(&service, conf, reg, tree)
                 .await
                 .chain_err(|| ErrorKind::InvalidManifest(service.clone()))?;
             if manifest.enabled && !manifest.external {
@@ -113,6 +227,193 @@ impl ManifestSource {
     fn services_dir() -> PathBuf {
         Path::new(".").join("services")
     }
+
+    /// Attribute diagnostics to the service file or fragment that last set their field
+    ///
+    /// Files are checked in merge order, and the one defining the most of the field path wins.
+    /// Fields that no service file or fragment defines come from the defaults in `shipcat.conf`.
+    pub fn attribute(service: &str, reg: &Region, diags: &mut Diagnostics) {
+        let dir = Self::services_dir().join(service);
+        let tree = Tree::Worktree;
+        let extends = read_value(&tree, &dir.join("manifest.yml"))
+            .and_then(|v| serde_yaml::from_value::<Vec<String>>(v["extends"].clone()).ok())
+            .unwrap_or_default();
+        let mut files = fragment_order(&tree, &extends)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|f| format!("{}.yml", f))
+            .collect::<Vec<_>>();
+        files.push(format!("services/{}/manifest.yml", service));
+        for f in Self::override_files(reg) {
+            files.push(format!("services/{}/{}", service, f));
+        }
+        let sources = files
+            .into_iter()
+            .filter_map(|f| Some((f.clone(), read_value(&tree, Path::new(&f))?)))
+            .collect::<Vec<_>>();
+        for d in &mut diags.items {
+            if d.path.is_empty() || d.file.is_some() {
+                continue;
+            }
+            let mut best = (0, None);
+            for (file, value) in &sources {
+                let depth = path_depth(value, &d.path);
+                if depth > 0 && depth >= best.0 {
+                    best = (depth, Some(file.clone()));
+                }
+            }
+            d.file = best.1.or_else(|| Some("shipcat.conf".into()));
+        }
+    }
+}
+
+/// A source in the merge chain of a manifest
+#[derive(Clone)]
+pub(crate) struct Layer {
+    /// Where the source came from, like `services/fake-ask/dev.yml`
+    name: String,
+    overrides: ManifestOverrides,
+}
+
+impl Layer {
+    fn defaults(name: &str, defaults: ManifestDefaults) -> Self {
+        Layer {
+            name: name.into(),
+            overrides: ManifestOverrides {
+                defaults,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Where a value of the merged manifest sources came from
+#[derive(Serialize, Clone, Debug)]
+pub struct Provenance {
+    /// Field path, like `env.LOG_LEVEL`
+    pub path: String,
+    pub value: serde_yaml::Value,
+    /// The layer that set the value
+    pub layer: String,
+    /// Earlier values of the field and their layers, in merge order
+    pub overrode: Vec<(String, serde_yaml::Value)>,
+}
+
+/// Collect the non-empty leaf values of a yaml value by field path
+fn leaves(prefix: &str, value: serde_yaml::Value, out: &mut BTreeMap<String, serde_yaml::Value>) {
+    use serde_yaml::Value;
+    match value {
+        Value::Null => {}
+        Value::Mapping(m) => {
+            for (k, v) in m {
+                let key = match k {
+                    Value::String(s) => s,
+                    k => serde_yaml::to_string(&k)
+                        .unwrap_or_default()
+                        .trim_start_matches("---")
+                        .trim()
+                        .into(),
+                };
+                let path = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                leaves(&path, v, out);
+            }
+        }
+        Value::Sequence(s) if s.is_empty() => {}
+        v => {
+            out.insert(prefix.to_string(), v);
+        }
+    }
+}
+
+fn read_value(tree: &Tree, path: &Path) -> Option<serde_yaml::Value> {
+    let data = tree.read(path).ok()??;
+    serde_yaml::from_str(&data).ok()
+}
+
+/// Path of a fragment like `fragments/java-service` in the manifests repository
+fn fragment_path(name: &str) -> Result<PathBuf> {
+    let path = Path::new(name);
+    if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        bail!(
+            "Fragment {} must be a relative path inside the manifests repository",
+            name
+        );
+    }
+    Ok(Path::new(".").join(format!("{}.yml", name)))
+}
+
+/// Fragments to merge for an `extends` list, in merge order
+///
+/// The fragments a fragment extends are merged before it, and a fragment reached
+/// twice is only merged the first time. Fails on fragments extending each other.
+fn fragment_order(tree: &Tree, extends: &[String]) -> Result<Vec<String>> {
+    let mut order = vec![];
+    visit_fragments(tree, extends, &mut vec![], &mut order)?;
+    Ok(order)
+}
+
+fn visit_fragments(
+    tree: &Tree,
+    extends: &[String],
+    chain: &mut Vec<String>,
+    order: &mut Vec<String>,
+) -> Result<()> {
+    for name in extends {
+        if let Some(i) = chain.iter().position(|f| f == name) {
+            let mut cycle = chain[i..].to_vec();
+            cycle.push(name.clone());
+            bail!("Fragments extend each other in a cycle: {}", cycle.join(" -> "));
+        }
+        if order.contains(name) {
+            continue;
+        }
+        let path = fragment_path(name)?;
+        let value = match read_value(tree, &path) {
+            Some(v) => v,
+            None => bail!("Fragment {} is missing or did not parse as YAML", path.display()),
+        };
+        let parents: Vec<String> = match serde_yaml::from_value(value["extends"].clone()) {
+            Ok(p) => p,
+            Err(_) if value["extends"].is_null() => vec![],
+            Err(e) => bail!("Fragment {} has an invalid extends: {}", path.display(), e),
+        };
+        chain.push(name.clone());
+        visit_fragments(tree, &parents, chain, order)?;
+        chain.pop();
+        order.push(name.clone());
+    }
+    Ok(())
+}
+
+/// How many segments of a field path like `dependencies[1].name` exist in a yaml value
+fn path_depth(value: &serde_yaml::Value, path: &str) -> usize {
+    let mut depth = 0;
+    let mut current = value;
+    for part in path.split('.') {
+        let mut segments = part.split('[');
+        let key = segments.next().unwrap_or_default();
+        current = match current.get(key) {
+            Some(v) => v,
+            None => return depth,
+        };
+        depth += 1;
+        for idx in segments {
+            let i: usize = match idx.trim_end_matches(']').parse() {
+                Ok(i) => i,
+                Err(_) => return depth,
+            };
+            current = match current.get(i) {
+                Some(v) => v,
+                None => return depth,
+            };
+            depth += 1;
+        }
+    }
+    depth
 }
 
 impl ManifestDefaults {
@@ -131,61 +432,28 @@ impl ManifestDefaults {
     }
 
     fn from_region(reg: &Region) -> Result<Self> {
-        // TODO: Remove Region#defaults and Region#env
-        Ok(
-            match (reg.defaultsV2.clone(), reg.defaults.clone(), reg.env.clone()) {
-                (Some(defaults), None, None) => match serde_yaml::from_value(defaults) {
-                    Err(e) => bail!("Region {} defaults did not parse as YAML: {}", reg.name, e),
-                    Ok(d) => d,
-                },
-                (None, Some(defaults), env) => {
-                    let mut defs = Self::default();
-                    if let Some(authz) = defaults.kong.authorization {
-                        defs.kong_apis.defaults.authorization = Enabled {
-                            enabled: None,
-                            item: AuthorizationSource {
-                                allow_anonymous: Some(authz.allow_anonymous),
-                                allowed_audiences: Some(authz.allowed_audiences),
-                                allow_cookies: Some(authz.allow_cookies),
-                                allow_invalid_tokens: Some(authz.allow_invalid_tokens),
-                                required_scopes: Some(authz.required_scopes),
-                                enable_cookie_refresh: Some(authz.enable_cookie_refresh),
-                                refresh_auth_service: authz.refresh_auth_service,
-                                refresh_body_refresh_token_key: authz.refresh_body_refresh_token_key,
-                                refresh_cookie_domain: authz.refresh_cookie_domain,
-                                refresh_max_age_sec: authz.refresh_max_age_sec,
-                                refresh_http_timeout_msec: authz.refresh_http_timeout_msec,
-                                refresh_renew_before_expiry_sec: authz.refresh_renew_before_expiry_sec,
-                            },
-                        };
-                    }
-                    defs.kong.item.authorization.enabled = Some(defaults.kong.authorizationEnabled);
-                    if let Some(env) = env {
-                        defs.env = env.into();
-                    }
-                    defs
-                }
-                (Some(_), Some(_), _) => bail!("Region#defaultsV2 and Region#default are mutually exclusive"),
-                (Some(_), _, Some(_)) => bail!("Region#defaultsV2 and Region#env are mutually exclusive"),
-                (None, None, env) => {
-                    let mut defs = Self::default();
-                    if let Some(env) = env {
-                        defs.env = env.into();
-                    }
-                    defs
-                }
+        if reg.defaults.is_some() || reg.env.is_some() {
+            bail!(
+                "Region {} uses the legacy defaults or env, run `shipcat migrate region-defaults`",
+                reg.name
+            );
+        }
+        match reg.defaultsV2.clone() {
+            None => Ok(Self::default()),
+            Some(defaults) => match serde_yaml::from_value(defaults) {
+                Err(e) => bail!("Region {} defaults did not parse as YAML: {}", reg.name, e),
+                Ok(d) => Ok(d),
             },
-        )
+        }
     }
 }
 
-async fn read_from<T: DeserializeOwned>(path: &PathBuf) -> Result<T> {
-    use tokio::fs;
+fn read_from<T: DeserializeOwned>(tree: &Tree, path: &Path) -> Result<T> {
     trace!("Reading manifest in {}", path.display());
-    if !path.exists() {
-        bail!("Manifest file {} does not exist", path.display())
-    }
-    let data = fs::read_to_string(&path).await?;
+    let data = match tree.read(path)? {
+        Some(d) => d,
+        None => bail!("Manifest file {} does not exist", path.display()),
+    };
     if data.is_empty() {
         bail!("Manifest file {} is empty", path.display());
     }
@@ -199,8 +467,8 @@ async fn read_from<T: DeserializeOwned>(path: &PathBuf) -> Result<T> {
 mod tests {
     use std::{env, fs, path::Path};
 
-    use super::ManifestSource;
-    use shipcat_definitions::Config;
+    use super::{fragment_order, ManifestSource, Tree};
+    use shipcat_definitions::{Config, Diagnostics};
 
     fn setup() {
         let pwd = env::current_dir().unwrap();
@@ -215,10 +483,28 @@ mod tests {
         let conf = Config::read().await.unwrap();
         let region = conf.get_region("dev-uk").unwrap();
 
-        let manifest = ManifestSource::load_manifest("fake-ask", &conf, &region)
+        let manifest = ManifestSource::load_manifest("fake-ask", &conf, &region, &Tree::Worktree)
+            .await
+            .unwrap();
+        assert_eq!(manifest.name, "fake-ask".to_string());
+    }
+
+    #[tokio::test]
+    async fn load_fake_ask_at_revision() {
+        setup();
+
+        let tree = Tree::revision("HEAD").unwrap();
+        let conf = tree.config().unwrap();
+        let region = conf.get_region("dev-uk").unwrap();
+
+        let manifest = ManifestSource::load_manifest("fake-ask", &conf, &region, &tree)
             .await
             .unwrap();
         assert_eq!(manifest.name, "fake-ask".to_string());
+        assert!(manifest.configs.is_some());
+
+        let available = ManifestSource::available(&conf, &region, &tree).await.unwrap();
+        assert!(available.iter().any(|m| m.base.name == "fake-ask"));
     }
 
     #[tokio::test]
@@ -228,7 +514,7 @@ mod tests {
         let conf = Config::read().await.unwrap();
         let region = conf.get_region("dev-uk").unwrap();
 
-        let manifest = ManifestSource::load_metadata("fake-ask", &conf, &region)
+        let manifest = ManifestSource::load_metadata("fake-ask", &conf, &region, &Tree::Worktree)
             .await
             .unwrap();
         assert_eq!(manifest.base.name, "fake-ask".to_string());
@@ -236,13 +522,151 @@ mod tests {
         assert_eq!(manifest.image, Some("quay.io/babylonhealth/fake-ask".into()));
     }
 
+    #[tokio::test]
+    async fn attribute_diagnostics() {
+        setup();
+
+        let conf = Config::read().await.unwrap();
+        let region = conf.get_region("dev-uk").unwrap();
+
+        let mut diags = Diagnostics::default();
+        diags.error("resources", "bad resources");
+        diags.error("env.MODE", "bad env");
+        diags.error("env.EXTRA_URL", "bad env");
+        diags.error("dependencies[0].name", "bad dependency");
+        diags.error("namespace", "bad namespace");
+        diags.error("", "bad manifest");
+        ManifestSource::attribute("fake-ask", &region, &mut diags);
+
+        let files = diags.items.iter().map(|d| d.file.as_deref()).collect::<Vec<_>>();
+        assert_eq!(files, vec![
+            Some("services/fake-ask/manifest.yml"),
+            Some("services/fake-ask/dev.yml"),
+            Some("services/fake-ask/dev-uk.yml"),
+            Some("services/fake-ask/manifest.yml"),
+            Some("shipcat.conf"),
+            None,
+        ]);
+    }
+
+    #[tokio::test]
+    async fn load_fragments() {
+        setup();
+
+        let conf = Config::read().await.unwrap();
+        let region = conf.get_region("dev-uk").unwrap();
+
+        let manifest = ManifestSource::load_manifest("fake-storage", &conf, &region, &Tree::Worktree)
+            .await
+            .unwrap();
+        // manifest values win over fragments, later fragments win over earlier ones
+        assert_eq!(manifest.env.plain["INSTANCE_TYPE"], "web");
+        assert_eq!(manifest.env.plain["JAVA_OPTS"], "-Xms256m -Xmx512m");
+        assert_eq!(manifest.labels["runtime"], "jvm");
+        assert_eq!(manifest.labels["tier"], "backend");
+
+        let mut diags = Diagnostics::default();
+        diags.error("labels.tier", "bad label");
+        diags.error("labels.runtime", "bad label");
+        diags.error("env.INSTANCE_TYPE", "bad env");
+        ManifestSource::attribute("fake-storage", &region, &mut diags);
+        let files = diags.items.iter().map(|d| d.file.as_deref()).collect::<Vec<_>>();
+        assert_eq!(files, vec![
+            Some("fragments/backend.yml"),
+            Some("fragments/java-service.yml"),
+            Some("services/fake-storage/manifest.yml"),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn load_location_overrides() {
+        setup();
+
+        let conf = Config::read().await.unwrap();
+        let region = conf.get_region("dev-uk").unwrap();
+        assert_eq!(ManifestSource::override_files(&region), vec![
+            "dev.yml",
+            "uk.yml",
+            "space.yml",
+            "dev-uk.yml"
+        ]);
+
+        let manifest = ManifestSource::load_manifest("fake-storage", &conf, &region, &Tree::Worktree)
+            .await
+            .unwrap();
+        assert_eq!(manifest.env.plain["DATA_RESIDENCY"], "uk");
+        assert_eq!(manifest.env.plain["RAILS_ENV"], "development");
+
+        let mut diags = Diagnostics::default();
+        diags.error("env.DATA_RESIDENCY", "bad env");
+        diags.error("env.RAILS_ENV", "bad env");
+        ManifestSource::attribute("fake-storage", &region, &mut diags);
+        let files = diags.items.iter().map(|d| d.file.as_deref()).collect::<Vec<_>>();
+        assert_eq!(files, vec![
+            Some("services/fake-storage/uk.yml"),
+            Some("services/fake-storage/dev-uk.yml"),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn explain_provenance() {
+        setup();
+
+        let conf = Config::read().await.unwrap();
+        let region = conf.get_region("dev-uk").unwrap();
+
+        let explained = ManifestSource::explain("fake-storage", &conf, &region)
+            .await
+            .unwrap();
+        let find = |path: &str| explained.iter().find(|p| p.path == path).unwrap();
+
+        let instance = find("env.INSTANCE_TYPE");
+        assert_eq!(instance.value, serde_yaml::Value::from("web"));
+        assert_eq!(instance.layer, "services/fake-storage/manifest.yml");
+        assert_eq!(instance.overrode, vec![(
+            "fragments/java-service.yml".to_string(),
+            "jvm".into()
+        )]);
+
+        let rails = find("env.RAILS_ENV");
+        assert_eq!(rails.layer, "services/fake-storage/dev-uk.yml");
+        assert_eq!(rails.overrode[0].0, "services/fake-storage/uk.yml");
+
+        assert_eq!(find("chart").layer, "shipcat.conf");
+        assert_eq!(find("env.GLOBAL_EVAR").layer, "shipcat.conf#dev-uk");
+        assert_eq!(find("kongApis.defaults.ip_rate_limits.enabled").layer, "builtin");
+        // lists are explained as a whole
+        assert_eq!(find("sidecars").layer, "services/fake-storage/manifest.yml");
+        assert!(explained.iter().all(|p| !p.path.starts_with("sidecars.")));
+    }
+
+    #[test]
+    fn fragment_cycles() {
+        setup();
+
+        let order = fragment_order(&Tree::Worktree, &[
+            "fragments/java-service".into(),
+            "fragments/backend".into(),
+        ])
+        .unwrap();
+        assert_eq!(order, vec!["fragments/backend", "fragments/java-service"]);
+
+        let err = fragment_order(&Tree::Worktree, &["fragments/cycle-a".into()]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Fragments extend each other in a cycle: fragments/cycle-a -> fragments/cycle-b -> fragments/cycle-a"
+        );
+        assert!(fragment_order(&Tree::Worktree, &["../fragments/backend".into()]).is_err());
+        assert!(fragment_order(&Tree::Worktree, &["fragments/nonexistent".into()]).is_err());
+    }
+
     #[tokio::test]
     async fn all() {
         setup();
 
         let conf = Config::read().await.unwrap();
 
-        let all = ManifestSource::all(&conf).await.unwrap();
+        let all = ManifestSource::all(&conf, &Tree::Worktree).await.unwrap();
 
         let svc = &all[0];
         assert_eq!(svc.name, "external");
@@ -264,7 +688,9 @@ mod tests {
         let conf = Config::read().await.unwrap();
         let region = conf.get_region("dev-uk").unwrap();
 
-        let available = ManifestSource::available(&conf, &region).await.unwrap();
+        let available = ManifestSource::available(&conf, &region, &Tree::Worktree)
+            .await
+            .unwrap();
         assert_eq!(available.len(), 2);
 
         let manifest = &available[0];
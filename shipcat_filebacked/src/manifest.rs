@@ -1,4 +1,5 @@
 use merge::Merge;
+use schemars::JsonSchema;
 use std::collections::BTreeMap;
 
 use shipcat_definitions::{
@@ -19,12 +20,13 @@ use super::{
     kong::{KongApisBuildParams, KongApisSource, KongSource},
     newrelic_source::NewrelicSource,
     sentry_source::SentrySource,
+    tree::Tree,
     util::{Build, Enabled, RelaxedString, Require},
     SimpleManifest,
 };
 
 /// Main manifest, deserialized from `manifest.yml`
-#[derive(Deserialize, Default)]
+#[derive(Deserialize, Default, JsonSchema)]
 #[serde(default, rename_all = "camelCase")]
 pub struct ManifestSource {
     pub name: Option<String>,
@@ -32,13 +34,26 @@ pub struct ManifestSource {
     pub disabled: bool,
     pub regions: Vec<String>,
     pub metadata: Option<Metadata>,
+    /// Shared fragments merged underneath this manifest, like `fragments/java-service`
+    pub extends: Vec<String>,
+
+    #[serde(flatten)]
+    pub overrides: ManifestOverrides,
+}
+
+/// Shared manifest fragment, deserialized from `fragments/java-service.yml` etc.
+#[derive(Deserialize, Default, JsonSchema)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FragmentSource {
+    /// Fragments merged underneath this fragment
+    pub extends: Vec<String>,
 
     #[serde(flatten)]
     pub overrides: ManifestOverrides,
 }
 
 /// Manifest overrides, deserialized from `dev-uk.yml`/`prod.yml` etc.
-#[derive(Deserialize, Default, Merge, Clone)]
+#[derive(Serialize, Deserialize, Default, Merge, Clone, JsonSchema)]
 #[serde(default, deny_unknown_fields, rename_all = "camelCase")]
 pub struct ManifestOverrides {
     pub workload: Option<PrimaryWorkload>,
@@ -95,7 +110,7 @@ pub struct ManifestOverrides {
 }
 
 /// Global/regional manifest defaults, deserialized from `shipcat.conf` etc.
-#[derive(Deserialize, Default, Merge, Clone)]
+#[derive(Serialize, Deserialize, Default, Merge, Clone, JsonSchema)]
 #[serde(default, deny_unknown_fields, rename_all = "camelCase")]
 pub struct ManifestDefaults {
     pub image_prefix: Option<String>,
@@ -103,22 +118,23 @@ pub struct ManifestDefaults {
     pub replica_count: Option<u32>,
     pub env: EnvVarsSource,
     pub kong_apis: KongApisSource,
-    // TODO: Migrate to kong_apis
+    // TODO: Remove after everything has been migrated with `shipcat migrate kong-apis`
     pub kong: Enabled<KongSource>,
 }
 
 // impl Build<Manifest, (Config, Region)> - but no need to have this as a trait
 impl ManifestSource {
     /// Build a Manifest from a ManifestSource, validating and mutating properties.
-    pub async fn build(self, (conf, region): &(Config, Region)) -> Result<Manifest> {
+    pub fn build(self, (conf, region): &(Config, Region), tree: &Tree) -> Result<Manifest> {
         let simple = self.build_simple(conf, region)?;
         let name = simple.base.name;
         let data_handling = self.build_data_handling();
         let kafka = self.build_kafka(&name, region);
-        let configs = self.build_configs(&name).await?;
+        let configs = self.build_configs(&name, tree)?;
 
         let overrides = self.overrides;
         let defaults = overrides.defaults;
+        let gate_public = overrides.gate.as_ref().map(|g| g.public);
 
         let container_build_params = ContainerBuildParams {
             main_envs: defaults.env.clone(),
@@ -133,7 +149,7 @@ impl ManifestSource {
 
         Ok(Manifest {
             name,
-            publiclyAccessible: overrides.publicly_accessible.unwrap_or_default(),
+            publiclyAccessible: overrides.publicly_accessible.or(gate_public).unwrap_or_default(),
             kompass_plugin: overrides.kompass_plugin.unwrap_or_default(),
             // TODO: Skip most validation if true
             external: simple.external,
@@ -209,6 +225,7 @@ impl ManifestSource {
             environment: region.environment.to_string(),
             namespace: region.namespace.clone(),
             uid: Default::default(),
+            imageDigest: Default::default(),
             secrets: Default::default(),
             state: Default::default(),
             workload: overrides.workload.unwrap_or_default(),
@@ -329,35 +346,29 @@ impl ManifestSource {
     }
 
     // TODO: Extract ConfigsSource
-    async fn build_configs(&self, service: &str) -> Result<Option<ConfigMap>> {
+    fn build_configs(&self, service: &str, tree: &Tree) -> Result<Option<ConfigMap>> {
         let original = &self.overrides.configs;
         if original.is_none() {
             return Ok(None);
         }
         let mut configs = original.clone().unwrap();
         for f in &mut configs.files {
-            f.value = Some(read_template_file(service, &f.name).await?);
+            f.value = Some(read_template_file(service, &f.name, tree)?);
         }
         Ok(Some(configs))
     }
-
-    pub(crate) fn merge_overrides(mut self, other: ManifestOverrides) -> Self {
-        self.overrides = self.overrides.merge(other);
-        self
-    }
 }
 
-async fn read_template_file(svc: &str, tmpl: &str) -> Result<String> {
+fn read_template_file(svc: &str, tmpl: &str, tree: &Tree) -> Result<String> {
     use std::path::Path;
-    use tokio::fs;
     // try to read file from ./services/{svc}/{tmpl} into `tpl` sting
     let pth = Path::new(".").join("services").join(svc).join(tmpl);
     let gpth = Path::new(".").join("templates").join(tmpl);
-    let found_pth = if pth.exists() {
+    let found_pth = if tree.is_file(&pth) {
         debug!("Reading template in {}", pth.display());
         pth
     } else {
-        if !gpth.exists() {
+        if !tree.is_file(&gpth) {
             bail!(
                 "Template {} does not exist in neither {} nor {}",
                 tmpl,
@@ -369,14 +380,9 @@ async fn read_template_file(svc: &str, tmpl: &str) -> Result<String> {
         gpth
     };
     // read the template - should work now
-    let data = fs::read_to_string(&found_pth).await?;
-    Ok(data)
-}
-
-impl ManifestDefaults {
-    pub(crate) fn merge_source(self, mut other: ManifestSource) -> ManifestSource {
-        other.overrides.defaults = self.merge(other.overrides.defaults);
-        other
+    match tree.read(&found_pth)? {
+        Some(data) => Ok(data),
+        None => bail!("Template {} could not be read", found_pth.display()),
     }
 }
 
@@ -424,4 +430,36 @@ mod tests {
         expected_env.insert("c", "override-c");
         assert_eq!(merged.env, expected_env.into());
     }
+
+    #[test]
+    fn schemas() {
+        let mf = serde_json::to_value(crate::manifest_schema()).unwrap();
+        let props = mf["properties"].as_object().unwrap();
+        // top level and flattened override/default properties are all present
+        for k in &[
+            "name",
+            "regions",
+            "metadata",
+            "workload",
+            "kongApis",
+            "env",
+            "imagePrefix",
+        ] {
+            assert!(props.contains_key(*k), "manifest schema has {}", k);
+        }
+        // doc comments are carried over
+        assert_eq!(
+            mf["description"],
+            "Main manifest, deserialized from `manifest.yml`"
+        );
+
+        let ov = serde_json::to_value(crate::overrides_schema()).unwrap();
+        let props = ov["properties"].as_object().unwrap();
+        assert!(props.contains_key("kongApis"));
+        assert!(!props.contains_key("regions"), "regions cannot be overridden");
+        assert_eq!(ov["additionalProperties"], false);
+        // kong is an Enabled wrapper
+        let kong = &ov["definitions"]["Enabled_KongSource"]["properties"];
+        assert!(kong["enabled"].is_object());
+    }
 }
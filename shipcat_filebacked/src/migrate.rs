@@ -0,0 +1,789 @@
+use serde_yaml::Value;
+use shipcat_definitions::{Config, Manifest, Result, ResultExt};
+use std::{collections::BTreeMap, path::PathBuf};
+
+use crate::{
+    format::{scalars, split, Block},
+    manifest::{ManifestDefaults, ManifestSource},
+    tree::Tree,
+};
+
+/// Which files a migration rewrites
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MigrationTarget {
+    /// `shipcat.conf`
+    Config,
+    /// Manifest and override files in `services/*/`
+    Services,
+}
+
+/// A check of the source files of a service by path
+type SourcesCheck = fn(&BTreeMap<PathBuf, String>) -> Result<()>;
+
+/// A registered rewrite of deprecated syntax
+///
+/// Rewrites work on the text of a file so comments survive,
+/// and are idempotent: a migrated file is returned unchanged.
+pub struct Migration {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub target: MigrationTarget,
+    /// Rewrite of the file of a service (empty for `shipcat.conf`)
+    rewrite: fn(&str, &str) -> Result<String>,
+    /// Check of all the source files of a service, before any of them are rewritten
+    sources: Option<SourcesCheck>,
+    /// What rewriting a service must leave unchanged in its merged manifest, in every region
+    preserves: Option<fn(&Manifest) -> String>,
+}
+
+impl Migration {
+    /// Rewrite the contents of a file of a service
+    ///
+    /// The service is ignored by migrations of `shipcat.conf`.
+    pub fn apply(&self, service: &str, data: &str) -> Result<String> {
+        (self.rewrite)(service, data)
+    }
+
+    /// Check the rewritten files of a service against all of its layers
+    ///
+    /// Files are rewritten one at a time, so this loads the merged manifest of the service
+    /// in every region it is enabled in with `files` (every source file of the service)
+    /// and again with the `rewritten` ones, and compares them.
+    pub async fn check_service(
+        &self,
+        service: &str,
+        files: &BTreeMap<PathBuf, String>,
+        rewritten: &BTreeMap<PathBuf, String>,
+        conf: &Config,
+    ) -> Result<()> {
+        if let Some(check) = self.sources {
+            check(files).chain_err(|| format!("Cannot migrate {}", service))?;
+        }
+        let preserves = match self.preserves {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let mut after = files.clone();
+        after.extend(rewritten.clone());
+        let (before, after) = (Tree::overlay(files.clone()), Tree::overlay(after));
+        for reg in conf.get_regions() {
+            if !ManifestSource::load_metadata(service, conf, &reg, &before)
+                .await?
+                .enabled
+            {
+                continue;
+            }
+            let old = preserves(&ManifestSource::load_manifest(service, conf, &reg, &before).await?);
+            let new = ManifestSource::load_manifest(service, conf, &reg, &after)
+                .await
+                .chain_err(|| {
+                    format!(
+                        "Migrating {} would break its manifest in {}, migrate it by hand",
+                        service, reg.name
+                    )
+                })?;
+            let new = preserves(&new);
+            if old != new {
+                bail!(
+                    "Migrating {} would change `{}` to `{}` in {}, migrate it by hand",
+                    service,
+                    old,
+                    new,
+                    reg.name
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// All migrations, in the order they should be run
+pub static MIGRATIONS: &[Migration] = &[
+    Migration {
+        name: "region-defaults",
+        description: "Move the legacy region `env` and `defaults.kong` authorization into `defaultsV2`",
+        target: MigrationTarget::Config,
+        rewrite: region_defaults,
+        sources: None,
+        preserves: None,
+    },
+    Migration {
+        name: "gate-public",
+        description: "Move `publiclyAccessible` into `gate.public` for services with a gate",
+        target: MigrationTarget::Services,
+        rewrite: gate_public,
+        sources: Some(gate_public_layers),
+        preserves: Some(|mf| format!("publiclyAccessible: {}", mf.publiclyAccessible)),
+    },
+    Migration {
+        name: "kong-apis",
+        description: "Move the single API `kong` block of services into `kongApis`",
+        target: MigrationTarget::Services,
+        rewrite: kong_apis,
+        sources: None,
+        // region `kong` defaults only apply to the single API, so services using them are refused
+        preserves: Some(|mf| format!("kongApis: {:?}", mf.kongApis)),
+    },
+];
+
+/// Find a registered migration by name
+pub fn migration(name: &str) -> Result<&'static Migration> {
+    match MIGRATIONS.iter().find(|m| m.name == name) {
+        Some(m) => Ok(m),
+        None => {
+            let names = MIGRATIONS.iter().map(|m| m.name).collect::<Vec<_>>();
+            bail!("Unknown migration {}, expected one of {}", name, names.join(", "))
+        }
+    }
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Indent lines by `n` spaces, leaving blank lines blank
+fn indent(lines: &[String], n: usize) -> Vec<String> {
+    lines
+        .iter()
+        .map(|l| {
+            if l.is_empty() {
+                l.clone()
+            } else {
+                format!("{}{}", " ".repeat(n), l)
+            }
+        })
+        .collect()
+}
+
+/// Remove up to `n` spaces of indentation from lines
+fn dedent(lines: &[String], n: usize) -> Vec<String> {
+    lines
+        .iter()
+        .map(|l| l[indent_of(l).min(n)..].to_string())
+        .collect()
+}
+
+fn is_content(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    !trimmed.is_empty() && !trimmed.starts_with('#')
+}
+
+/// Index of the `key:` line of a block, after the comments above it
+fn key_line(block: &Block) -> usize {
+    block.lines.iter().position(|l| is_content(l)).unwrap_or(0)
+}
+
+/// The children of a block as a top level document, refusing inline values
+fn children(block: &Block) -> Result<(Vec<String>, Vec<Block>, Vec<String>)> {
+    let k = key_line(block);
+    let header = &block.lines[k];
+    let value = header[header.find(':').map_or(header.len(), |i| i + 1)..].trim();
+    if !value.is_empty() && !value.starts_with('#') {
+        bail!("{} is written inline, it has to be migrated by hand", block.key);
+    }
+    let rest = &block.lines[k + 1..];
+    let depth = rest.iter().find(|l| is_content(l)).map_or(0, |l| indent_of(l));
+    Ok(split(&dedent(rest, depth).join("\n")))
+}
+
+fn join(preamble: Vec<String>, blocks: Vec<Block>, trailing: Vec<String>) -> Vec<String> {
+    let mut lines = preamble;
+    for b in blocks {
+        lines.extend(b.lines);
+    }
+    lines.extend(trailing);
+    lines
+}
+
+/// Compare the scalars of a file before and after a rewrite, with moved paths renamed
+fn check_meaning(
+    before: &str,
+    after: &str,
+    moved: impl Fn(&str) -> String,
+    added: impl Fn(&str, &str) -> bool,
+) -> Result<()> {
+    let (mut old, mut new) = (BTreeMap::new(), BTreeMap::new());
+    scalars(String::new(), &serde_yaml::from_str(before)?, &mut old);
+    scalars(String::new(), &serde_yaml::from_str(after)?, &mut new);
+    let expected = old
+        .into_iter()
+        .map(|(k, v)| (moved(&k), v))
+        .collect::<BTreeMap<_, _>>();
+    for (k, v) in &new {
+        let ok = match expected.get(k) {
+            Some(e) => e == v,
+            None => added(k, v),
+        };
+        if !ok {
+            bail!("Migrating would change the value of {}, please report this", k);
+        }
+    }
+    if let Some(k) = expected.keys().find(|k| !new.contains_key(*k)) {
+        bail!("Migrating would drop {}, please report this", k);
+    }
+    Ok(())
+}
+
+/// Rewrite the legacy `env` and `defaults` of every region into `defaultsV2`
+///
+/// `defaults.kong.authorization` becomes `defaultsV2.kongApis.defaults.authorization`
+/// and `defaults.kong.authorizationEnabled` becomes `defaultsV2.kong.authorization.enabled`,
+/// which the legacy loader always set.
+fn region_defaults(_service: &str, data: &str) -> Result<String> {
+    let (preamble, mut blocks, trailing) = split(data);
+    let regions = match blocks.iter_mut().find(|b| b.key == "regions") {
+        Some(b) => b,
+        None => return Ok(data.to_string()),
+    };
+    let k = key_line(regions);
+    let mut items: Vec<Vec<String>> = vec![];
+    let mut head = regions.lines[..=k].to_vec();
+    let item_indent = regions.lines[k + 1..]
+        .iter()
+        .find(|l| l.trim_start().starts_with('-'))
+        .map(|l| indent_of(l));
+    for line in &regions.lines[k + 1..] {
+        let starts_item = Some(indent_of(line)) == item_indent && line.trim_start().starts_with('-');
+        match items.last_mut() {
+            Some(item) if !starts_item => item.push(line.clone()),
+            None if !starts_item => head.push(line.clone()),
+            _ => items.push(vec![line.clone()]),
+        }
+    }
+    let item_indent = match item_indent {
+        Some(i) => i,
+        None => return Ok(data.to_string()),
+    };
+
+    let mut changed = false;
+    for item in &mut items {
+        // turn `- name: x` into a top level mapping
+        let mut lines = item.clone();
+        lines[0] = format!(
+            "{}  {}",
+            &lines[0][..item_indent],
+            &lines[0][item_indent + 1..].trim_start()
+        );
+        // blank lines between regions do not survive a split
+        let blanks = lines.iter().rev().take_while(|l| l.is_empty()).count();
+        lines.truncate(lines.len() - blanks);
+        let lines = dedent(&lines, item_indent + 2);
+        if let Some(migrated) = migrate_region(&lines)? {
+            let mut migrated = indent(&migrated, item_indent + 2);
+            migrated[0] = format!("{}- {}", " ".repeat(item_indent), migrated[0].trim_start());
+            migrated.extend(vec![String::new(); blanks]);
+            *item = migrated;
+            changed = true;
+        }
+    }
+    if !changed {
+        return Ok(data.to_string());
+    }
+    regions.lines = head;
+    for item in items {
+        regions.lines.extend(item);
+    }
+    let mut res = join(preamble, blocks, trailing).join("\n");
+    res.push('\n');
+
+    for region in serde_yaml::from_str::<Value>(&res)?["regions"]
+        .as_sequence()
+        .cloned()
+        .unwrap_or_default()
+    {
+        if region["defaultsV2"].is_null() {
+            continue;
+        }
+        if let Err(e) = serde_yaml::from_value::<ManifestDefaults>(region["defaultsV2"].clone()) {
+            bail!(
+                "Migrated defaultsV2 of {} does not parse: {}",
+                region["name"].as_str().unwrap_or_default(),
+                e
+            );
+        }
+    }
+    check_meaning(data, &res, region_defaults_path, |k, v| {
+        k.ends_with("].defaultsV2.kong.authorization.enabled") && v == "false"
+    })?;
+    Ok(res)
+}
+
+/// Where a scalar of a region ends up after `region_defaults`
+fn region_defaults_path(path: &str) -> String {
+    if !path.starts_with(".regions[") {
+        return path.to_string();
+    }
+    let i = match path.find("].") {
+        Some(i) => i + 2,
+        None => return path.to_string(),
+    };
+    let (item, key) = path.split_at(i);
+    let moved = if key.starts_with("env.") {
+        format!("defaultsV2.{}", key)
+    } else if key.starts_with("defaults.kong.authorization.") {
+        key.replacen("defaults.kong.", "defaultsV2.kongApis.defaults.", 1)
+    } else if key == "defaults.kong.authorizationEnabled" {
+        "defaultsV2.kong.authorization.enabled".to_string()
+    } else {
+        key.to_string()
+    };
+    format!("{}{}", item, moved)
+}
+
+/// Migrate a single region written as a top level mapping, if it uses legacy keys
+fn migrate_region(lines: &[String]) -> Result<Option<Vec<String>>> {
+    let (preamble, blocks, trailing) = split(&lines.join("\n"));
+    let position = match blocks.iter().position(|b| b.key == "env" || b.key == "defaults") {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+    let name = blocks
+        .iter()
+        .find(|b| b.key == "name")
+        .and_then(|b| serde_yaml::from_str::<Value>(&b.lines.join("\n")).ok())
+        .and_then(|v| v["name"].as_str().map(String::from))
+        .unwrap_or_default();
+    if blocks.iter().any(|b| b.key == "defaultsV2") {
+        bail!(
+            "Region {} has both legacy defaults and defaultsV2, merge them by hand",
+            name
+        );
+    }
+
+    let mut comments = vec![];
+    let mut body = vec![];
+    let mut has_kong = false;
+    let mut rest = vec![];
+    for b in blocks {
+        let k = key_line(&b);
+        match b.key.as_str() {
+            "env" => {
+                comments.extend(b.lines[..k].to_vec());
+                body.extend(indent(&b.lines[k..], 2));
+            }
+            "defaults" => {
+                comments.extend(b.lines[..k].to_vec());
+                let (pre, defaults, trail) = children(&b)?;
+                comments.extend(pre.into_iter().chain(trail).filter(|l| !l.is_empty()));
+                for d in defaults {
+                    if d.key != "kong" {
+                        bail!("Region {} has an unknown defaults.{}", name, d.key);
+                    }
+                    let dk = key_line(&d);
+                    comments.extend(d.lines[..dk].to_vec());
+                    body.extend(migrate_kong_defaults(&d, &name)?);
+                    has_kong = true;
+                }
+                if !has_kong {
+                    body.extend(vec![
+                        "  kong:".to_string(),
+                        "    authorization:".to_string(),
+                        "      enabled: false".to_string(),
+                    ]);
+                }
+            }
+            _ => rest.push(b),
+        }
+    }
+    let mut lines = comments.into_iter().filter(|l| !l.is_empty()).collect::<Vec<_>>();
+    lines.push("defaultsV2:".to_string());
+    lines.extend(body);
+    // the first key shares its line with the `-` of the region
+    rest.insert(position.max(1).min(rest.len()), Block {
+        key: "defaultsV2".into(),
+        lines,
+    });
+    Ok(Some(join(preamble, rest, trailing)))
+}
+
+/// The `kongApis` and `kong` parts of `defaultsV2` for a legacy `defaults.kong`
+fn migrate_kong_defaults(kong: &Block, region: &str) -> Result<Vec<String>> {
+    let (pre, fields, trail) = children(kong)?;
+    let mut apis = vec![];
+    let mut enabled = vec![];
+    for f in fields {
+        let k = key_line(&f);
+        match f.key.as_str() {
+            "authorization" => {
+                apis.push("  kongApis:".to_string());
+                apis.push("    defaults:".to_string());
+                apis.extend(indent(&f.lines, 6));
+            }
+            "authorizationEnabled" => {
+                enabled.extend(indent(&f.lines[..k], 6));
+                let line = f.lines[k].replacen("authorizationEnabled:", "enabled:", 1);
+                enabled.extend(indent(&[line], 6));
+                enabled.extend(indent(&f.lines[k + 1..], 6));
+            }
+            key => bail!("Region {} has an unknown defaults.kong.{}", region, key),
+        }
+    }
+    if enabled.iter().all(|l| !is_content(l)) {
+        enabled.push("      enabled: false".to_string());
+    }
+    let mut lines = indent(
+        &pre.into_iter()
+            .chain(trail)
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>(),
+        2,
+    );
+    lines.extend(apis);
+    lines.push("  kong:".to_string());
+    lines.push("    authorization:".to_string());
+    lines.extend(enabled);
+    Ok(lines)
+}
+
+/// Move `publiclyAccessible` into the `gate` block of a file that has both
+///
+/// Files without a gate are left alone, a gate needs kong and the manifest
+/// falls back to `publiclyAccessible` when no gate is configured.
+fn gate_public(_service: &str, data: &str) -> Result<String> {
+    let (preamble, mut blocks, trailing) = split(data);
+    let public = match blocks.iter().position(|b| b.key == "publiclyAccessible") {
+        Some(p) => p,
+        None => return Ok(data.to_string()),
+    };
+    let gate = match blocks.iter().position(|b| b.key == "gate") {
+        Some(g) => g,
+        None => return Ok(data.to_string()),
+    };
+    let doc: Value = serde_yaml::from_str(data)?;
+    let value = match doc["publiclyAccessible"].as_bool() {
+        Some(b) => b,
+        None => bail!("publiclyAccessible is not a boolean"),
+    };
+    let current = doc["gate"]["public"].as_bool();
+    if current.map_or(false, |c| c != value) {
+        bail!("publiclyAccessible and gate.public disagree, fix this by hand");
+    }
+
+    let pa = blocks[public].lines.clone();
+    let pk = pa.iter().position(|l| is_content(l)).unwrap_or(0);
+    // keep comments on publiclyAccessible with the new value
+    let trailing_comment = pa[pk].find(" #").map(|i| pa[pk][i..].to_string());
+    // only for the inline check
+    children(&blocks[gate])?;
+    let g = &mut blocks[gate];
+    let gk = key_line(g);
+    let depth = g.lines[gk + 1..]
+        .iter()
+        .find(|l| is_content(l))
+        .map_or(2, |l| indent_of(l));
+    let mut moved = indent(&pa[..pk], depth);
+    let existing = g.lines[gk + 1..]
+        .iter()
+        .position(|l| indent_of(l) == depth && l.trim_start().starts_with("public:"))
+        .map(|i| i + gk + 1);
+    match existing {
+        Some(i) => {
+            if trailing_comment.is_some() && !g.lines[i].contains(" #") {
+                g.lines[i].push_str(&trailing_comment.unwrap_or_default());
+            }
+            g.lines.splice(i..i, moved);
+        }
+        None => {
+            moved.push(format!(
+                "{}public: {}{}",
+                " ".repeat(depth),
+                value,
+                trailing_comment.unwrap_or_default()
+            ));
+            g.lines.splice(gk + 1..gk + 1, moved);
+        }
+    }
+    // comments below publiclyAccessible stay where they are
+    blocks[public].lines = pa[pk + 1..].to_vec();
+    let mut res = join(preamble, blocks, trailing).join("\n");
+    res.push('\n');
+    check_meaning(
+        data,
+        &res,
+        |k| {
+            if k == ".publiclyAccessible" {
+                ".gate.public".to_string()
+            } else {
+                k.to_string()
+            }
+        },
+        |_, _| false,
+    )?;
+    Ok(res)
+}
+
+/// Move the `kong` block of a file into `kongApis` under the name of the service
+///
+/// The single API was always named after its service, so `kong.x` becomes `kongApis.<service>.x`.
+/// Files with both are left for a human to merge.
+fn kong_apis(service: &str, data: &str) -> Result<String> {
+    let (preamble, mut blocks, trailing) = split(data);
+    let kong = match blocks.iter().position(|b| b.key == "kong") {
+        Some(k) => k,
+        None => return Ok(data.to_string()),
+    };
+    if blocks.iter().any(|b| b.key == "kongApis") {
+        bail!("kong and kongApis are both set, merge them by hand");
+    }
+    // only for the inline check
+    children(&blocks[kong])?;
+    let b = &mut blocks[kong];
+    let k = key_line(b);
+    let depth = b.lines[k + 1..]
+        .iter()
+        .find(|l| is_content(l))
+        .map_or(2, |l| indent_of(l));
+    let comment = b.lines[k].find(" #").map(|i| b.lines[k][i..].to_string());
+    let mut lines = b.lines[..k].to_vec();
+    lines.push(format!("kongApis:{}", comment.unwrap_or_default()));
+    lines.push(format!("{}{}:", " ".repeat(depth), service));
+    lines.extend(indent(&b.lines[k + 1..], depth));
+    b.key = "kongApis".into();
+    b.lines = lines;
+    let mut res = join(preamble, blocks, trailing).join("\n");
+    res.push('\n');
+    check_meaning(
+        data,
+        &res,
+        |k| {
+            if k == ".kong" || k.starts_with(".kong.") {
+                format!(".kongApis.{}{}", service, &k[".kong".len()..])
+            } else {
+                k.to_string()
+            }
+        },
+        |_, _| false,
+    )?;
+    Ok(res)
+}
+
+/// Refuse services that set `publiclyAccessible` and `gate.public` in different files
+///
+/// Moving `publiclyAccessible` into the gate of its own file would change which of them wins.
+fn gate_public_layers(files: &BTreeMap<PathBuf, String>) -> Result<()> {
+    let (mut public, mut gate) = (vec![], vec![]);
+    for (pth, data) in files {
+        let doc: Value = serde_yaml::from_str(data)?;
+        if !doc["publiclyAccessible"].is_null() {
+            public.push(pth);
+        }
+        if !doc["gate"]["public"].is_null() {
+            gate.push(pth);
+        }
+    }
+    for p in &public {
+        if let Some(g) = gate.iter().find(|g| *g != p) {
+            bail!(
+                "publiclyAccessible in {} and gate.public in {} are in different files, migrate them by hand",
+                p.display(),
+                g.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{migration, MIGRATIONS};
+    use maplit::btreemap;
+    use shipcat_definitions::Config;
+    use std::{collections::BTreeMap, env, fs, path::Path};
+
+    fn setup() {
+        let pwd = env::current_dir().unwrap();
+        let pth = fs::canonicalize(Path::new(&pwd).join("..").join("tests")).unwrap();
+        std::env::set_current_dir(pth).unwrap();
+    }
+
+    const LEGACY_CONF: &str = "# config
+defaults:
+  chart: base
+regions:
+- name: dev-uk
+  namespace: dev
+  # injected everywhere
+  env:
+    GLOBAL_EVAR: indeed
+  defaults:
+    kong:
+      # sso
+      authorization:
+        allowed_audiences:
+        - https://babylonhealth.com
+        allow_anonymous: false
+        allow_invalid_tokens: false
+        required_scopes: []
+        allow_cookies: false
+        enable_cookie_refresh: false
+      authorizationEnabled: true # everywhere
+  locations: []
+- name: dev-global
+  namespace: dev
+  env:
+    OTHER: \"1\"
+";
+
+    #[test]
+    fn region_defaults() {
+        let m = migration("region-defaults").unwrap();
+        let res = m.apply("", LEGACY_CONF).unwrap();
+        assert_eq!(
+            res,
+            "# config
+defaults:
+  chart: base
+regions:
+- name: dev-uk
+  namespace: dev
+  # injected everywhere
+  defaultsV2:
+    env:
+      GLOBAL_EVAR: indeed
+    kongApis:
+      defaults:
+        # sso
+        authorization:
+          allowed_audiences:
+          - https://babylonhealth.com
+          allow_anonymous: false
+          allow_invalid_tokens: false
+          required_scopes: []
+          allow_cookies: false
+          enable_cookie_refresh: false
+    kong:
+      authorization:
+        enabled: true # everywhere
+  locations: []
+- name: dev-global
+  namespace: dev
+  defaultsV2:
+    env:
+      OTHER: \"1\"
+"
+        );
+        // idempotent
+        assert_eq!(m.apply("", &res).unwrap(), res);
+    }
+
+    #[test]
+    fn region_defaults_conflict() {
+        let conf = "regions:\n- name: dev-uk\n  env:\n    A: b\n  defaultsV2:\n    env:\n      C: d\n";
+        assert!(migration("region-defaults").unwrap().apply("", conf).is_err());
+    }
+
+    #[test]
+    fn gate_public() {
+        let m = migration("gate-public").unwrap();
+        let mf = "name: webapp\n# open to the world\npubliclyAccessible: true\ngate:\n  websockets: true\n";
+        let res = m.apply("webapp", mf).unwrap();
+        assert_eq!(
+            res,
+            "name: webapp\ngate:\n  # open to the world\n  public: true\n  websockets: true\n"
+        );
+        assert_eq!(m.apply("webapp", &res).unwrap(), res);
+
+        let nogate = "name: webapp\npubliclyAccessible: true\n";
+        assert_eq!(m.apply("webapp", nogate).unwrap(), nogate);
+
+        let disagree = "publiclyAccessible: true\ngate:\n  public: false\n";
+        assert!(m.apply("webapp", disagree).is_err());
+    }
+
+    #[test]
+    fn gate_public_layers() {
+        let check = migration("gate-public").unwrap().sources.unwrap();
+        let files = |overrides: &str| {
+            btreemap! {
+                "services/webapp/manifest.yml".into() => "publiclyAccessible: false\ngate:\n  websockets: true\n".to_string(),
+                "services/webapp/dev-uk.yml".into() => overrides.to_string(),
+            }
+        };
+        assert!(check(&files("replicaCount: 2\n")).is_ok());
+        assert!(check(&files("gate:\n  public: true\n")).is_err());
+    }
+
+    #[tokio::test]
+    async fn gate_public_merged() {
+        setup();
+        let conf = Config::read().await.unwrap();
+        let m = migration("gate-public").unwrap();
+        let dir = Path::new(".").join("services").join("fake-ask");
+        let mut files = BTreeMap::new();
+        for f in &["manifest.yml", "dev.yml", "dev-uk.yml"] {
+            files.insert(dir.join(f), fs::read_to_string(dir.join(f)).unwrap());
+        }
+        let manifest = dir.join("manifest.yml");
+        files
+            .get_mut(&manifest)
+            .unwrap()
+            .push_str("publiclyAccessible: true\ngate:\n  websockets: true\n");
+
+        let migrated = m.apply("fake-ask", &files[&manifest]).unwrap();
+        let rewritten = btreemap! { manifest.clone() => migrated };
+        m.check_service("fake-ask", &files, &rewritten, &conf)
+            .await
+            .unwrap();
+
+        // dropping publiclyAccessible without moving it makes the service private
+        let dropped = files[&manifest].replace("publiclyAccessible: true\n", "");
+        let rewritten = btreemap! { manifest => dropped };
+        let err = m
+            .check_service("fake-ask", &files, &rewritten, &conf)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("in dev-uk"));
+    }
+
+    #[test]
+    fn kong_apis() {
+        let m = migration("kong-apis").unwrap();
+        let mf = "name: webapp\n# api\nkong: # public\n  uris: /webapp\n  hosts:\n  - webapp\n\nport: 80\n";
+        let res = m.apply("webapp", mf).unwrap();
+        assert_eq!(
+            res,
+            "name: webapp\n# api\nkongApis: # public\n  webapp:\n    uris: /webapp\n    hosts:\n    - webapp\n\nport: 80\n"
+        );
+        assert_eq!(m.apply("webapp", &res).unwrap(), res);
+
+        // overrides are moved the same way
+        let disabled = "kong:\n  enabled: false\n";
+        assert_eq!(
+            m.apply("webapp", disabled).unwrap(),
+            "kongApis:\n  webapp:\n    enabled: false\n"
+        );
+
+        assert!(m.apply("webapp", "kong: {}\n").is_err());
+        let both = "kong:\n  uris: /webapp\nkongApis:\n  webapp-v2:\n    uris: /webapp/v2\n";
+        assert!(m.apply("webapp", both).is_err());
+    }
+
+    #[tokio::test]
+    async fn kong_apis_region_defaults() {
+        setup();
+        let conf = Config::read().await.unwrap();
+        let m = migration("kong-apis").unwrap();
+        let dir = Path::new(".").join("services").join("fake-ask");
+        let mut files = BTreeMap::new();
+        for f in &["manifest.yml", "dev.yml", "dev-uk.yml"] {
+            files.insert(dir.join(f), fs::read_to_string(dir.join(f)).unwrap());
+        }
+        let manifest = dir.join("manifest.yml");
+        let migrated = m.apply("fake-ask", &files[&manifest]).unwrap();
+        assert!(migrated.contains("kongApis:\n  fake-ask:\n    uris: /ai-auth\n"));
+
+        // dev-uk sets `kong` defaults, which only apply to the single API
+        let rewritten = btreemap! { manifest => migrated };
+        let err = m
+            .check_service("fake-ask", &files, &rewritten, &conf)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("in dev-uk"));
+    }
+
+    #[test]
+    fn unknown_migration() {
+        assert!(migration("nope").is_err());
+        assert!(MIGRATIONS.iter().all(|m| migration(m.name).is_ok()));
+    }
+}
@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use shipcat_definitions::{
     structs::{metadata::SlackChannel, sentry::Sentry},
     Result,
@@ -14,7 +15,7 @@ use crate::util::Build;
 /// if you find sentry too noisy you are able to mute it with true
 ///   silent: true
 /// ```
-#[derive(Debug, Default, Clone, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(deny_unknown_fields, rename_all = "camelCase")]
 pub struct SentrySource {
@@ -0,0 +1,335 @@
+use git2::{ObjectType, Oid, Repository, TreeEntry};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use shipcat_definitions::{Config, Result, ResultExt};
+
+/// Where manifest files are read from
+///
+/// Either the working tree, or a commit read straight from the git object database,
+/// so that older states can be loaded without touching the working tree.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Tree {
+    /// Files in the current directory
+    Worktree,
+    /// Files in a commit of the repository containing the current directory
+    Commit(Commit),
+    /// Files in the current directory, with some replaced by new contents
+    ///
+    /// Used to load manifests with rewritten files before writing them.
+    Overlay(BTreeMap<PathBuf, String>),
+}
+
+impl Default for Tree {
+    fn default() -> Self {
+        Tree::Worktree
+    }
+}
+
+/// A commit opened once for reading many files
+///
+/// Keeps the repository, the tree of the commit, and where the current directory
+/// sits in the repository when the commit was opened.
+#[derive(Clone)]
+pub struct Commit {
+    sha: String,
+    tree: Oid,
+    prefix: PathBuf,
+    repo: Arc<Mutex<Repository>>,
+}
+
+impl fmt::Debug for Commit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Commit({})", self.sha)
+    }
+}
+
+impl PartialEq for Commit {
+    fn eq(&self, other: &Self) -> bool {
+        self.sha == other.sha && self.prefix == other.prefix
+    }
+}
+
+impl Commit {
+    fn open(rev: &str) -> Result<Self> {
+        let repo =
+            Repository::discover(".").chain_err(|| "Failed to find a git repository for the manifests")?;
+        let commit = repo
+            .revparse_single(rev)
+            .and_then(|o| o.peel_to_commit())
+            .chain_err(|| format!("Failed to resolve git revision {}", rev))?;
+        let (sha, tree) = (commit.id().to_string(), commit.tree_id());
+        let prefix = workdir_prefix(&repo)?;
+        drop(commit);
+        Ok(Commit {
+            sha,
+            tree,
+            prefix,
+            repo: Arc::new(Mutex::new(repo)),
+        })
+    }
+
+    /// Run `f` with the tree entry at a path, None if nothing is there
+    fn with_entry<T, F>(&self, path: &Path, f: F) -> Result<T>
+    where
+        F: for<'r> FnOnce(&'r Repository, Option<TreeEntry<'r>>) -> Result<T>,
+    {
+        let pth = repo_path(&self.prefix, path)?;
+        let repo = self.repo.lock().expect("git repository lock is not poisoned");
+        let tree = repo
+            .find_tree(self.tree)
+            .chain_err(|| format!("Failed to find git commit {}", self.sha))?;
+        let entry = tree.get_path(&pth).ok();
+        f(&repo, entry)
+    }
+}
+
+impl Tree {
+    /// The commit a git revision like `origin/master`, `HEAD~1` or a sha points to
+    pub fn revision(rev: &str) -> Result<Self> {
+        Ok(Tree::Commit(Commit::open(rev)?))
+    }
+
+    /// The working tree with some files replaced by new contents
+    pub fn overlay(files: BTreeMap<PathBuf, String>) -> Self {
+        Tree::Overlay(files.into_iter().map(|(p, d)| (normalise(&p), d)).collect())
+    }
+
+    /// Contents of a file, or None if it does not exist
+    pub fn read(&self, path: &Path) -> Result<Option<String>> {
+        match self {
+            Tree::Overlay(files) => match files.get(&normalise(path)) {
+                Some(data) => Ok(Some(data.clone())),
+                None => Tree::Worktree.read(path),
+            },
+            Tree::Worktree => {
+                if !path.is_file() {
+                    return Ok(None);
+                }
+                Ok(Some(std::fs::read_to_string(path)?))
+            }
+            Tree::Commit(c) => c.with_entry(path, |repo, entry| {
+                let entry = match entry {
+                    Some(e) => e,
+                    None => return Ok(None),
+                };
+                let object = entry.to_object(repo).chain_err(|| "Failed to read git object")?;
+                match object.as_blob() {
+                    Some(blob) => match std::str::from_utf8(blob.content()) {
+                        Ok(data) => Ok(Some(data.to_string())),
+                        Err(_) => bail!("{} is not utf-8 at {}", path.display(), c.sha),
+                    },
+                    None => Ok(None),
+                }
+            }),
+        }
+    }
+
+    pub fn is_file(&self, path: &Path) -> bool {
+        match self {
+            Tree::Worktree => path.is_file(),
+            Tree::Overlay(files) => files.contains_key(&normalise(path)) || path.is_file(),
+            Tree::Commit(_) => self.entry_kind(path) == Some(ObjectType::Blob),
+        }
+    }
+
+    pub fn is_dir(&self, path: &Path) -> bool {
+        match self {
+            Tree::Worktree | Tree::Overlay(_) => path.is_dir(),
+            Tree::Commit(_) => self.entry_kind(path) == Some(ObjectType::Tree),
+        }
+    }
+
+    /// Sorted names of the directories in a directory
+    pub fn dirs(&self, path: &Path) -> Result<Vec<String>> {
+        let mut res = match self {
+            Tree::Worktree | Tree::Overlay(_) => std::fs::read_dir(path)?
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+                .filter_map(|e| e.file_name().to_str().map(String::from))
+                .collect::<Vec<_>>(),
+            Tree::Commit(c) => c.with_entry(path, |repo, entry| {
+                let dir = match entry {
+                    Some(e) => e.to_object(repo).chain_err(|| "Failed to read git object")?,
+                    None => bail!("{} does not exist at {}", path.display(), c.sha),
+                };
+                match dir.as_tree() {
+                    Some(t) => Ok(t
+                        .iter()
+                        .filter(|e| e.kind() == Some(ObjectType::Tree))
+                        .filter_map(|e| e.name().map(String::from))
+                        .collect()),
+                    None => bail!("{} is not a directory at {}", path.display(), c.sha),
+                }
+            })?,
+        };
+        res.sort();
+        Ok(res)
+    }
+
+    /// Write a directory and everything in it to `dest`, replacing what was there
+    ///
+    /// Only commits need exporting, the working tree is already on disk.
+    pub fn export(&self, path: &Path, dest: &Path) -> Result<()> {
+        let c = match self {
+            Tree::Worktree | Tree::Overlay(_) => bail!("{} is already in the working tree", path.display()),
+            Tree::Commit(c) => c,
+        };
+        c.with_entry(path, |repo, entry| {
+            let dir = match entry {
+                Some(e) => e.to_object(repo).chain_err(|| "Failed to read git object")?,
+                None => bail!("{} does not exist at {}", path.display(), c.sha),
+            };
+            let dir = match dir.into_tree() {
+                Ok(t) => t,
+                Err(_) => bail!("{} is not a directory at {}", path.display(), c.sha),
+            };
+            if dest.exists() {
+                std::fs::remove_dir_all(dest)?;
+            }
+            export_tree(repo, &dir, dest)
+        })
+    }
+
+    /// The config from `shipcat.conf` and `teams.yml`, with placeholders like `Config::read`
+    pub fn config(&self) -> Result<Config> {
+        let conf = match self.read(Path::new("shipcat.conf"))? {
+            Some(data) => data,
+            None => bail!("Config file shipcat.conf does not exist"),
+        };
+        let teams = match self.read(Path::new("teams.yml"))? {
+            Some(data) => data,
+            None => bail!("Teams file teams.yml does not exist"),
+        };
+        Config::parse(&conf, &teams)
+    }
+
+    fn entry_kind(&self, path: &Path) -> Option<ObjectType> {
+        if let Tree::Commit(c) = self {
+            c.with_entry(path, |_, entry| Ok(entry.and_then(|e| e.kind())))
+                .ok()?
+        } else {
+            None
+        }
+    }
+}
+
+/// A relative path without `.` components, as overlay files are keyed
+fn normalise(path: &Path) -> PathBuf {
+    path.components().filter(|c| *c != Component::CurDir).collect()
+}
+
+fn export_tree(repo: &Repository, tree: &git2::Tree, dest: &Path) -> Result<()> {
+    std::fs::create_dir_all(dest)?;
+    for entry in tree.iter() {
+        let name = match entry.name() {
+            Some(n) => n,
+            None => continue,
+        };
+        let object = entry.to_object(repo).chain_err(|| "Failed to read git object")?;
+        if let Some(t) = object.as_tree() {
+            export_tree(repo, t, &dest.join(name))?;
+        } else if let Some(blob) = object.as_blob() {
+            std::fs::write(dest.join(name), blob.content())?;
+        }
+    }
+    Ok(())
+}
+
+/// Path of the current directory in the repository
+///
+/// Manifests do not have to live at the root of the repository.
+fn workdir_prefix(repo: &Repository) -> Result<PathBuf> {
+    let root = match repo.workdir() {
+        Some(w) => w.canonicalize()?,
+        None => bail!("Manifests cannot be loaded from a bare repository"),
+    };
+    let pwd = std::env::current_dir()?.canonicalize()?;
+    match pwd.strip_prefix(&root) {
+        Ok(p) => Ok(p.to_path_buf()),
+        Err(_) => bail!(
+            "{} is not inside the repository at {}",
+            pwd.display(),
+            root.display()
+        ),
+    }
+}
+
+/// Path of a file relative to the current directory as a path in the repository
+fn repo_path(prefix: &Path, path: &Path) -> Result<PathBuf> {
+    let mut res = prefix.to_path_buf();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::Normal(p) => res.push(p),
+            _ => bail!("{} must be a relative path inside the repository", path.display()),
+        }
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tree;
+    use shipcat_definitions::ConfigState;
+    use std::{env, fs, path::Path};
+
+    fn setup() {
+        let pwd = env::current_dir().unwrap();
+        let pth = fs::canonicalize(Path::new(&pwd).join("..").join("tests")).unwrap();
+        std::env::set_current_dir(pth).unwrap();
+    }
+
+    #[test]
+    fn worktree() {
+        setup();
+        let tree = Tree::Worktree;
+        assert!(tree.is_dir(Path::new("./services/fake-ask")));
+        assert!(tree.is_file(Path::new("./services/fake-ask/manifest.yml")));
+        assert_eq!(
+            tree.read(Path::new("./services/fake-ask/nope.yml")).unwrap(),
+            None
+        );
+        let dirs = tree.dirs(Path::new("./services")).unwrap();
+        assert_eq!(dirs.first().map(String::as_str), Some("external"));
+        assert!(tree.config().unwrap().get_region("dev-uk").is_ok());
+    }
+
+    #[tokio::test]
+    async fn commit() {
+        setup();
+        // HEAD of this repository, with the manifests under tests/
+        let tree = Tree::revision("HEAD").unwrap();
+        assert!(tree.is_dir(Path::new("./services/fake-ask")));
+        assert!(tree.is_file(Path::new("./services/fake-ask/manifest.yml")));
+        assert!(!tree.is_file(Path::new("./services/fake-ask")));
+        assert_eq!(
+            tree.read(Path::new("./services/fake-ask/nope.yml")).unwrap(),
+            None
+        );
+        assert!(tree.read(Path::new("../tests/shipcat.conf")).is_err());
+        let dirs = tree.dirs(Path::new("./services")).unwrap();
+        assert_eq!(dirs.first().map(String::as_str), Some("external"));
+
+        let (conf, reg) = tree
+            .config()
+            .unwrap()
+            .for_context(ConfigState::Base, "dev-uk")
+            .await
+            .unwrap();
+        let mf = crate::load_manifest_at(&tree, "fake-ask", &conf, &reg)
+            .await
+            .unwrap();
+        assert_eq!(mf.name, "fake-ask");
+        assert_eq!(mf.region, "dev-uk");
+
+        let charts = env::temp_dir().join("shipcat-tree-test-charts");
+        tree.export(Path::new("charts"), &charts).unwrap();
+        assert!(charts.join("base").join("Chart.yaml").is_file());
+        fs::remove_dir_all(&charts).unwrap();
+    }
+}
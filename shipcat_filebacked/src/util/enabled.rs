@@ -1,3 +1,4 @@
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
 use std::collections::BTreeMap;
 
 use merge::Merge;
@@ -23,7 +24,7 @@ use super::Build;
 ///     value: 3
 /// bar: ~
 /// ```
-#[derive(Deserialize, Default, Clone, PartialEq, Merge)]
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Merge)]
 #[cfg_attr(test, derive(Debug, Copy))]
 #[serde(default, deny_unknown_fields)]
 pub struct Enabled<T: Merge> {
@@ -33,6 +34,22 @@ pub struct Enabled<T: Merge> {
     pub item: T,
 }
 
+/// Schema of the inner struct with an extra optional `enabled` property
+impl<T: Merge + JsonSchema> JsonSchema for Enabled<T> {
+    fn schema_name() -> String {
+        format!("Enabled_{}", T::schema_name())
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let mut schema = T::json_schema(gen).into_object();
+        schema
+            .object()
+            .properties
+            .insert("enabled".into(), gen.subschema_for::<bool>());
+        schema.into()
+    }
+}
+
 /// Builds the inner struct unless enabled is explicitly false.
 impl<S: Build<B, P> + Merge, B, P> Build<Option<B>, P> for Enabled<S> {
     fn build(self, params: &P) -> Result<Option<B>> {
@@ -46,7 +63,7 @@ impl<S: Build<B, P> + Merge, B, P> Build<Option<B>, P> for Enabled<S> {
 /// EnabledMap is a map where each value is wrapped in an Enabled.
 ///
 /// It can be built into a map which flattens the Enabled wrappers, so disabled values are excluded.
-#[derive(Deserialize, Default, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, JsonSchema)]
 #[cfg_attr(test, derive(Debug))]
 pub struct EnabledMap<K: Clone + std::hash::Hash + Ord, V: Clone + Default + Merge>(BTreeMap<K, Enabled<V>>);
 